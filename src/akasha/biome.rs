@@ -0,0 +1,74 @@
+use crate::{tile::blocks, world::BlockId};
+
+/// Broad climate of a column, picked from the low frequency biome noise
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Biome {
+    Desert,
+    Plains,
+    Forest,
+    Mountains,
+}
+
+impl Biome {
+    // Ordered by biome noise value, each biome covers an equal slice of 0..1
+    const ORDER: [Biome; 4] = [
+        Biome::Desert,
+        Biome::Plains,
+        Biome::Forest,
+        Biome::Mountains,
+    ];
+
+    /// Biome for a normalized (roughly 0..1) biome noise value
+    pub fn from_noise(value: f32) -> Self {
+        let slice = (value * Self::ORDER.len() as f32) as i32;
+        Self::ORDER[slice.clamp(0, Self::ORDER.len() as i32 - 1) as usize]
+    }
+
+    /// Block placed on top of the terrain
+    pub fn surface_block(&self) -> BlockId {
+        match self {
+            Biome::Desert => blocks::SAND,
+            _ => blocks::GRASS,
+        }
+    }
+
+    /// Block filling the few layers below the surface
+    pub fn subsurface_block(&self) -> BlockId {
+        match self {
+            Biome::Desert => blocks::SAND,
+            _ => blocks::DIRT,
+        }
+    }
+
+    /// Chance for each candidate column in a chunk to grow a tree
+    pub fn tree_chance(&self) -> f64 {
+        match self {
+            Biome::Desert => 0.0,
+            Biome::Plains => 0.15,
+            Biome::Forest => 0.6,
+            Biome::Mountains => 0.25,
+        }
+    }
+
+    fn height_amplitude(&self) -> f32 {
+        match self {
+            Biome::Desert => 0.5,
+            Biome::Plains => 0.8,
+            Biome::Forest => 1.0,
+            Biome::Mountains => 1.6,
+        }
+    }
+
+    /// Scale applied to the terrain height. Interpolated between the centers
+    /// of neighbouring biomes so there are no cliffs at biome borders.
+    pub fn height_amplitude_at(value: f32) -> f32 {
+        let count = Self::ORDER.len();
+        let position = (value * count as f32 - 0.5).clamp(0.0, (count - 1) as f32);
+        let lower = position.floor() as usize;
+        let upper = (lower + 1).min(count - 1);
+        let t = position - lower as f32;
+        let lower_amplitude = Self::ORDER[lower].height_amplitude();
+        let upper_amplitude = Self::ORDER[upper].height_amplitude();
+        lower_amplitude + (upper_amplitude - lower_amplitude) * t
+    }
+}
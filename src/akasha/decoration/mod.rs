@@ -1,7 +1,9 @@
 use std::hash::Hash;
 
 use super::locus_into_rng;
+use crate::world::Neighborhood;
 
+pub mod ore_vein;
 pub mod tree;
 
 #[derive(Hash)]
@@ -21,6 +23,13 @@ pub trait Decoration {
     where
         Self: Sized;
 
+    /// Write the decoration's blocks into the world
+    fn decorate(&self, neighborhood: &mut Neighborhood);
+
+    /// Furthest the decoration writes from its origin block, in blocks along
+    /// x, y and z
+    fn extent(&self) -> (i32, i32, i32);
+
     fn from_locus(locus: Self::Locus) -> Self
     where
         Self: Sized,
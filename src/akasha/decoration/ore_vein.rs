@@ -0,0 +1,94 @@
+use crate::{
+    akasha::decoration::{Decoration, WorldPos},
+    tile::blocks,
+    world::{BlockId, Neighborhood},
+};
+
+pub struct OreVein {
+    vein_x: i32,
+    vein_y: i32,
+    vein_z: i32,
+    block_id: BlockId,
+    // Number of blob centers placed along the vein
+    length: u32,
+    direction: [f32; 3],
+}
+
+impl OreVein {
+    // Coal is found everywhere, iron only below y = 0 and diamonds are a rare
+    // find below y = -64
+    fn ore_for_depth<R: rand::Rng>(rng: &mut R, y: i32) -> BlockId {
+        if y < -64 && rng.random_bool(0.15) {
+            blocks::DIAMOND_ORE
+        } else if y < 0 && rng.random_bool(0.5) {
+            blocks::IRON_ORE
+        } else {
+            blocks::COAL_ORE
+        }
+    }
+}
+
+impl Decoration for OreVein {
+    type Locus = WorldPos;
+    fn decorate(&self, neighborhood: &mut Neighborhood) {
+        // Walk along the direction, placing a small blob at every step
+        for step in 0..self.length {
+            let center_x =
+                self.vein_x + (self.direction[0] * step as f32).round() as i32;
+            let center_y =
+                self.vein_y + (self.direction[1] * step as f32).round() as i32;
+            let center_z =
+                self.vein_z + (self.direction[2] * step as f32).round() as i32;
+            for dx in -1..=1_i32 {
+                for dy in -1..=1_i32 {
+                    for dz in -1..=1_i32 {
+                        if dx.abs() + dy.abs() + dz.abs() > 1 {
+                            continue;
+                        }
+                        let (x, y, z) = (center_x + dx, center_y + dy, center_z + dz);
+                        // Ores only ever replace stone
+                        if neighborhood.get_block(x, y, z) == blocks::STONE {
+                            neighborhood.set_block(x, y, z, self.block_id);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // The last blob center plus the one block blob around it
+    fn extent(&self) -> (i32, i32, i32) {
+        let last_step = self.length.saturating_sub(1) as f32;
+        let reach = |d: f32| (d.abs() * last_step).round() as i32 + 1;
+        (
+            reach(self.direction[0]),
+            reach(self.direction[1]),
+            reach(self.direction[2]),
+        )
+    }
+
+    fn from_rng<R: rand::Rng>(rng: &mut R, locus: &Self::Locus) -> Self
+    where
+        Self: Sized,
+    {
+        let block_id = Self::ore_for_depth(rng, locus.y);
+        // Random orientation, normalized so each step moves about one block
+        let mut direction = [
+            rng.random_range(-1.0..1.0_f32),
+            rng.random_range(-1.0..1.0_f32),
+            rng.random_range(-1.0..1.0_f32),
+        ];
+        let length = direction.iter().map(|d| d * d).sum::<f32>().sqrt();
+        if length > 0.0 {
+            direction = direction.map(|d| d / length);
+        }
+        OreVein {
+            vein_x: locus.x,
+            vein_y: locus.y,
+            vein_z: locus.z,
+            block_id,
+            length: rng.random_range(2..=5),
+            direction,
+        }
+    }
+}
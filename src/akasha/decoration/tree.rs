@@ -1,6 +1,9 @@
 use rand::Rng;
 
-use crate::akasha::decoration::{Decoration, WorldPos};
+use crate::{
+    akasha::decoration::{Decoration, WorldPos},
+    world::Neighborhood,
+};
 
 pub struct Tree {
     tree_x: i32,
@@ -11,24 +14,20 @@ pub struct Tree {
 
 impl Decoration for Tree {
     type Locus = WorldPos;
-    /*fn decorate<'a>(self, neighborhood: &'a mut crate::world::Neighborhood) {
-        let Tree {
-            tree_x,
-            tree_y,
-            tree_z,
-            tree_height,
-        } = self;
-
-        let tree_height = tree_height as i32;
+    fn decorate(&self, neighborhood: &mut Neighborhood) {
+        let tree_x = self.tree_x;
+        let tree_y = self.tree_y;
+        let tree_z = self.tree_z;
+        let tree_height = self.tree_height as i32;
 
         for dy in 0..(tree_height / 2) {
-            for dx in -2..=2 {
-                for dz in -2..=2 {
-                    if (dx as i32).abs() + (dz as i32).abs() <= 2 {
+            for dx in -2..=2_i32 {
+                for dz in -2..=2_i32 {
+                    if dx.abs() + dz.abs() <= 2 {
                         neighborhood.set_block(
-                            (tree_x + dx) as i32,
-                            (tree_y + tree_height - dy) as i32,
-                            (tree_z + dz) as i32,
+                            tree_x + dx,
+                            tree_y + tree_height - dy,
+                            tree_z + dz,
                             6, // Assuming block ID 6 is a leaf
                         );
                     }
@@ -36,13 +35,13 @@ impl Decoration for Tree {
             }
         }
         for dy in 0..2 {
-            for dx in -1..=1 {
-                for dz in -1..=1 {
-                    if (dx as i32).abs() + (dz as i32).abs() <= 2 {
+            for dx in -1..=1_i32 {
+                for dz in -1..=1_i32 {
+                    if dx.abs() + dz.abs() <= 2 {
                         neighborhood.set_block(
-                            (tree_x + dx) as i32,
-                            (tree_y + tree_height + dy + 1) as i32,
-                            (tree_z + dz) as i32,
+                            tree_x + dx,
+                            tree_y + tree_height + dy + 1,
+                            tree_z + dz,
                             6, // Assuming block ID 6 is a leaf
                         );
                     }
@@ -51,13 +50,18 @@ impl Decoration for Tree {
         }
         for dy in 0..tree_height {
             neighborhood.set_block(
-                tree_x as i32,
-                (tree_y + dy) as i32,
-                tree_z as i32,
+                tree_x,
+                tree_y + dy,
+                tree_z,
                 5, // Assuming block ID 5 is a log
             );
         }
-    }*/
+    }
+
+    // Leaves spread two blocks around the trunk and stack two blocks above it
+    fn extent(&self) -> (i32, i32, i32) {
+        (2, self.tree_height as i32 + 2, 2)
+    }
 
     fn from_rng<R: rand::Rng>(rng: &mut R, locus: &Self::Locus) -> Self
     where
@@ -0,0 +1,129 @@
+use std::sync::Arc;
+
+use crate::{
+    akasha::Akasha,
+    tile::blocks,
+    world::{BlockId, CHUNK_SIZE, CHUNK_SIZE_X, ChunkData, WorldConfig},
+};
+
+/// Turns a chunk position into its terrain, before any decorations
+pub trait WorldGenerator: Send + Sync {
+    /// Generates the chunk's terrain, taking whatever noise it needs from
+    /// `akasha`
+    fn generate(
+        &self,
+        x: i32,
+        y: i32,
+        z: i32,
+        akasha: &Arc<Akasha>,
+        config: &WorldConfig,
+    ) -> ChunkData;
+
+    /// Rough height of the surface of the column at (x, z), where
+    /// World::surface_height starts looking for the actual blocks. Defaults to
+    /// the height of the noise terrain.
+    fn surface_guess(&self, akasha: &Arc<Akasha>, x: i32, z: i32) -> i32 {
+        let akasha_chunk = Akasha::ensure_chunk(
+            akasha,
+            x.div_euclid(CHUNK_SIZE_X),
+            0,
+            z.div_euclid(CHUNK_SIZE_X),
+        );
+        let local_x = x.rem_euclid(CHUNK_SIZE_X);
+        let local_z = z.rem_euclid(CHUNK_SIZE_X);
+        akasha_chunk.read().unwrap().noises.target_height
+            [(local_x + local_z * CHUNK_SIZE_X) as usize]
+    }
+
+    /// Whether the akasha's trees and ores are placed on the terrain. They
+    /// are laid out on the noise terrain, so generators with their own
+    /// surface usually leave them out.
+    fn decorates(&self) -> bool {
+        true
+    }
+}
+
+/// The noise terrain with biomes, caves and a flat sea
+pub struct DefaultGenerator;
+
+impl WorldGenerator for DefaultGenerator {
+    fn generate(
+        &self,
+        x: i32,
+        y: i32,
+        z: i32,
+        akasha: &Arc<Akasha>,
+        config: &WorldConfig,
+    ) -> ChunkData {
+        let akasha_chunk = Akasha::ensure_chunk(akasha, x, y, z);
+        ChunkData::new(x, y, z, &akasha_chunk.read().unwrap().noises, config)
+    }
+}
+
+/// Stone up to a fixed height, then a few layers of dirt and one of grass.
+/// Needs no noise, so no akasha chunks are made for it.
+pub struct FlatGenerator {
+    /// Height of the grass layer
+    pub surface_height: i32,
+    /// Layers of dirt right below the grass
+    pub dirt_depth: i32,
+}
+
+impl Default for FlatGenerator {
+    // Same surface as the default terrain at height 0
+    fn default() -> Self {
+        FlatGenerator {
+            surface_height: 3,
+            dirt_depth: 2,
+        }
+    }
+}
+
+impl FlatGenerator {
+    fn block_at(&self, global_y: i32) -> BlockId {
+        if global_y == self.surface_height {
+            blocks::GRASS
+        } else if global_y > self.surface_height {
+            0
+        } else if global_y >= self.surface_height - self.dirt_depth {
+            blocks::DIRT
+        } else {
+            blocks::STONE
+        }
+    }
+}
+
+impl WorldGenerator for FlatGenerator {
+    fn generate(
+        &self,
+        _x: i32,
+        y: i32,
+        _z: i32,
+        _akasha: &Arc<Akasha>,
+        _config: &WorldConfig,
+    ) -> ChunkData {
+        let mut block_ids = [0; CHUNK_SIZE as usize];
+        // Every column is the same, so fill whole layers at once
+        let layer_size = (CHUNK_SIZE_X * CHUNK_SIZE_X) as usize;
+        for local_y in 0..CHUNK_SIZE_X {
+            let block_id = self.block_at(y * CHUNK_SIZE_X + local_y);
+            for z in 0..CHUNK_SIZE_X as usize {
+                let row = local_y as usize * CHUNK_SIZE_X as usize + z * layer_size;
+                block_ids[row..row + CHUNK_SIZE_X as usize].fill(block_id);
+            }
+        }
+        ChunkData {
+            block_ids,
+            metadata: [0; CHUNK_SIZE as usize],
+            light_levels: [0; CHUNK_SIZE as usize],
+        }
+    }
+
+    fn surface_guess(&self, _akasha: &Arc<Akasha>, _x: i32, _z: i32) -> i32 {
+        self.surface_height
+    }
+
+    fn decorates(&self) -> bool {
+        false
+    }
+}
@@ -4,12 +4,20 @@ use std::{
     sync::{Arc, Mutex, RwLock},
 };
 
-use rand::SeedableRng;
+use rand::{Rng, SeedableRng};
 use simdnoise::NoiseBuilder;
 
-use crate::{akasha::decoration::tree::Tree, world::CHUNK_SIZE_X};
+use crate::{
+    akasha::{
+        biome::Biome,
+        decoration::{Decoration, WorldPos, ore_vein::OreVein, tree::Tree},
+    },
+    world::CHUNK_SIZE_X,
+};
 
+pub mod biome;
 pub mod decoration;
+pub mod generator;
 
 fn locus_into_seed<T: Hash>(locus: T) -> u64 {
     let mut hasher = std::collections::hash_map::DefaultHasher::new();
@@ -22,28 +30,119 @@ fn locus_into_rng<T: Hash>(locus: &T) -> rand::rngs::StdRng {
     rand::rngs::StdRng::seed_from_u64(seed)
 }
 
+/// Parameters of one fractal noise layer
+#[derive(Clone, Copy, Debug)]
+pub struct NoiseConfig {
+    pub freq: f32,
+    pub octaves: u8,
+    pub gain: f32,
+    pub lacunarity: f32,
+    pub seed: i32,
+}
+
+/// Shape of the generated terrain. The surface height of a column blends the
+/// base and mountain noises, weighted by the variance noise.
+#[derive(Clone, Copy, Debug)]
+pub struct TerrainConfig {
+    pub base: NoiseConfig,
+    pub mountains: NoiseConfig,
+    pub dirt: NoiseConfig,
+    pub variance: NoiseConfig,
+    /// Multiplies the biome height amplitude, 0 gives a flat world and values
+    /// above 1 amplify the mountains
+    pub height_scale: f32,
+    /// Highest y filled with water wherever the terrain is lower
+    pub sea_level: i32,
+    /// Layers of subsurface blocks between the stone and the surface block
+    pub dirt_depth: i32,
+}
+
+impl TerrainConfig {
+    /// Stone up to y = 0 everywhere with the default layers on top, so the
+    /// surface block is at `surface_height(0)`. Only the height noise is
+    /// flattened, caves, ores and trees still generate. FlatGenerator makes
+    /// plain layers without them.
+    pub fn flat() -> Self {
+        TerrainConfig {
+            height_scale: 0.0,
+            ..Self::default()
+        }
+    }
+
+    /// Height of the surface block above a column whose stone ends at
+    /// `stone_height`
+    pub fn surface_height(&self, stone_height: i32) -> i32 {
+        stone_height + self.dirt_depth + 1
+    }
+}
+
+impl Default for TerrainConfig {
+    fn default() -> Self {
+        TerrainConfig {
+            base: NoiseConfig {
+                freq: 0.0001,
+                octaves: 8,
+                gain: 2.2,
+                lacunarity: 2.0,
+                seed: 42,
+            },
+            mountains: NoiseConfig {
+                freq: 0.01 / 64000.0,
+                octaves: 12,
+                gain: 2.3,
+                lacunarity: 2.2,
+                seed: 42,
+            },
+            dirt: NoiseConfig {
+                freq: 0.0001,
+                octaves: 1,
+                gain: 2.0,
+                lacunarity: 2.0,
+                seed: 44,
+            },
+            variance: NoiseConfig {
+                freq: 1.0 / 2000.0,
+                octaves: 1,
+                gain: 1.0,
+                lacunarity: 1.0,
+                seed: 43,
+            },
+            height_scale: 1.0,
+            sea_level: 0,
+            dirt_depth: 2,
+        }
+    }
+}
+
+/// Per-column terrain noise of a chunk, shared by the akasha and the world
+/// generation so the terrain height formula only lives here
 pub struct ChunkNoises {
     pub noise: Vec<f32>,
     pub noise_mountains: Vec<f32>,
     pub dirt_noise: Vec<f32>,
     pub variance: Vec<f32>,
+    /// Normalized biome noise, roughly in 0..1
+    pub biome_noise: Vec<f32>,
 
+    pub biomes: Vec<Biome>,
+    /// Height of the stone surface of each column, blended from the base,
+    /// mountain and variance noises
     pub target_height: Vec<i32>,
 }
 
 impl ChunkNoises {
-    pub fn new(x: i32, y: i32, z: i32) -> Self {
+    pub fn new(x: i32, y: i32, z: i32, terrain: &TerrainConfig) -> Self {
         let (noise, _, _) = NoiseBuilder::fbm_2d_offset(
             (x * CHUNK_SIZE_X) as f32,
             CHUNK_SIZE_X as usize,
             (z * CHUNK_SIZE_X) as f32,
             CHUNK_SIZE_X as usize,
         )
-        .with_freq(0.0001)
-        .with_octaves(8)
-        .with_gain(2.2)
-        .with_seed(42)
-        .with_lacunarity(2.0)
+        .with_freq(terrain.base.freq)
+        .with_octaves(terrain.base.octaves)
+        .with_gain(terrain.base.gain)
+        .with_seed(terrain.base.seed)
+        .with_lacunarity(terrain.base.lacunarity)
         .generate();
 
         let (noise_mountains, _, _) = NoiseBuilder::ridge_2d_offset(
@@ -52,11 +151,11 @@ impl ChunkNoises {
             (z * CHUNK_SIZE_X) as f32,
             CHUNK_SIZE_X as usize,
         )
-        .with_freq(0.01 / 64000.0)
-        .with_octaves(12)
-        .with_gain(2.3)
-        .with_seed(42)
-        .with_lacunarity(2.2)
+        .with_freq(terrain.mountains.freq)
+        .with_octaves(terrain.mountains.octaves)
+        .with_gain(terrain.mountains.gain)
+        .with_seed(terrain.mountains.seed)
+        .with_lacunarity(terrain.mountains.lacunarity)
         .generate();
 
         let (dirt_noise, min, max) = NoiseBuilder::fbm_2d_offset(
@@ -65,11 +164,11 @@ impl ChunkNoises {
             (z * CHUNK_SIZE_X) as f32,
             CHUNK_SIZE_X as usize,
         )
-        .with_freq(0.0001)
-        .with_octaves(1)
-        .with_gain(2.0)
-        .with_seed(44)
-        .with_lacunarity(2.0)
+        .with_freq(terrain.dirt.freq)
+        .with_octaves(terrain.dirt.octaves)
+        .with_gain(terrain.dirt.gain)
+        .with_seed(terrain.dirt.seed)
+        .with_lacunarity(terrain.dirt.lacunarity)
         .generate();
 
         let (variance, _, _) = NoiseBuilder::fbm_2d_offset(
@@ -78,13 +177,32 @@ impl ChunkNoises {
             (z * CHUNK_SIZE_X) as f32,
             CHUNK_SIZE_X as usize,
         )
-        .with_freq(1.0 / 2000.0)
+        .with_freq(terrain.variance.freq)
+        .with_octaves(terrain.variance.octaves)
+        .with_gain(terrain.variance.gain)
+        .with_seed(terrain.variance.seed)
+        .with_lacunarity(terrain.variance.lacunarity)
+        .generate();
+
+        let (biome_noise, _, _) = NoiseBuilder::fbm_2d_offset(
+            (x * CHUNK_SIZE_X) as f32,
+            CHUNK_SIZE_X as usize,
+            (z * CHUNK_SIZE_X) as f32,
+            CHUNK_SIZE_X as usize,
+        )
+        .with_freq(1.0 / 4000.0)
         .with_octaves(1)
         .with_gain(1.0)
-        .with_seed(43)
+        .with_seed(45)
         .with_lacunarity(1.0)
         .generate();
+        // Same normalization as the variance noise
+        let biome_noise: Vec<f32> = biome_noise
+            .into_iter()
+            .map(|value| ((value / 0.02) + 1.0) / 2.0)
+            .collect();
 
+        let mut biomes = Vec::with_capacity((CHUNK_SIZE_X * CHUNK_SIZE_X) as usize);
         let mut target_height =
             Vec::with_capacity((CHUNK_SIZE_X * CHUNK_SIZE_X) as usize);
         for i in 0..CHUNK_SIZE_X * CHUNK_SIZE_X {
@@ -93,9 +211,12 @@ impl ChunkNoises {
             let mountains_noise = -noise_mountains[i];
             let variance_noise = variance[i];
             let normalized_variance = ((variance_noise / 0.02) + 1.0) / 2.0;
-            let target_height_value = (mountains_noise * normalized_variance
+            let amplitude =
+                Biome::height_amplitude_at(biome_noise[i]) * terrain.height_scale;
+            let target_height_value = ((mountains_noise * normalized_variance
                 + base_noise * (1.0 - normalized_variance))
-                as i32;
+                * amplitude) as i32;
+            biomes.push(Biome::from_noise(biome_noise[i]));
             target_height.push(target_height_value);
         }
 
@@ -104,13 +225,88 @@ impl ChunkNoises {
             noise_mountains,
             dirt_noise,
             variance,
+            biome_noise,
+            biomes,
             target_height,
         }
     }
 }
 
+// Columns per chunk that may grow a tree, depending on their biome
+const TREE_CANDIDATES: usize = 6;
+// Ore veins started per chunk, those outside stone simply place nothing
+const ORE_VEINS_PER_CHUNK: usize = 8;
+
 pub struct ChunkDecorations {
     pub trees: Vec<Tree>,
+    pub ore_veins: Vec<OreVein>,
+}
+
+impl ChunkDecorations {
+    // Picks a few surface columns of the chunk to grow trees on, each kept with
+    // the tree chance of its biome. Only the chunk containing the surface block
+    // gets the tree, and never under water.
+    fn new(
+        x: i32,
+        y: i32,
+        z: i32,
+        noises: &ChunkNoises,
+        terrain: &TerrainConfig,
+    ) -> Self {
+        let mut rng = locus_into_rng(&(x, y, z));
+        let mut trees = Vec::new();
+        for _ in 0..TREE_CANDIDATES {
+            let local_x = rng.random_range(0..CHUNK_SIZE_X);
+            let local_z = rng.random_range(0..CHUNK_SIZE_X);
+            let biome = noises.biomes[(local_x + local_z * CHUNK_SIZE_X) as usize];
+            if !rng.random_bool(biome.tree_chance()) {
+                continue;
+            }
+            // Same surface ChunkData::new puts grass on
+            let grass_height = terrain.surface_height(
+                noises.target_height[(local_x + local_z * CHUNK_SIZE_X) as usize],
+            );
+            let tree_y = grass_height + 1;
+            if grass_height < terrain.sea_level || tree_y.div_euclid(CHUNK_SIZE_X) != y
+            {
+                continue;
+            }
+            trees.push(Tree::from_locus(WorldPos {
+                chunk_x: x,
+                chunk_y: y,
+                chunk_z: z,
+                x: x * CHUNK_SIZE_X + local_x,
+                y: tree_y,
+                z: z * CHUNK_SIZE_X + local_z,
+            }));
+        }
+        let mut ore_veins = Vec::new();
+        for _ in 0..ORE_VEINS_PER_CHUNK {
+            ore_veins.push(OreVein::from_locus(WorldPos {
+                chunk_x: x,
+                chunk_y: y,
+                chunk_z: z,
+                x: x * CHUNK_SIZE_X + rng.random_range(0..CHUNK_SIZE_X),
+                y: y * CHUNK_SIZE_X + rng.random_range(0..CHUNK_SIZE_X),
+                z: z * CHUNK_SIZE_X + rng.random_range(0..CHUNK_SIZE_X),
+            }));
+        }
+        ChunkDecorations { trees, ore_veins }
+    }
+
+    /// How many chunks around this one the decorations may write into
+    pub fn neighborhood_size(&self) -> i32 {
+        let trees = self.trees.iter().map(|tree| tree.extent());
+        let ore_veins = self.ore_veins.iter().map(|ore_vein| ore_vein.extent());
+        // Origins can sit on the chunk border, so any reach at all may cross it
+        trees
+            .chain(ore_veins)
+            .map(|(x, y, z)| x.max(y).max(z))
+            .max()
+            .map_or(0, |reach| {
+                (reach as u32).div_ceil(CHUNK_SIZE_X as u32) as i32
+            })
+    }
 }
 
 pub struct AkashaChunk {
@@ -119,9 +315,9 @@ pub struct AkashaChunk {
 }
 
 impl AkashaChunk {
-    pub fn new(x: i32, y: i32, z: i32) -> Self {
-        let noises = ChunkNoises::new(x, y, z);
-        let decorations = ChunkDecorations { trees: Vec::new() };
+    pub fn new(x: i32, y: i32, z: i32, terrain: &TerrainConfig) -> Self {
+        let noises = ChunkNoises::new(x, y, z, terrain);
+        let decorations = ChunkDecorations::new(x, y, z, &noises, terrain);
         AkashaChunk {
             noises,
             decorations,
@@ -131,12 +327,14 @@ impl AkashaChunk {
 
 pub struct Akasha {
     pub chunks: Arc<RwLock<HashMap<(i32, i32, i32), Arc<RwLock<AkashaChunk>>>>>,
+    pub terrain: TerrainConfig,
 }
 
 impl Akasha {
-    pub fn new() -> Self {
+    pub fn new(terrain: TerrainConfig) -> Self {
         Akasha {
             chunks: Arc::new(RwLock::new(HashMap::new())),
+            terrain,
         }
     }
 
@@ -154,7 +352,7 @@ impl Akasha {
         }
 
         let mut chunks = akasha.chunks.write().unwrap();
-        let chunk = Arc::new(RwLock::new(AkashaChunk::new(x, y, z)));
+        let chunk = Arc::new(RwLock::new(AkashaChunk::new(x, y, z, &akasha.terrain)));
         chunks.insert((x, y, z), chunk.clone());
         chunk
     }
@@ -0,0 +1,9 @@
+#version 330 core
+
+in vec4 fragColor;
+
+out vec4 final_color;
+void main() {
+    // Outlines are flat colored, no texture or lighting
+    final_color = fragColor;
+}
\ No newline at end of file
@@ -3,14 +3,27 @@
 in vec4 fragColor;
 in vec2 fragUV;
 in vec3 fragLight;
+in vec3 fragNormal;
 in vec3 worldPos;
 in vec4 glPos;
 flat in ivec2 fragMaterialId;
 
 uniform sampler2D terrainTexture;
+uniform vec2 atlasGrid; // Columns and rows of tiles in terrainTexture
+// Column, row and current frame of each animated region
+uniform int terrainTextureAnimations[3 * 8];
+uniform int terrainTextureAnimationCount;
 uniform float time;
 uniform vec3 cameraPos;
-uniform vec3 cursorPos;
+uniform vec3 sunDirection; // Direction the sunlight travels in
+uniform float daylight; // 1 during the day, 0 at night
+// Distances from the camera where fog begins and fully hides the terrain
+uniform float fogStart;
+uniform float fogEnd;
+uniform vec3 fogColor;
+
+// How bright terrain still is in the middle of the night
+const float NIGHT_BRIGHTNESS = 0.3;
 
 float dither4x4(vec2 position) {
     int x = int(mod(position.x, 4.0));
@@ -77,24 +90,24 @@ float noise3(vec3 p) {
 out vec4 final_color;
 void main() {
 
-  float cursorStartX = cursorPos.x - 0.05;
-  float cursorEndX = cursorPos.x + 1.05;
-  float cursorStartY = cursorPos.y - 0.05;
-  float cursorEndY = cursorPos.y + 1.05;
-  float cursorStartZ = cursorPos.z - 0.05;
-  float cursorEndZ = cursorPos.z + 1.05;
-  bool isCursorInBlock = (worldPos.x >= cursorStartX && worldPos.x <= cursorEndX) &&
-                         (worldPos.y >= cursorStartY && worldPos.y <= cursorEndY) &&
-                         (worldPos.z >= cursorStartZ && worldPos.z <= cursorEndZ);
-
   vec3 flooredPos = floor(worldPos * 16.0) / 16.0;
 
   // Convert materialId to a texture coordinate by 
-  // dividing by the number of materials (16)
-  vec2 matCoord = vec2(fragMaterialId) / 16.0;
+  // dividing by the size of the atlas grid
+  ivec2 material = fragMaterialId;
+  // Animated regions keep their frames stacked below the first one
+  for (int i = 0; i < terrainTextureAnimationCount; i++) {
+      ivec2 region = ivec2(terrainTextureAnimations[3 * i], terrainTextureAnimations[3 * i + 1]);
+      if (material == region) {
+          material.y += terrainTextureAnimations[3 * i + 2];
+      }
+  }
+  vec2 matCoord = vec2(material) / atlasGrid;
   // Add uv downscaled by 16
 
-  vec2 UV = fragUV.xy / glPos.w;
+  // Vertex uvs are in atlas space, starting at the tile's rect. Get back the
+  // position within the tile, which grows past 1 across merged faces.
+  vec2 UV = (fragUV.xy / glPos.w) * atlasGrid - vec2(fragMaterialId);
 
   if (fragLight.r < fragLight.b) {
       vec3 flooredPos = floor((worldPos + 0.001) * 16.0) / 16.0 + 0.1321;
@@ -124,10 +137,17 @@ void main() {
   }
 
   vec2 wrappedUV = vec2(mod(UV.x,1), mod(UV.y,1));
-  vec2 texCoord = matCoord + wrappedUV / 16.0;
+  vec2 texCoord = matCoord + wrappedUV / atlasGrid;
+  // Keep samples half a texel inside the tile so neighbours don't bleed in
+  vec2 halfTexel = 0.5 / vec2(textureSize(terrainTexture, 0));
+  texCoord = clamp(texCoord, matCoord + halfTexel, matCoord + 1.0 / atlasGrid - halfTexel);
   // Sample the texture
   
-  vec4 sampledColor = texture(terrainTexture, texCoord);
+  // Pick the mip level from the unwrapped UV. The wrapped coordinate jumps
+  // at every tile edge, which would select the smallest mip along the seams.
+  // The unwrapped UV also grows with lod, so far chunks get coarser levels.
+  vec2 gradCoord = UV / atlasGrid;
+  vec4 sampledColor = textureGrad(terrainTexture, texCoord, dFdx(gradCoord), dFdy(gradCoord));
   
   // Only apply darkness to RGB, preserve alpha
   vec3 darkness = fragLight.rgb - vec3(1.0, 1.0, 1.0);
@@ -159,6 +179,14 @@ void main() {
       final_color = vec4(sampledColor.rgb + darkness, sampledColor.a);
   }
 
+  // Directional sun shading, skipped for meshes without normals. It fades
+  // out with the daylight so faces aren't lit by a sun below the horizon.
+  if (length(fragNormal) > 0.0 && length(sunDirection) > 0.0) {
+      float sunFacing = max(dot(normalize(fragNormal), -normalize(sunDirection)), 0.0);
+      final_color.rgb *= mix(1.0, mix(0.6, 1.0, sunFacing), daylight);
+  }
+  final_color.rgb *= mix(NIGHT_BRIGHTNESS, 1.0, daylight);
+
   // special handling for water
   if (fragMaterialId.x == 15 && fragMaterialId.y == 13) {
       // Water material, apply special color
@@ -225,10 +253,9 @@ void main() {
       discard; // Discard fragments with very low alpha
   }
 
-  if (isCursorInBlock) {
-      // Highlight the block under the cursor
-      final_color.rgb -= vec3(0.1, 0.1, 0.1);
-  }
+  // Fade into the sky towards the far plane so chunks don't pop in
+  float fogAmount = smoothstep(fogStart, fogEnd, length(worldPos - cameraPos));
+  final_color.rgb = mix(final_color.rgb, fogColor, fogAmount);
 
   //final_color = vec4(fragUV, 0.0, 1.0);
 }
\ No newline at end of file
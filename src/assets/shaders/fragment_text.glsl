@@ -12,6 +12,12 @@ uniform vec3 cameraPos;
 
 out vec4 final_color;
 void main() {
+    // Decorations like underlines have no glyph and are drawn flat
+    if (fragMaterialId.x < 0) {
+        final_color = fragColor;
+        return;
+    }
+
     vec2 UV = fragUV.xy;
 
     vec2 matCoord = vec2(fragMaterialId) / 16.0;
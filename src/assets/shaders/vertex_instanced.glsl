@@ -0,0 +1,30 @@
+#version 330 core
+layout (location = 0) in vec3 pos;
+layout (location = 1) in vec2 uv;
+layout (location = 2) in ivec2 mat;
+layout (location = 3) in vec4 color;
+layout (location = 4) in vec3 light;
+layout (location = 5) in vec3 normal;
+// One transform per instance, takes locations 6 to 9
+layout (location = 6) in mat4 instanceModel;
+flat out ivec2 fragMaterialId;
+out vec4 fragColor;
+out vec2 fragUV;
+out vec3 fragLight;
+out vec3 fragNormal;
+out vec3 worldPos;
+out vec4 glPos;
+
+// View projection only, the model transform comes from the instance
+uniform mat4 mvp;
+void main() {
+  vec4 instancePos = instanceModel * vec4(pos, 1.0);
+  gl_Position = mvp * instancePos;
+  worldPos = instancePos.xyz;
+  fragColor = color;
+  fragUV = uv * gl_Position.w;
+  glPos = gl_Position;
+  fragMaterialId = mat;
+  fragLight = light;
+  fragNormal = mat3(instanceModel) * normal;
+}
\ No newline at end of file
@@ -4,10 +4,12 @@ layout (location = 1) in vec2 uv;
 layout (location = 2) in ivec2 mat;
 layout (location = 3) in vec4 color;
 layout (location = 4) in vec3 light;
+layout (location = 5) in vec3 normal;
 flat out ivec2 fragMaterialId;
 out vec4 fragColor;
 out vec2 fragUV;
 out vec3 fragLight;
+out vec3 fragNormal;
 out vec3 worldPos;
 out vec4 glPos;
 
@@ -25,4 +27,5 @@ void main() {
   glPos = gl_Position;
   fragMaterialId = mat;
   fragLight = light;
+  fragNormal = normal;
 }
\ No newline at end of file
@@ -1,4 +1,4 @@
-use ultraviolet::{Mat4, Vec3};
+use ultraviolet::{Mat4, Vec3, projection};
 
 pub struct Camera {
     pub position: Vec3,
@@ -10,6 +10,41 @@ pub struct Camera {
     pub pitch: f32,
     pub movement_speed: f32,
     pub mouse_sensitivity: f32,
+    // Seconds it takes mouse look to catch up with about 63% of the mouse
+    // movement, 0 applies movement right away
+    pub mouse_smoothing: f32,
+    // Moving the mouse up looks down
+    pub invert_y: bool,
+    // Yaw and pitch in degrees still to be applied by smooth_look
+    pending_yaw: f32,
+    pending_pitch: f32,
+    // Vertical field of view in degrees
+    pub fov: f32,
+    // Distances to the near and far clipping planes
+    pub near: f32,
+    pub far: f32,
+    pub view: CameraView,
+}
+
+/// Where the camera sits relative to the eyes it follows
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CameraView {
+    FirstPerson,
+    /// Behind the eyes, looking the same way
+    ThirdPersonBack,
+    /// In front of the eyes, looking back at them
+    ThirdPersonFront,
+}
+
+impl CameraView {
+    /// The view after this one when cycling with a toggle key
+    pub fn next(self) -> Self {
+        match self {
+            CameraView::FirstPerson => CameraView::ThirdPersonBack,
+            CameraView::ThirdPersonBack => CameraView::ThirdPersonFront,
+            CameraView::ThirdPersonFront => CameraView::FirstPerson,
+        }
+    }
 }
 
 impl Camera {
@@ -24,6 +59,14 @@ impl Camera {
             pitch: 0.0,
             movement_speed: 2.5,
             mouse_sensitivity: 0.1,
+            mouse_smoothing: 0.0,
+            invert_y: false,
+            pending_yaw: 0.0,
+            pending_pitch: 0.0,
+            fov: 90.0,
+            near: 0.1,
+            far: 100.0,
+            view: CameraView::FirstPerson,
         };
         camera.update_camera_vectors();
         camera
@@ -41,7 +84,74 @@ impl Camera {
     }
 
     pub fn get_view_matrix(&self) -> Mat4 {
-        Mat4::look_at(self.position, self.position + self.front, self.up)
+        // The front view looks back the way the eyes face
+        let facing = if self.view == CameraView::ThirdPersonFront {
+            -self.front
+        } else {
+            self.front
+        };
+        Mat4::look_at(self.position, self.position + facing, self.up)
+    }
+
+    /// Direction from the eyes to where the camera sits in the current view,
+    /// None in first person
+    pub fn third_person_offset(&self) -> Option<Vec3> {
+        match self.view {
+            CameraView::FirstPerson => None,
+            CameraView::ThirdPersonBack => Some(-self.front),
+            CameraView::ThirdPersonFront => Some(self.front),
+        }
+    }
+
+    /// Perspective projection for a viewport with the given width / height
+    pub fn projection(&self, aspect: f32) -> Mat4 {
+        projection::rh_yup::perspective_gl(
+            self.fov.to_radians(),
+            aspect,
+            self.near,
+            self.far,
+        )
+    }
+
+    /// Extracts the six clipping planes (left, right, bottom, top, near, far) of
+    /// the view frustum as `[a, b, c, d]`, where `a*x + b*y + c*z + d >= 0` for
+    /// points inside. The normals are normalized so `d` is a true distance.
+    pub fn frustum_planes(&self, proj: Mat4) -> [[f32; 4]; 6] {
+        let clip = proj * self.get_view_matrix();
+        let row = |i: usize| {
+            [
+                clip.cols[0][i],
+                clip.cols[1][i],
+                clip.cols[2][i],
+                clip.cols[3][i],
+            ]
+        };
+        let (r0, r1, r2, r3) = (row(0), row(1), row(2), row(3));
+        let combine = |a: [f32; 4], b: [f32; 4], sign: f32| {
+            let plane = [
+                a[0] + sign * b[0],
+                a[1] + sign * b[1],
+                a[2] + sign * b[2],
+                a[3] + sign * b[3],
+            ];
+            let length =
+                (plane[0] * plane[0] + plane[1] * plane[1] + plane[2] * plane[2])
+                    .sqrt();
+            [
+                plane[0] / length,
+                plane[1] / length,
+                plane[2] / length,
+                plane[3] / length,
+            ]
+        };
+        [
+            combine(r3, r0, 1.0),
+            combine(r3, r0, -1.0),
+            combine(r3, r1, 1.0),
+            combine(r3, r1, -1.0),
+            combine(r3, r2, 1.0),
+            combine(r3, r2, -1.0),
+        ]
     }
 
     pub fn process_keyboard(&mut self, direction: CameraMovement, delta_time: f32) {
@@ -55,14 +165,45 @@ impl Camera {
     }
 
     pub fn process_mouse_movement(&mut self, x_offset: f32, y_offset: f32) {
-        self.yaw += x_offset * self.mouse_sensitivity;
-        self.pitch += y_offset * self.mouse_sensitivity;
+        let y_offset = if self.invert_y { -y_offset } else { y_offset };
+        let yaw = x_offset * self.mouse_sensitivity;
+        let pitch = y_offset * self.mouse_sensitivity;
+        if self.mouse_smoothing > 0.0 {
+            // Left for smooth_look to ease in over the next frames
+            self.pending_yaw += yaw;
+            self.pending_pitch += pitch;
+        } else {
+            self.rotate(yaw, pitch);
+        }
+    }
 
-        if self.pitch > 89.0 {
-            self.pitch = 89.0;
+    /// Applies part of the mouse movement still pending from
+    /// process_mouse_movement, call once per frame. The fraction depends on
+    /// delta_time so smoothing feels the same at any frame rate.
+    pub fn smooth_look(&mut self, delta_time: f32) {
+        if self.pending_yaw == 0.0 && self.pending_pitch == 0.0 {
+            return;
         }
-        if self.pitch < -89.0 {
-            self.pitch = -89.0;
+        let fraction = if self.mouse_smoothing > 0.0 {
+            1.0 - (-delta_time / self.mouse_smoothing).exp()
+        } else {
+            1.0
+        };
+        let yaw = self.pending_yaw * fraction;
+        let pitch = self.pending_pitch * fraction;
+        self.pending_yaw -= yaw;
+        self.pending_pitch -= pitch;
+        self.rotate(yaw, pitch);
+    }
+
+    fn rotate(&mut self, yaw: f32, pitch: f32) {
+        self.yaw += yaw;
+        self.pitch += pitch;
+
+        if self.pitch > 89.0 || self.pitch < -89.0 {
+            self.pitch = self.pitch.clamp(-89.0, 89.0);
+            // Pushing further against the limit shouldn't be saved up
+            self.pending_pitch = 0.0;
         }
 
         self.update_camera_vectors();
@@ -0,0 +1,27 @@
+use crate::world::BlockId;
+
+/// The blocks the player can place, one of them selected at a time
+pub struct Hotbar {
+    slots: Vec<BlockId>,
+    selected: usize,
+}
+
+impl Hotbar {
+    /// Creates a hotbar with the first slot selected, panics without slots
+    pub fn new(slots: Vec<BlockId>) -> Self {
+        if slots.is_empty() {
+            panic!("A hotbar needs at least one slot");
+        }
+        Self { slots, selected: 0 }
+    }
+
+    /// Moves the selection by `steps` slots, wrapping around at both ends
+    pub fn scroll(&mut self, steps: i32) {
+        let len = self.slots.len() as i32;
+        self.selected = (self.selected as i32 + steps).rem_euclid(len) as usize;
+    }
+
+    pub fn selected_block(&self) -> BlockId {
+        self.slots[self.selected]
+    }
+}
@@ -0,0 +1,209 @@
+use std::collections::{HashMap, HashSet};
+
+use beryllium::events::{self, Event, SDL_Keycode};
+
+/// What the player wants to do, independent of the key or button doing it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    Jump,
+    // Sneaks while walking, moves down while flying
+    Sneak,
+    Sprint,
+    Break,
+    Place,
+    CycleMovementMode,
+    CycleCameraView,
+    PlaceStructure,
+}
+
+/// A physical input an action can be bound to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Binding {
+    Key(SDL_Keycode),
+    // SDL button index, 1 is the left button and 3 the right one
+    MouseButton(u8),
+}
+
+/// Maps keys and mouse buttons to actions and keeps track of which are held.
+/// Continuous actions are queried with is_pressed, one-off actions with
+/// was_pressed, which only reports the frame the input went down.
+pub struct InputMap {
+    bindings: HashMap<Binding, Action>,
+    held: HashSet<Binding>,
+    // Actions that went down since the last call to end_frame
+    just_pressed: HashSet<Action>,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InputMap {
+    /// Creates a map with the default WASD controls
+    pub fn new() -> Self {
+        let mut input = Self {
+            bindings: HashMap::new(),
+            held: HashSet::new(),
+            just_pressed: HashSet::new(),
+        };
+        input.bind(Binding::Key(events::SDLK_w), Action::MoveForward);
+        input.bind(Binding::Key(events::SDLK_s), Action::MoveBackward);
+        input.bind(Binding::Key(events::SDLK_a), Action::MoveLeft);
+        input.bind(Binding::Key(events::SDLK_d), Action::MoveRight);
+        input.bind(Binding::Key(events::SDLK_SPACE), Action::Jump);
+        input.bind(Binding::Key(events::SDLK_LSHIFT), Action::Sneak);
+        input.bind(Binding::Key(events::SDLK_LCTRL), Action::Sprint);
+        input.bind(Binding::Key(events::SDLK_f), Action::CycleMovementMode);
+        input.bind(Binding::Key(events::SDLK_F5), Action::CycleCameraView);
+        input.bind(Binding::Key(events::SDLK_g), Action::PlaceStructure);
+        input.bind(Binding::MouseButton(1), Action::Break);
+        input.bind(Binding::MouseButton(3), Action::Place);
+        input
+    }
+
+    /// Adds `binding` as one more way to trigger `action`. Returns the action
+    /// the binding triggered before, if any.
+    pub fn bind(&mut self, binding: Binding, action: Action) -> Option<Action> {
+        self.held.remove(&binding);
+        self.bindings.insert(binding, action)
+    }
+
+    /// Replaces every binding of `action` with `binding`
+    pub fn rebind(&mut self, action: Action, binding: Binding) {
+        self.bindings.retain(|_, bound| *bound != action);
+        self.bind(binding, action);
+    }
+
+    pub fn bindings_for(&self, action: Action) -> Vec<Binding> {
+        self.bindings
+            .iter()
+            .filter(|(_, bound)| **bound == action)
+            .map(|(binding, _)| *binding)
+            .collect()
+    }
+
+    /// Updates the held inputs from a key or mouse button event, other events
+    /// are ignored
+    pub fn handle_event(&mut self, event: &Event) {
+        let (binding, pressed) = match *event {
+            Event::Key {
+                keycode,
+                pressed,
+                repeat,
+                ..
+            } => {
+                // Held keys repeat, but they were already pressed
+                if repeat > 0 {
+                    return;
+                }
+                (Binding::Key(keycode), pressed)
+            }
+            Event::MouseButton {
+                button, pressed, ..
+            } => (Binding::MouseButton(button), pressed),
+            _ => return,
+        };
+        let Some(&action) = self.bindings.get(&binding) else {
+            return;
+        };
+        if pressed {
+            if self.held.insert(binding) {
+                self.just_pressed.insert(action);
+            }
+        } else {
+            self.held.remove(&binding);
+        }
+    }
+
+    /// Whether any input bound to `action` is being held down
+    pub fn is_pressed(&self, action: Action) -> bool {
+        self.held
+            .iter()
+            .any(|binding| self.bindings.get(binding) == Some(&action))
+    }
+
+    /// Whether an input bound to `action` went down this frame
+    pub fn was_pressed(&self, action: Action) -> bool {
+        self.just_pressed.contains(&action)
+    }
+
+    /// Forgets this frame's presses, call once the frame's input was processed
+    pub fn end_frame(&mut self) {
+        self.just_pressed.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use beryllium::events::{SDL_Keymod, SDL_Scancode};
+
+    fn key_event(keycode: SDL_Keycode, pressed: bool) -> Event {
+        Event::Key {
+            win_id: 0,
+            pressed,
+            repeat: 0,
+            scancode: SDL_Scancode(0),
+            keycode,
+            modifiers: SDL_Keymod(0),
+        }
+    }
+
+    #[test]
+    fn bound_keys_trigger_their_actions() {
+        let mut input = InputMap::new();
+        input.handle_event(&key_event(events::SDLK_w, true));
+        assert!(input.is_pressed(Action::MoveForward));
+        assert!(input.was_pressed(Action::MoveForward));
+        assert!(!input.is_pressed(Action::MoveBackward));
+
+        input.end_frame();
+        assert!(input.is_pressed(Action::MoveForward));
+        assert!(!input.was_pressed(Action::MoveForward));
+
+        input.handle_event(&key_event(events::SDLK_w, false));
+        assert!(!input.is_pressed(Action::MoveForward));
+    }
+
+    #[test]
+    fn unbound_keys_are_ignored() {
+        let mut input = InputMap::new();
+        input.handle_event(&key_event(events::SDLK_q, true));
+        assert!(input.held.is_empty());
+        assert!(input.just_pressed.is_empty());
+    }
+
+    #[test]
+    fn rebinding_replaces_every_old_binding() {
+        let mut input = InputMap::new();
+        input.bind(Binding::Key(events::SDLK_UP), Action::MoveForward);
+        input.rebind(Action::MoveForward, Binding::Key(events::SDLK_i));
+        assert_eq!(
+            input.bindings_for(Action::MoveForward),
+            [Binding::Key(events::SDLK_i)]
+        );
+
+        input.handle_event(&key_event(events::SDLK_w, true));
+        input.handle_event(&key_event(events::SDLK_UP, true));
+        assert!(!input.is_pressed(Action::MoveForward));
+        input.handle_event(&key_event(events::SDLK_i, true));
+        assert!(input.is_pressed(Action::MoveForward));
+    }
+
+    #[test]
+    fn binding_a_used_input_takes_it_from_its_action() {
+        let mut input = InputMap::new();
+        let previous = input.bind(Binding::Key(events::SDLK_w), Action::Jump);
+        assert_eq!(previous, Some(Action::MoveForward));
+        assert!(input.bindings_for(Action::MoveForward).is_empty());
+
+        input.handle_event(&key_event(events::SDLK_w, true));
+        assert!(input.is_pressed(Action::Jump));
+    }
+}
@@ -0,0 +1,154 @@
+use crate::{
+    mesh::Mesh,
+    physics::{ObjectId, PhysicsEnvironment, PhysicsObject},
+    tile::{self, LayerBuffers, TERRAIN_ATLAS, TileRegistry},
+    world::ItemId,
+};
+use gl33::GlFns;
+use std::collections::HashMap;
+use ultraviolet::{Mat4, Vec3};
+
+/// A dropped item lying in the world, simulated by the physics environment as
+/// a small box that falls and lands on blocks
+pub struct ItemEntity {
+    pub item: ItemId,
+    pub count: u8,
+    object: ObjectId,
+    // Seconds since it was spawned
+    age: f32,
+}
+
+/// Every dropped item in the world, along with the cube meshes they are drawn
+/// with
+pub struct ItemEntities {
+    entities: Vec<ItemEntity>,
+    // Edge length of the cube, in blocks
+    size: f32,
+    // Seconds before an item despawns
+    lifetime: f32,
+    // Radians per second the cubes turn around the vertical axis
+    spin_speed: f32,
+    // Built on first use, one cube per item id
+    meshes: HashMap<ItemId, Mesh>,
+}
+
+impl ItemEntities {
+    pub fn new(size: f32, lifetime: f32, spin_speed: f32) -> Self {
+        Self {
+            entities: Vec::new(),
+            size,
+            lifetime,
+            spin_speed,
+            meshes: HashMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// Drops `count` of `item` with its bottom centered on `position`, popping
+    /// it slightly upwards
+    pub fn spawn(
+        &mut self,
+        environment: &PhysicsEnvironment,
+        item: ItemId,
+        count: u8,
+        position: [f32; 3],
+    ) {
+        let half = self.size / 2.0;
+        let mut object = PhysicsObject::new(
+            position,
+            [0.0, 4.0, 0.0],
+            [[-half, 0.0, -half], [half, self.size, half]],
+        );
+        // Items don't climb ledges
+        object.step_height = 0.0;
+        let object = environment.register_object(object);
+        self.entities.push(ItemEntity {
+            item,
+            count,
+            object,
+            age: 0.0,
+        });
+    }
+
+    /// Ages every item and despawns those past their lifetime. Items whose
+    /// physics object was removed elsewhere are forgotten as well.
+    pub fn update(&mut self, environment: &PhysicsEnvironment, delta_time: f32) {
+        let lifetime = self.lifetime;
+        self.entities.retain_mut(|entity| {
+            entity.age += delta_time;
+            if entity.age < lifetime {
+                return environment.get_object(entity.object).is_some();
+            }
+            environment.remove_object(entity.object);
+            false
+        });
+    }
+
+    /// Draws every item as a spinning cube textured like its block. Expects
+    /// a shader using vertex_instanced.glsl to be in use, with its mvp set to
+    /// the view projection.
+    pub fn render(
+        &mut self,
+        gl: &GlFns,
+        environment: &PhysicsEnvironment,
+        tile_registry: &TileRegistry,
+    ) {
+        let mut transforms: HashMap<ItemId, Vec<Mat4>> = HashMap::new();
+        for entity in &self.entities {
+            let Some(object) = environment.get_object(entity.object) else {
+                continue;
+            };
+            // Each item starts at its own angle so piles don't turn in lockstep
+            let angle = entity.age * self.spin_speed + entity.object as f32;
+            let transform = Mat4::from_translation(Vec3::from(object.position))
+                * Mat4::from_rotation_y(angle);
+            transforms.entry(entity.item).or_default().push(transform);
+        }
+
+        for (item, transforms) in transforms {
+            let Some(tile) = tile_registry.get_handler(item) else {
+                continue;
+            };
+            let size = self.size;
+            let mesh = self
+                .meshes
+                .entry(item)
+                .or_insert_with(|| cube_mesh(gl, tile, size));
+            mesh.render_instanced(gl, &transforms);
+        }
+    }
+}
+
+// A cube of the given size textured like `tile`, its bottom face centered on
+// the origin
+fn cube_mesh(gl: &GlFns, tile: &dyn tile::Tile, size: f32) -> Mesh {
+    let half = size / 2.0;
+    let mut buffers = LayerBuffers::default();
+    tile::tesselate_box(
+        tile,
+        [0.0, 0.0, 0.0],
+        0,
+        [[-half, 0.0, -half], [half, size, half]],
+        &mut buffers,
+    );
+    for (uv, &material) in buffers.uvs.iter_mut().zip(&buffers.materials) {
+        *uv = TERRAIN_ATLAS.atlas_uv(material, *uv);
+    }
+    Mesh::new(
+        gl,
+        &buffers.vertices,
+        Some(&buffers.indices),
+        Some(&buffers.uvs),
+        Some(&buffers.materials),
+        Some(&buffers.colors),
+        Some(&buffers.lights),
+        Some(&buffers.normals),
+    )
+}
@@ -0,0 +1,28 @@
+//! The game's world, generation, physics and rendering pieces, usable
+//! without the window `main.rs` opens. Generation, world views, block edits
+//! and raycasts need no GL context, so tests and tools can drive them
+//! directly.
+
+pub mod akasha;
+pub mod camera;
+pub mod hotbar;
+pub mod input;
+pub mod item_entity;
+pub mod mesh;
+pub mod mining;
+pub mod physics;
+pub mod shader;
+pub mod sky;
+pub mod structure;
+pub mod tessellator;
+pub mod text;
+pub mod texture;
+pub mod tile;
+pub mod toki;
+pub mod utils;
+pub mod world;
+
+pub use akasha::{TerrainConfig, generator::WorldGenerator};
+pub use physics::{PhysicsEnvironment, PhysicsObject, RaycastHit};
+pub use tile::TileRegistry;
+pub use world::{BlockId, World, WorldConfig, WorldView};
@@ -2,30 +2,64 @@ use std::{env, sync::Arc};
 
 use beryllium::*;
 use gl33::*;
-use physics::{PhysicsEnvironment, PhysicsObject};
-use text::TextOptions;
+use mkcraft::{
+    camera::Camera,
+    hotbar::Hotbar,
+    input::{Action, InputMap},
+    item_entity::ItemEntities,
+    mesh,
+    mining::MiningProgress,
+    physics::{MovementMode, PhysicsEnvironment, PhysicsObject},
+    shader::Shader,
+    sky,
+    structure::Structure,
+    tessellator::{self, Tessellator},
+    text::{self, TextCache, TextOptions},
+    texture::TextureManager,
+    tile::{self, TERRAIN_ATLAS, blocks},
+    utils::FrameTimer,
+    world::{self, CHUNK_SIZE_X, World, WorldConfig, WorldViewCache},
+};
 use ultraviolet::{Mat4, projection};
-
-mod akasha;
-mod camera;
-mod mesh;
-mod physics;
-mod shader;
-mod tessellator;
-mod text;
-mod texture;
-mod tile;
-mod toki;
-mod utils;
-mod world;
-
-use camera::Camera;
-use shader::Shader;
-use texture::TextureManager;
-use world::{CHUNK_SIZE_X, World};
-
-use crate::tessellator::Tessellator;
 const RENDER_DISTANCE: i32 = 16; // Number of chunks to render in each direction
+const MESH_BUDGET: usize = 8; // Number of chunk meshes enqueued per frame at most
+const MAX_MESHES_IN_FLIGHT: usize = 32; // Number of chunk meshes generating at once
+const VIEW_CACHE_SIZE: usize = 32; // Padded world views kept around for meshing
+const SPAWN_READY_RADIUS: i32 = 2; // Chunks around spawn meshed before the player moves
+// Placeable blocks
+const HOTBAR_BLOCKS: [world::BlockId; 7] = [
+    blocks::STONE,
+    blocks::DIRT,
+    blocks::GRASS,
+    blocks::LOG,
+    blocks::LEAVES,
+    blocks::SAND,
+    blocks::LAMP,
+];
+const STRUCTURE_PATH: &str = "src/assets/structures/well.txt"; // Loaded at startup
+const PLAYER_STEP_HEIGHT: f32 = 0.6; // Low ledges only, full blocks need a jump
+// Player collision box around the eyes
+const PLAYER_BOX: [[f32; 3]; 2] = [[-0.3, -1.64, -0.3], [0.3, 1.8 - 1.62, 0.3]];
+const THIRD_PERSON_DISTANCE: f32 = 4.0; // Blocks between the eyes and a third person camera
+const CAMERA_WALL_MARGIN: f32 = 0.2; // Gap kept between a pulled in camera and the wall
+const CAMERA_FACE_CLEARANCE: f32 = 0.25; // First person camera's least distance to blocks
+const SNEAK_HEIGHT_DROP: f32 = 0.3; // How much lower the eyes and head are sneaking
+const JUMP_VELOCITY: f32 = 9.0; // Upward speed given by a jump
+const SWIM_VELOCITY: f32 = 4.0; // Upward speed given by a stroke while in water
+const FLY_SPEED: f32 = 8.0; // Vertical speed while flying or in noclip
+const SPRINT_SPEED_MULTIPLIER: f32 = 1.3; // Forward speed while sprinting
+const SPRINT_FOV_KICK: f32 = 8.0; // Degrees the FOV widens by while sprinting
+const FOV_EASE_TIME: f32 = 0.1; // Seconds the FOV takes to mostly settle
+const FOG_START: f32 = 0.6; // Fraction of the far plane distance fog starts at
+const FOG_END: f32 = 0.95; // Fraction of the far plane distance fog is opaque at
+const DAY_LENGTH: f32 = 600.0; // Seconds a full day and night cycle lasts
+const INITIAL_WINDOW_SIZE: (i32, i32) = (800, 600); // Window size in pixels at startup
+const FRAME_TIMER_SAMPLES: usize = 60; // Frames the FPS counter averages over
+const MOUSE_SMOOTHING: f32 = 0.02; // Seconds mouse look takes to mostly catch up
+const FPS_REFRESH_INTERVAL: f32 = 0.5; // Seconds between FPS counter updates
+const ITEM_SIZE: f32 = 0.25; // Edge length of a dropped item's cube, in blocks
+const ITEM_LIFETIME: f32 = 300.0; // Seconds before a dropped item despawns
+const ITEM_SPIN_SPEED: f32 = 1.5; // Radians per second dropped items turn
 
 //enum QueuedMesh {
 //    Generating(JoinHandle<MeshEnvelope>),
@@ -46,6 +80,12 @@ const RENDER_DISTANCE: i32 = 16; // Number of chunks to render in each direction
 //    }
 //}
 
+// Orthographic projection of the HUD, one unit per window pixel with the
+// origin in the bottom left corner
+fn hud_projection((width, height): (f32, f32)) -> Mat4 {
+    projection::rh_yup::orthographic_gl(0.0, width, 0.0, height, -1.0, 1.0)
+}
+
 #[tokio::main]
 async fn main() {
     println!("Hello, world!");
@@ -59,16 +99,26 @@ async fn main() {
     let mut camera = Camera::new();
     let mut delta_time;
     let mut last_frame = std::time::Instant::now();
-
-    let mut keys_pressed = std::collections::HashSet::new();
+    // Chunk the camera was in when distant chunks were last unloaded
+    let mut last_prune_center = None;
+    // Whether the terrain around the player was meshed, see SPAWN_READY_RADIUS
+    let mut spawn_ready = false;
+
+    let mut input = InputMap::new();
+    // Sprinting starts on the ground and lasts through jumps while moving forward
+    let mut sprinting = false;
+    // Extra horizontal velocity sprinting adds, kept from the last grounded frame
+    let mut sprint_boost = [0.0, 0.0];
+    let mut mining_progress = MiningProgress::new();
+    let mut hotbar = Hotbar::new(HOTBAR_BLOCKS.to_vec());
 
     let win_args = video::CreateWinArgs {
         title: &env::args().next().unwrap_or_else(|| "mkcraft".to_string()),
-        width: 800,
-        height: 600,
+        width: INITIAL_WINDOW_SIZE.0,
+        height: INITIAL_WINDOW_SIZE.1,
         allow_high_dpi: true,
         borderless: false,
-        resizable: false,
+        resizable: true,
     };
 
     let _win = sdl
@@ -81,7 +131,6 @@ async fn main() {
 
     // Initialize OpenGL settings
     unsafe {
-        gl.ClearColor(148.0 / 255.0, 243.0 / 255.0, 255.0 / 255.0, 1.0);
         //gl.ClearColor(255.0 / 255.0, 126.0 / 255.0, 33.0 / 255.0, 1.0);
         //gl.ClearColor(0.51, 0.86, 0.9, 1.0);
         gl.Enable(GL_DEPTH_TEST);
@@ -90,6 +139,10 @@ async fn main() {
         gl.BlendFunc(GL_SRC_ALPHA, GL_ONE_MINUS_SRC_ALPHA);
     }
 
+    // Window size in pixels, the HUD projection is rebuilt whenever it changes
+    let mut window_size = (INITIAL_WINDOW_SIZE.0 as f32, INITIAL_WINDOW_SIZE.1 as f32);
+    let mut gui_projection = hud_projection(window_size);
+
     // Create shader program
     let vertex_source = include_str!("assets/shaders/vertex_test.glsl");
     let fragment_source = include_str!("assets/shaders/fragment_test.glsl");
@@ -101,14 +154,39 @@ async fn main() {
     let text_shader = Shader::new(&gl, text_vertex_source, text_fragment_source)
         .expect("Failed to create text shader");
 
+    let selection_fragment_source =
+        include_str!("assets/shaders/fragment_selection.glsl");
+    let selection_shader = Shader::new(&gl, vertex_source, selection_fragment_source)
+        .expect("Failed to create selection shader");
+    // Outline drawn around the block being aimed at
+    let selection_box = mesh::selection_box_mesh(&gl);
+
+    let item_vertex_source = include_str!("assets/shaders/vertex_instanced.glsl");
+    let item_shader = Shader::new(&gl, item_vertex_source, fragment_source)
+        .expect("Failed to create item shader");
+    let mut item_entities =
+        ItemEntities::new(ITEM_SIZE, ITEM_LIFETIME, ITEM_SPIN_SPEED);
+
     // Create mesh
     //let quad_mesh = create_quad_mesh(&gl);
 
     let tile_registry = Arc::new(tile::TileRegistry::new());
-    let mut _world = World::new();
+    // Placed in front of the player with the PlaceStructure action
+    let structure =
+        Structure::load(std::path::Path::new(STRUCTURE_PATH), &tile_registry)
+            .map_err(|e| eprintln!("{}", e))
+            .ok();
+    let mut _world = World::new(WorldConfig::default());
+    let view_cache = Arc::new(WorldViewCache::new(
+        VIEW_CACHE_SIZE,
+        _world.register_chunk_update_listener(),
+    ));
     let tessellator = Tessellator::new(
         RENDER_DISTANCE,
+        MESH_BUDGET,
+        MAX_MESHES_IN_FLIGHT,
         _world.register_chunk_update_listener(),
+        view_cache,
         tile_registry.clone(),
     );
     let physics_env = PhysicsEnvironment::new(
@@ -126,28 +204,54 @@ async fn main() {
     //    }
     //}
 
+    // Feet on top of the ground at the origin, or up in the air if the column
+    // has no ground at all
+    let spawn_height = World::surface_height(&world, &tile_registry, 0, 0)
+        .map_or(25.0, |surface| surface as f32 + 1.0 - PLAYER_BOX[0][1]);
     let mut player_obj = PhysicsObject {
-        position: [0.0, 25.0, 0.0],
+        position: [0.5, spawn_height, 0.5],
         velocity: [0.0, 0.0, 0.0],
-        collision_box: [[-0.3, -1.64, -0.3], [0.3, 1.8 - 1.62, 0.3]],
+        collision_box: PLAYER_BOX,
+        step_height: PLAYER_STEP_HEIGHT,
+        mode: MovementMode::Walking,
+        sneaking: false,
     };
+    // Same box with the top lowered, for crouching under things while sneaking
+    let mut sneaking_box = PLAYER_BOX;
+    sneaking_box[1][1] -= SNEAK_HEIGHT_DROP;
 
     let mut time: f32 = 0.0;
 
-    let mut test_text = TextOptions::new(15)
-        .set_alignment(text::Alignment::Top)
-        .render_spec("o pona kama tawa musi leko pona mi a")
-        .expect("Failed to create text");
-    let mut test_text2 = TextOptions::new(15)
+    let test_text = TextOptions::new(15).set_alignment(text::Alignment::Top);
+    let test_text2 = TextOptions::new(15)
+        .set_alignment(text::Alignment::Bottom)
+        .set_origin(text::MeshOrigin::BL);
+    let mut text_cache = TextCache::new();
+    // The FPS counter changes too often for the text cache, it keeps its own mesh
+    let fps_text = TextOptions::new(15);
+    let hotbar_text = TextOptions::new(15)
         .set_alignment(text::Alignment::Bottom)
-        .set_origin(text::MeshOrigin::BL)
-        .render_spec("f:#ff0000ff ma li pali mute... o awen.")
-        .expect("Failed to create text");
+        .set_origin(text::MeshOrigin::BR);
+    let mut frame_timer = FrameTimer::new(FRAME_TIMER_SAMPLES);
+    let mut fps_label = None;
+    let mut fps_refresh_timer = 0.0;
 
     // Create texture manager (for future use)
-    let texture_manager = TextureManager::new(&gl);
+    let mut texture_manager = TextureManager::new(&gl);
+    for animation in tile_registry.animations() {
+        texture_manager.declare_animation(
+            "terrain",
+            animation.region,
+            animation.frames,
+            animation.frame_time,
+        );
+    }
 
     camera.movement_speed = 5.0; // Set camera movement speed
+    camera.far = (CHUNK_SIZE_X * RENDER_DISTANCE) as f32;
+    camera.mouse_smoothing = MOUSE_SMOOTHING;
+    // FOV sprinting widens from
+    let base_fov = camera.fov;
 
     sdl.set_relative_mouse_mode(true).unwrap();
 
@@ -155,71 +259,55 @@ async fn main() {
         let current_frame = std::time::Instant::now();
         delta_time = current_frame.duration_since(last_frame).as_secs_f32();
         last_frame = current_frame;
-
-        shader.use_program(&gl);
-        texture_manager.set_texture_uniform(
-            &gl,
-            "terrain",
-            shader,
-            "terrainTexture",
-            0,
-        );
-
-        shader.set_float(&gl, "time", time);
-        shader.set_vec3(
-            &gl,
-            "cameraPos",
-            &[camera.position.x, camera.position.y, camera.position.z],
-        );
-
-        let mut breaking_block = false;
-        let mut placing_block = false;
+        frame_timer.record(delta_time);
+        texture_manager.advance_animations(delta_time);
+
+        let sun_direction = sky::sun_direction(time, DAY_LENGTH);
+        let daylight = sky::daylight(sun_direction);
+        let sky_color = sky::sky_color(daylight);
+        // Dropped items are lit and fogged the same way as the terrain
+        for program in [&item_shader, &shader] {
+            program.use_program(&gl);
+            texture_manager.set_texture_uniform(
+                &gl,
+                "terrain",
+                program,
+                "terrainTexture",
+                0,
+            );
+            TERRAIN_ATLAS.set_grid_uniform(&gl, program, "atlasGrid");
+
+            program.set_float(&gl, "time", time);
+            program.set_vec3(
+                &gl,
+                "cameraPos",
+                &[camera.position.x, camera.position.y, camera.position.z],
+            );
+            program.set_vec3(&gl, "sunDirection", &sun_direction);
+            program.set_float(&gl, "daylight", daylight);
+            program.set_float(&gl, "fogStart", camera.far * FOG_START);
+            program.set_float(&gl, "fogEnd", camera.far * FOG_END);
+            program.set_vec3(&gl, "fogColor", &sky_color);
+        }
+        let [sky_r, sky_g, sky_b] = sky_color;
+        unsafe {
+            gl.ClearColor(sky_r, sky_g, sky_b, 1.0);
+        }
 
         // handle events this frame
         while let Some(event) = sdl.poll_events() {
+            input.handle_event(&event.0);
             match event {
                 (events::Event::Quit, _) => break 'main_loop,
-                (
-                    events::Event::Key {
-                        pressed, keycode, ..
-                    },
-                    _,
-                ) => match keycode {
-                    events::SDLK_w => {
-                        if pressed {
-                            keys_pressed.insert('w');
-                        } else {
-                            keys_pressed.remove(&'w');
-                        }
-                    }
-                    events::SDLK_s => {
-                        if pressed {
-                            keys_pressed.insert('s');
-                        } else {
-                            keys_pressed.remove(&'s');
-                        }
+                (events::Event::WindowSizeChanged { width, height, .. }, _) => {
+                    window_size = (width as f32, height as f32);
+                    gui_projection = hud_projection(window_size);
+                    // The drawable can be larger than the window on high DPI screens
+                    let (drawable_width, drawable_height) = _win.get_drawable_size();
+                    unsafe {
+                        gl.Viewport(0, 0, drawable_width, drawable_height);
                     }
-                    events::SDLK_a => {
-                        if pressed {
-                            keys_pressed.insert('a');
-                        } else {
-                            keys_pressed.remove(&'a');
-                        }
-                    }
-                    events::SDLK_d => {
-                        if pressed {
-                            keys_pressed.insert('d');
-                        } else {
-                            keys_pressed.remove(&'d');
-                        }
-                    }
-                    events::SDLK_SPACE => {
-                        if pressed {
-                            player_obj.velocity[1] = 9.0; // Jump
-                        }
-                    }
-                    _ => (),
-                },
+                }
                 (
                     events::Event::MouseMotion {
                         x_delta, y_delta, ..
@@ -228,38 +316,58 @@ async fn main() {
                 ) => {
                     camera.process_mouse_movement(x_delta as f32, -(y_delta as f32));
                 }
-                (
-                    events::Event::MouseButton {
-                        win_id,
-                        mouse_id,
-                        button,
-                        pressed,
-                        clicks,
-                        x,
-                        y,
-                    },
-                    _,
-                ) => {
-                    //println!(
-                    //    "Mouse Button Event: win_id: {}, mouse_id: {}, button: {:?}, pressed: {}, clicks: {}, x: {}, y: {}",
-                    //    win_id, mouse_id, button, pressed, clicks, x, y
-                    //);
-                    if button == 1 {
-                        if pressed {
-                            // Handle left click (e.g., breaking a block)
-                            breaking_block = true;
-                        }
-                    } else if button == 3 {
-                        if pressed {
-                            // Handle right click (e.g., placing a block)
-                            placing_block = true;
-                        }
-                    }
-                }
+                // Scrolling down moves to the next slot
+                (events::Event::MouseWheel { y, .. }, _) => hotbar.scroll(-y),
                 _ => (),
             }
         }
         // now the events are clear
+        camera.smooth_look(delta_time);
+
+        if input.was_pressed(Action::CycleCameraView) {
+            camera.view = camera.view.next();
+        }
+        if input.was_pressed(Action::CycleMovementMode) {
+            // Cycles through walking, flying and noclip
+            player_obj.mode = match player_obj.mode {
+                MovementMode::Walking => MovementMode::Flying,
+                MovementMode::Flying => MovementMode::Noclip,
+                MovementMode::Noclip => MovementMode::Walking,
+            };
+            player_obj.velocity[1] = 0.0;
+        }
+        if player_obj.mode == MovementMode::Walking && input.is_pressed(Action::Sneak) {
+            player_obj.sneaking = true;
+            player_obj.collision_box = sneaking_box;
+        } else if player_obj.sneaking
+            && !physics_env
+                .is_colliding(player_obj.position, PLAYER_BOX)
+                .await
+        {
+            // Only stands back up where there is room for the head
+            player_obj.sneaking = false;
+            player_obj.collision_box = PLAYER_BOX;
+        }
+
+        let on_ground = player_obj.mode != MovementMode::Walking
+            || player_obj.is_on_ground(&physics_env).await;
+        if !input.is_pressed(Action::MoveForward) {
+            sprinting = false;
+        } else if on_ground {
+            // Can't start sprinting in the air, but keeps going after a jump
+            sprinting = input.is_pressed(Action::Sprint);
+        }
+        let target_fov = base_fov + if sprinting { SPRINT_FOV_KICK } else { 0.0 };
+        camera.fov +=
+            (target_fov - camera.fov) * (1.0 - (-delta_time / FOV_EASE_TIME).exp());
+
+        if input.was_pressed(Action::Jump) && player_obj.mode == MovementMode::Walking {
+            if on_ground {
+                player_obj.velocity[1] = JUMP_VELOCITY;
+            } else if player_obj.is_in_fluid(&physics_env).await {
+                player_obj.velocity[1] = SWIM_VELOCITY;
+            }
+        }
 
         let front = camera.front;
         let right = camera.right;
@@ -269,27 +377,27 @@ async fn main() {
         let mut intended_velocity = [0.0, 0.0, 0.0];
 
         // Process continuous key input
-        if keys_pressed.contains(&'w') {
+        if input.is_pressed(Action::MoveForward) {
             let front_player =
                 ultraviolet::Vec3::new(front.x, 0.0, front.z).normalized();
 
             intended_velocity[0] += front_player.x * PLAYER_SPEED;
             intended_velocity[2] += front_player.z * PLAYER_SPEED;
         }
-        if keys_pressed.contains(&'s') {
+        if input.is_pressed(Action::MoveBackward) {
             let back_player =
                 ultraviolet::Vec3::new(-front.x, 0.0, -front.z).normalized();
 
             intended_velocity[0] += back_player.x * PLAYER_SPEED;
             intended_velocity[2] += back_player.z * PLAYER_SPEED;
         }
-        if keys_pressed.contains(&'a') {
+        if input.is_pressed(Action::MoveLeft) {
             let left_player =
                 ultraviolet::Vec3::new(-right.x, 0.0, -right.z).normalized();
             intended_velocity[0] += left_player.x * PLAYER_SPEED;
             intended_velocity[2] += left_player.z * PLAYER_SPEED;
         }
-        if keys_pressed.contains(&'d') {
+        if input.is_pressed(Action::MoveRight) {
             let right_player =
                 ultraviolet::Vec3::new(right.x, 0.0, right.z).normalized();
 
@@ -297,10 +405,10 @@ async fn main() {
             intended_velocity[2] += right_player.z * PLAYER_SPEED;
         }
 
-        if !keys_pressed.contains(&'w')
-            && !keys_pressed.contains(&'s')
-            && !keys_pressed.contains(&'a')
-            && !keys_pressed.contains(&'d')
+        if !input.is_pressed(Action::MoveForward)
+            && !input.is_pressed(Action::MoveBackward)
+            && !input.is_pressed(Action::MoveLeft)
+            && !input.is_pressed(Action::MoveRight)
         {
         } else {
             if !(intended_velocity[0] == 0.0 && intended_velocity[2] == 0.0) {
@@ -314,6 +422,30 @@ async fn main() {
                 player_obj.velocity[2] += intended_normed.z * PLAYER_SPEED;
             }
         }
+        if sprinting {
+            if on_ground {
+                // Only forward movement speeds up, strafing stays at walking speed
+                let front_player =
+                    ultraviolet::Vec3::new(front.x, 0.0, front.z).normalized();
+                let extra_speed = PLAYER_SPEED * (SPRINT_SPEED_MULTIPLIER - 1.0);
+                sprint_boost =
+                    [front_player.x * extra_speed, front_player.z * extra_speed];
+            }
+            // In the air the boost keeps the direction of the take-off
+            player_obj.velocity[0] += sprint_boost[0];
+            player_obj.velocity[2] += sprint_boost[1];
+        }
+        if player_obj.mode != MovementMode::Walking {
+            player_obj.velocity[1] = match (
+                input.is_pressed(Action::Jump),
+                input.is_pressed(Action::Sneak),
+            ) {
+                (true, false) => FLY_SPEED,
+                (false, true) => -FLY_SPEED,
+                _ => 0.0,
+            };
+        }
+
         // Apply friction
         player_obj.velocity[0] *= 0.5; // Friction on X
         player_obj.velocity[2] *= 0.5; // Friction on Z
@@ -327,12 +459,7 @@ async fn main() {
 
         let model = Mat4::identity();
         let view = camera.get_view_matrix();
-        let projection = projection::rh_yup::perspective_gl(
-            90.0_f32.to_radians(),
-            800.0 / 600.0,
-            0.1,
-            (CHUNK_SIZE_X * RENDER_DISTANCE) as f32,
-        );
+        let projection = camera.projection(window_size.0 / window_size.1.max(1.0));
         let mvp = projection * view * model;
 
         shader.set_mat4(&gl, "mvp", &mvp);
@@ -352,54 +479,177 @@ async fn main() {
         //    player_obj.position, player_obj.velocity
         //);
 
-        player_obj.velocity[1] -= 32.6 * delta_time; // Simple gravity
-        player_obj.update(&physics_env, delta_time).await;
+        // The player stays put until the terrain around spawn can be drawn
+        if !spawn_ready {
+            let player_chunk = (
+                (player_obj.position[0].floor() as i32).div_euclid(CHUNK_SIZE_X),
+                (player_obj.position[1].floor() as i32).div_euclid(CHUNK_SIZE_X),
+                (player_obj.position[2].floor() as i32).div_euclid(CHUNK_SIZE_X),
+            );
+            let (ready, total) =
+                tessellator.chunks_ready_in(player_chunk, SPAWN_READY_RADIUS);
+            spawn_ready = ready == total;
+        }
+        if spawn_ready {
+            player_obj.update(&physics_env, delta_time).await;
+        }
 
-        camera.position = ultraviolet::Vec3::new(
+        physics_env
+            .ensure_for_all(world.clone(), tile_registry.clone())
+            .await;
+        physics_env.step_all(delta_time).await;
+
+        let eye_drop = if player_obj.sneaking {
+            SNEAK_HEIGHT_DROP
+        } else {
+            0.0
+        };
+        let eye = ultraviolet::Vec3::new(
             player_obj.position[0],
-            player_obj.position[1],
+            player_obj.position[1] - eye_drop,
             player_obj.position[2],
         );
+        if let Some(offset) = camera.third_person_offset() {
+            // Pull the camera in front of any wall between it and the eyes
+            let distance = physics_env
+                .raycast(eye.into(), offset.into(), THIRD_PERSON_DISTANCE)
+                .await
+                .map_or(THIRD_PERSON_DISTANCE, |hit| {
+                    (hit.distance - CAMERA_WALL_MARGIN).max(0.0)
+                });
+            camera.position = eye + offset * distance;
+        } else {
+            // Keeps the near plane from poking through walls the player is
+            // pressed against, noclip is free to look inside blocks
+            let clearance = if player_obj.mode == MovementMode::Noclip {
+                0.0
+            } else {
+                CAMERA_FACE_CLEARANCE
+            };
+            camera.position = physics_env
+                .clear_of_faces(eye.into(), clearance)
+                .await
+                .into();
+        }
 
+        let frustum_planes = camera.frustum_planes(projection);
         let unmet_meshes = tessellator
             .render_chunks(
                 Arc::clone(&world),
                 Arc::clone(&tile_registry),
                 (camera.position.x, camera.position.y, camera.position.z),
+                &frustum_planes,
                 &gl,
             )
             .await;
 
+        // Unload everything outside the render distance whenever the camera moves
+        // into another chunk
+        let camera_chunk = (
+            (camera.position.x.floor() as i32).div_euclid(CHUNK_SIZE_X),
+            (camera.position.y.floor() as i32).div_euclid(CHUNK_SIZE_X),
+            (camera.position.z.floor() as i32).div_euclid(CHUNK_SIZE_X),
+        );
+        if last_prune_center != Some(camera_chunk) {
+            World::prune(
+                &world,
+                camera_chunk,
+                RENDER_DISTANCE,
+                tessellator::MAX_MESH_PADDING,
+            );
+            tessellator.discard_outside(camera_chunk, RENDER_DISTANCE);
+            physics_env.discard_outside(camera_chunk, RENDER_DISTANCE);
+            last_prune_center = Some(camera_chunk);
+        }
+
+        let mut selected_voxel = None;
         if let Some(result) = physics_env
-            .raycast(camera.position.into(), camera.front.into(), 4.0)
+            .raycast(eye.into(), camera.front.into(), 4.0)
             .await
         {
             //println!(
             //    "Raycast hit: {:?} at distance: {}",
             //    result.hit_point, result.distance
             //);
-            let hit_as_float = [
-                result.voxel[0] as f32,
-                result.voxel[1] as f32,
-                result.voxel[2] as f32,
-            ];
-            shader.set_vec3(&gl, "cursorPos", &hit_as_float);
-            if breaking_block {
-                World::set_block(
+            selected_voxel = Some(result.voxel);
+            // Block id 0 means the hit chunk isn't loaded yet, leave it alone
+            // Blocks break after being held down for their hardness
+            let mining_target = (input.is_pressed(Action::Break)
+                && result.block_id != 0)
+                .then_some(result.voxel);
+            let hardness = tile_registry
+                .get_handler(result.block_id)
+                .map_or(0.0, |tile| tile.hardness());
+            if mining_progress.advance(mining_target, hardness, delta_time) {
+                let drops = World::break_block(
                     &world,
+                    &tile_registry,
                     result.voxel[0],
                     result.voxel[1],
                     result.voxel[2],
-                    0,
                 );
-            } else if placing_block {
-                World::set_block(
+                // Collision follows the break now, like it does for placing
+                let [x, y, z] = result.voxel;
+                physics_env.set_block(x, y, z, 0, 0);
+                // Drops fall from the middle of the broken block
+                let drop_position = [
+                    result.voxel[0] as f32 + 0.5,
+                    result.voxel[1] as f32 + 0.5,
+                    result.voxel[2] as f32 + 0.5,
+                ];
+                for (item, count) in drops {
+                    item_entities.spawn(&physics_env, item, count, drop_position);
+                }
+            } else if let Some(structure) = structure
+                .as_ref()
+                .filter(|_| input.was_pressed(Action::PlaceStructure))
+            {
+                structure.place(
                     &world,
-                    result.last_voxel[0],
-                    result.last_voxel[1],
-                    result.last_voxel[2],
-                    1,
+                    &physics_env,
+                    (
+                        result.last_voxel[0],
+                        result.last_voxel[1],
+                        result.last_voxel[2],
+                    ),
                 );
+                player_obj.unstick(&physics_env).await;
+            } else if input.was_pressed(Action::Place) && result.block_id != 0 {
+                let [x, y, z] = result.last_voxel;
+                World::set_block(&world, x, y, z, hotbar.selected_block());
+                // Push the player out of a block placed inside them now, rather
+                // than once the chunk update reaches the physics environment
+                let metadata = World::get_block_meta(&world, x, y, z);
+                physics_env.set_block(x, y, z, hotbar.selected_block(), metadata);
+                player_obj.unstick(&physics_env).await;
+            }
+        } else {
+            mining_progress.reset();
+        }
+
+        item_entities.update(&physics_env, delta_time);
+        item_shader.use_program(&gl);
+        item_shader.set_mat4(&gl, "mvp", &(projection * view));
+        item_entities.render(&gl, &physics_env, &tile_registry);
+
+        if let Some(voxel) = selected_voxel {
+            selection_shader.use_program(&gl);
+            let selection_model = Mat4::from_translation(ultraviolet::Vec3::new(
+                voxel[0] as f32,
+                voxel[1] as f32,
+                voxel[2] as f32,
+            ));
+            selection_shader.set_mat4(
+                &gl,
+                "mvp",
+                &(projection * view * selection_model),
+            );
+            unsafe {
+                gl.Disable(GL_CULL_FACE);
+            }
+            selection_box.render_lines(&gl);
+            unsafe {
+                gl.Enable(GL_CULL_FACE);
             }
         }
 
@@ -407,19 +657,16 @@ async fn main() {
         texture_manager.set_texture_uniform(
             &gl,
             "font",
-            text_shader,
+            &text_shader,
             "terrainTexture",
             0,
         );
 
-        let gui_projection =
-            projection::rh_yup::orthographic_gl(0.0, 800.0, 0.0, 600.0, -1.0, 1.0);
-
         let test_scale = Mat4::from_scale(16.0);
 
         let test_translation = Mat4::from_translation(ultraviolet::Vec3::new(
-            800.0 - 64.0,
-            600.0 - 64.0,
+            window_size.0 - 64.0,
+            window_size.1 - 64.0,
             0.0,
         ));
 
@@ -428,7 +675,11 @@ async fn main() {
         text_shader.set_mat4(&gl, "mvp", &gui_mvp);
         //shader.set_mat4(&gl, "mvp", &gui_projection);
 
-        test_text.get_mesh(&gl).render(&gl);
+        text_cache
+            .render_spec(&test_text, "o pona kama tawa musi leko pona mi a")
+            .expect("Failed to create text")
+            .get_mesh(&gl)
+            .render(&gl);
 
         let test_translation = Mat4::from_translation(ultraviolet::Vec3::new(
             20.0 + 64.0,
@@ -440,12 +691,58 @@ async fn main() {
 
         text_shader.set_mat4(&gl, "mvp", &gui_mvp);
 
-        if unmet_meshes > 0 {
-            test_text2.get_mesh(&gl).render(&gl);
+        if unmet_meshes > 0 || !spawn_ready {
+            text_cache
+                .render_spec(&test_text2, "f:#ff0000ff ma li pali mute... o awen.")
+                .expect("Failed to create text")
+                .get_mesh(&gl)
+                .render(&gl);
+        }
+
+        let hotbar_translation = Mat4::from_translation(ultraviolet::Vec3::new(
+            window_size.0 - 8.0,
+            8.0,
+            0.0,
+        ));
+        text_shader.set_mat4(
+            &gl,
+            "mvp",
+            &(gui_projection * hotbar_translation * test_scale),
+        );
+        text_cache
+            .render_spec(&hotbar_text, &format!("leko : {}", hotbar.selected_block()))
+            .expect("Failed to create hotbar text")
+            .get_mesh(&gl)
+            .render(&gl);
+
+        fps_refresh_timer -= delta_time;
+        if fps_refresh_timer <= 0.0 {
+            fps_refresh_timer = FPS_REFRESH_INTERVAL;
+            fps_label = Some(
+                fps_text
+                    .render_spec(&frame_timer.summary())
+                    .expect("Failed to create FPS text"),
+            );
+        }
+        let fps_translation = Mat4::from_translation(ultraviolet::Vec3::new(
+            window_size.0 - 8.0,
+            window_size.1 - 8.0,
+            0.0,
+        ));
+        text_shader.set_mat4(
+            &gl,
+            "mvp",
+            &(gui_projection * fps_translation * test_scale),
+        );
+        if let Some(fps_label) = &mut fps_label {
+            fps_label.get_mesh(&gl).render(&gl);
         }
 
         time += delta_time;
 
+        mesh::delete_dropped_meshes(&gl);
+
         _win.swap_window();
+        input.end_frame();
     }
 }
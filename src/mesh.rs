@@ -1,18 +1,64 @@
 use gl33::*;
-use std::mem::size_of;
+use std::{
+    cell::Cell,
+    mem::{offset_of, size_of},
+    sync::Mutex,
+};
+use ultraviolet::Mat4;
 
 pub type Vertex = [f32; 3];
 pub type UV = [f32; 2];
 pub type Color = [f32; 4];
 pub type MaterialId = [i32; 2];
+pub type Normal = [f32; 3];
 
-#[derive(Debug, Clone)]
+/// Layout of one vertex in a mesh's vertex buffer, every attribute of a vertex
+/// is stored next to each other so a mesh needs a single upload
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct PackedVertex {
+    pub position: Vertex,
+    pub uv: UV,
+    pub material_id: MaterialId,
+    pub color: Color,
+    pub light: Color,
+    pub normal: Normal,
+}
+
+#[derive(Debug)]
 pub struct Mesh {
     pub vao: u32,
     pub vbo: u32,
     pub ebo: Option<u32>,
     pub index_count: i32,
+    // GL_UNSIGNED_SHORT or GL_UNSIGNED_INT, whichever the indices were uploaded as
+    pub index_type: GLenum,
     pub vertex_count: i32,
+    // Per instance transforms for render_instanced, made on its first call
+    instance_vbo: Cell<u32>,
+}
+
+// First of the four vec4 attribute locations an instance transform takes
+const INSTANCE_TRANSFORM_LOCATION: u32 = 6;
+
+// Meshes with fewer vertices than this get 16 bit indices
+const SHORT_INDEX_VERTEX_LIMIT: usize = 1 << 16;
+
+// GL objects of meshes dropped since the last call to delete_dropped_meshes:
+// (vao, buffers). Meshes are often dropped on worker threads that can't touch
+// the GL context, so freeing them is left to the render thread.
+static DROPPED_MESHES: Mutex<Vec<(u32, Vec<u32>)>> = Mutex::new(Vec::new());
+
+/// Frees the GL objects of every mesh dropped so far, call once per frame from
+/// the thread that owns the GL context
+pub fn delete_dropped_meshes(gl: &GlFns) {
+    let dropped = std::mem::take(&mut *DROPPED_MESHES.lock().unwrap());
+    for (vao, buffers) in dropped {
+        unsafe {
+            gl.DeleteBuffers(buffers.len() as i32, buffers.as_ptr());
+            gl.DeleteVertexArrays(1, &vao);
+        }
+    }
 }
 
 impl Mesh {
@@ -24,6 +70,7 @@ impl Mesh {
         material_ids: Option<&[MaterialId]>,
         colors: Option<&[Color]>,
         light: Option<&[Color]>,
+        normals: Option<&[Normal]>,
     ) -> Self {
         if vertices.is_empty() {
             return Mesh {
@@ -31,9 +78,25 @@ impl Mesh {
                 vbo: 0,
                 ebo: None,
                 index_count: 0,
+                index_type: GL_UNSIGNED_INT,
                 vertex_count: 0,
+                instance_vbo: Cell::new(0),
             };
         }
+
+        // Transpose the separate attribute arrays into one interleaved buffer
+        let packed: Vec<PackedVertex> = (0..vertices.len())
+            .map(|i| PackedVertex {
+                position: vertices[i],
+                uv: uvs.map_or([0.0; 2], |uvs| uvs[i]),
+                material_id: material_ids
+                    .map_or([0; 2], |material_ids| material_ids[i]),
+                color: colors.map_or([0.0; 4], |colors| colors[i]),
+                light: light.map_or([0.0; 4], |light| light[i]),
+                normal: normals.map_or([0.0; 3], |normals| normals[i]),
+            })
+            .collect();
+
         unsafe {
             let mut vao = 0;
             gl.GenVertexArrays(1, &mut vao);
@@ -44,124 +107,90 @@ impl Mesh {
             gl.BindBuffer(GL_ARRAY_BUFFER, vbo);
             gl.BufferData(
                 GL_ARRAY_BUFFER,
-                (vertices.len() * size_of::<Vertex>()) as isize,
-                vertices.as_ptr().cast(),
+                (packed.len() * size_of::<PackedVertex>()) as isize,
+                packed.as_ptr().cast(),
                 GL_STATIC_DRAW,
             );
 
+            let stride = size_of::<PackedVertex>() as i32;
+            let float_attribute = |location: u32, size: i32, offset: usize| {
+                gl.VertexAttribPointer(
+                    location,
+                    size,
+                    GL_FLOAT,
+                    GL_FALSE.0 as u8,
+                    stride,
+                    offset as *const _,
+                );
+                gl.EnableVertexAttribArray(location);
+            };
+
             // Position attribute (location 0)
-            gl.VertexAttribPointer(
-                0,
-                3,
-                GL_FLOAT,
-                GL_FALSE.0 as u8,
-                size_of::<Vertex>() as i32,
-                0 as *const _,
-            );
-            gl.EnableVertexAttribArray(0);
+            float_attribute(0, 3, offset_of!(PackedVertex, position));
 
             let mut ebo = None;
+            let mut index_type = GL_UNSIGNED_INT;
             let (index_count, vertex_count) = if let Some(indices) = indices {
                 let mut ebo_id = 0;
                 gl.GenBuffers(1, &mut ebo_id);
                 gl.BindBuffer(GL_ELEMENT_ARRAY_BUFFER, ebo_id);
-                gl.BufferData(
-                    GL_ELEMENT_ARRAY_BUFFER,
-                    (indices.len() * size_of::<u32>()) as isize,
-                    indices.as_ptr().cast(),
-                    GL_STATIC_DRAW,
-                );
+                // Half the index data for the usual mesh, every index fits a u16
+                if vertices.len() < SHORT_INDEX_VERTEX_LIMIT {
+                    let short_indices: Vec<u16> =
+                        indices.iter().map(|&index| index as u16).collect();
+                    gl.BufferData(
+                        GL_ELEMENT_ARRAY_BUFFER,
+                        (short_indices.len() * size_of::<u16>()) as isize,
+                        short_indices.as_ptr().cast(),
+                        GL_STATIC_DRAW,
+                    );
+                    index_type = GL_UNSIGNED_SHORT;
+                } else {
+                    gl.BufferData(
+                        GL_ELEMENT_ARRAY_BUFFER,
+                        size_of_val(indices) as isize,
+                        indices.as_ptr().cast(),
+                        GL_STATIC_DRAW,
+                    );
+                }
                 ebo = Some(ebo_id);
                 (indices.len() as i32, vertices.len() as i32)
             } else {
                 (0, vertices.len() as i32)
             };
 
-            // UVs (location 1) - always set up the attribute even if no data
-            if let Some(uvs) = uvs {
-                let mut uv_vbo = 0;
-                gl.GenBuffers(1, &mut uv_vbo);
-                gl.BindBuffer(GL_ARRAY_BUFFER, uv_vbo);
-                gl.BufferData(
-                    GL_ARRAY_BUFFER,
-                    (uvs.len() * size_of::<UV>()) as isize,
-                    uvs.as_ptr().cast(),
-                    GL_STATIC_DRAW,
-                );
-                gl.VertexAttribPointer(
-                    1,
-                    2,
-                    GL_FLOAT,
-                    GL_FALSE.0 as u8,
-                    size_of::<UV>() as i32,
-                    0 as *const _,
-                );
-                gl.EnableVertexAttribArray(1);
+            // Attributes without data are left disabled so the shader sees
+            // the default value instead of zeros
+            // UVs (location 1)
+            if uvs.is_some() {
+                float_attribute(1, 2, offset_of!(PackedVertex, uv));
             }
 
             // Material IDs (location 2)
-            if let Some(material_ids) = material_ids {
-                let mut material_vbo = 0;
-                gl.GenBuffers(1, &mut material_vbo);
-                gl.BindBuffer(GL_ARRAY_BUFFER, material_vbo);
-                gl.BufferData(
-                    GL_ARRAY_BUFFER,
-                    (material_ids.len() * size_of::<MaterialId>()) as isize,
-                    material_ids.as_ptr().cast(),
-                    GL_STATIC_DRAW,
-                );
+            if material_ids.is_some() {
                 gl.VertexAttribIPointer(
                     2,
                     2,
                     GL_INT,
-                    size_of::<MaterialId>() as i32,
-                    0 as *const _,
+                    stride,
+                    offset_of!(PackedVertex, material_id) as *const _,
                 );
                 gl.EnableVertexAttribArray(2);
             }
 
             // Colors (location 3)
-            if let Some(colors) = colors {
-                let mut color_vbo = 0;
-                gl.GenBuffers(1, &mut color_vbo);
-                gl.BindBuffer(GL_ARRAY_BUFFER, color_vbo);
-                gl.BufferData(
-                    GL_ARRAY_BUFFER,
-                    (colors.len() * size_of::<Color>()) as isize,
-                    colors.as_ptr().cast(),
-                    GL_STATIC_DRAW,
-                );
-                gl.VertexAttribPointer(
-                    3,
-                    4,
-                    GL_FLOAT,
-                    GL_FALSE.0 as u8,
-                    size_of::<Color>() as i32,
-                    0 as *const _,
-                );
-                gl.EnableVertexAttribArray(3);
+            if colors.is_some() {
+                float_attribute(3, 4, offset_of!(PackedVertex, color));
             }
 
             // Light (location 4), same as colors
-            if let Some(light) = light {
-                let mut light_vbo = 0;
-                gl.GenBuffers(1, &mut light_vbo);
-                gl.BindBuffer(GL_ARRAY_BUFFER, light_vbo);
-                gl.BufferData(
-                    GL_ARRAY_BUFFER,
-                    (light.len() * size_of::<Color>()) as isize,
-                    light.as_ptr().cast(),
-                    GL_STATIC_DRAW,
-                );
-                gl.VertexAttribPointer(
-                    4,
-                    4,
-                    GL_FLOAT,
-                    GL_FALSE.0 as u8,
-                    size_of::<Color>() as i32,
-                    0 as *const _,
-                );
-                gl.EnableVertexAttribArray(4);
+            if light.is_some() {
+                float_attribute(4, 4, offset_of!(PackedVertex, light));
+            }
+
+            // Normals (location 5), left out by meshes that aren't lit
+            if normals.is_some() {
+                float_attribute(5, 3, offset_of!(PackedVertex, normal));
             }
 
             gl.BindVertexArray(0);
@@ -171,7 +200,9 @@ impl Mesh {
                 vbo,
                 ebo,
                 index_count,
+                index_type,
                 vertex_count,
+                instance_vbo: Cell::new(0),
             }
         }
     }
@@ -186,7 +217,7 @@ impl Mesh {
                 gl.DrawElements(
                     GL_TRIANGLES,
                     self.index_count,
-                    GL_UNSIGNED_INT,
+                    self.index_type,
                     0 as *const _,
                 );
             } else {
@@ -195,36 +226,236 @@ impl Mesh {
         }
     }
 
-    pub fn update_colors(&self, gl: &GlFns, colors: &[Color]) {
+    /// Draws the mesh as a list of line segments, every pair of indices (or of
+    /// vertices without indices) is one segment
+    pub fn render_lines(&self, gl: &GlFns) {
+        if self.vertex_count == 0 {
+            return;
+        }
+        unsafe {
+            gl.BindVertexArray(self.vao);
+            if self.ebo.is_some() {
+                gl.DrawElements(
+                    GL_LINES,
+                    self.index_count,
+                    self.index_type,
+                    0 as *const _,
+                );
+            } else {
+                gl.DrawArrays(GL_LINES, 0, self.vertex_count);
+            }
+        }
+    }
+
+    /// Draws one copy of the mesh per transform in a single draw call. The
+    /// vertex shader reads the transform as a mat4 at location 6, see
+    /// vertex_instanced.glsl.
+    pub fn render_instanced(&self, gl: &GlFns, transforms: &[Mat4]) {
+        if self.vertex_count == 0 || transforms.is_empty() {
+            return;
+        }
         unsafe {
             gl.BindVertexArray(self.vao);
-            let mut color_vbo = 0;
-            gl.GenBuffers(1, &mut color_vbo);
-            gl.BindBuffer(GL_ARRAY_BUFFER, color_vbo);
+            if self.instance_vbo.get() == 0 {
+                let mut instance_vbo = 0;
+                gl.GenBuffers(1, &mut instance_vbo);
+                gl.BindBuffer(GL_ARRAY_BUFFER, instance_vbo);
+                // A mat4 attribute is four vec4 columns, each advancing once
+                // per instance instead of once per vertex
+                for column in 0..4 {
+                    let location = INSTANCE_TRANSFORM_LOCATION + column;
+                    gl.VertexAttribPointer(
+                        location,
+                        4,
+                        GL_FLOAT,
+                        GL_FALSE.0 as u8,
+                        size_of::<Mat4>() as i32,
+                        (column as usize * 4 * size_of::<f32>()) as *const _,
+                    );
+                    gl.EnableVertexAttribArray(location);
+                    gl.VertexAttribDivisor(location, 1);
+                }
+                self.instance_vbo.set(instance_vbo);
+            }
+            gl.BindBuffer(GL_ARRAY_BUFFER, self.instance_vbo.get());
             gl.BufferData(
                 GL_ARRAY_BUFFER,
-                (colors.len() * size_of::<Color>()) as isize,
-                colors.as_ptr().cast(),
-                GL_DYNAMIC_DRAW,
+                size_of_val(transforms) as isize,
+                transforms.as_ptr().cast(),
+                GL_STREAM_DRAW,
             );
+            if self.ebo.is_some() {
+                gl.DrawElementsInstanced(
+                    GL_TRIANGLES,
+                    self.index_count,
+                    self.index_type,
+                    0 as *const _,
+                    transforms.len() as i32,
+                );
+            } else {
+                gl.DrawArraysInstanced(
+                    GL_TRIANGLES,
+                    0,
+                    self.vertex_count,
+                    transforms.len() as i32,
+                );
+            }
+        }
+    }
+
+    // Overwrites the field at `offset` of every vertex in place through a
+    // mapped range of the existing buffer, the other attributes are kept
+    fn update_field<T: Copy>(&self, gl: &GlFns, values: &[T], offset: usize) {
+        assert_eq!(
+            values.len(),
+            self.vertex_count as usize,
+            "Mesh updates must provide a value for every vertex"
+        );
+        if self.vbo == 0 {
+            return;
+        }
+        let stride = size_of::<PackedVertex>();
+        unsafe {
+            gl.BindBuffer(GL_ARRAY_BUFFER, self.vbo);
+            let mapped = gl
+                .MapBufferRange(
+                    GL_ARRAY_BUFFER,
+                    0,
+                    (values.len() * stride) as isize,
+                    GL_MAP_WRITE_BIT,
+                )
+                .cast::<u8>();
+            if mapped.is_null() {
+                panic!("Failed to map mesh vertex buffer");
+            }
+            for (i, value) in values.iter().enumerate() {
+                mapped
+                    .add(i * stride + offset)
+                    .cast::<T>()
+                    .write_unaligned(*value);
+            }
+            gl.UnmapBuffer(GL_ARRAY_BUFFER);
+        }
+    }
+
+    // Points a float attribute at its field of the vertex buffer, for
+    // attributes the mesh may have been created without
+    fn enable_float_attribute(
+        &self,
+        gl: &GlFns,
+        location: u32,
+        size: i32,
+        offset: usize,
+    ) {
+        unsafe {
+            gl.BindVertexArray(self.vao);
+            gl.BindBuffer(GL_ARRAY_BUFFER, self.vbo);
             gl.VertexAttribPointer(
-                3,
-                4,
+                location,
+                size,
                 GL_FLOAT,
                 GL_FALSE.0 as u8,
-                size_of::<Color>() as i32,
-                0 as *const _,
+                size_of::<PackedVertex>() as i32,
+                offset as *const _,
             );
-            gl.EnableVertexAttribArray(3);
+            gl.EnableVertexAttribArray(location);
+            gl.BindVertexArray(0);
+        }
+    }
+
+    pub fn update_vertices(&self, gl: &GlFns, vertices: &[Vertex]) {
+        self.update_field(gl, vertices, offset_of!(PackedVertex, position));
+    }
+
+    pub fn update_uvs(&self, gl: &GlFns, uvs: &[UV]) {
+        self.update_field(gl, uvs, offset_of!(PackedVertex, uv));
+        self.enable_float_attribute(gl, 1, 2, offset_of!(PackedVertex, uv));
+    }
+
+    pub fn update_colors(&self, gl: &GlFns, colors: &[Color]) {
+        self.update_field(gl, colors, offset_of!(PackedVertex, color));
+        self.enable_float_attribute(gl, 3, 4, offset_of!(PackedVertex, color));
+    }
+}
+
+impl Mesh {
+    // Takes the GL object ids out of the mesh so they are only freed once
+    fn take_gl_objects(&mut self) -> Option<(u32, Vec<u32>)> {
+        if self.vao == 0 {
+            return None;
+        }
+        let mut buffers = vec![self.vbo];
+        buffers.extend(self.ebo.take());
+        if self.instance_vbo.get() != 0 {
+            buffers.push(self.instance_vbo.replace(0));
+        }
+        let vao = self.vao;
+        self.vao = 0;
+        self.vbo = 0;
+        self.vertex_count = 0;
+        self.index_count = 0;
+        Some((vao, buffers))
+    }
+
+    /// Frees the mesh's GL objects right away, for callers on the GL thread
+    pub fn delete(mut self, gl: &GlFns) {
+        if let Some((vao, buffers)) = self.take_gl_objects() {
+            unsafe {
+                gl.DeleteBuffers(buffers.len() as i32, buffers.as_ptr());
+                gl.DeleteVertexArrays(1, &vao);
+            }
         }
     }
 }
 
 impl Drop for Mesh {
     fn drop(&mut self) {
-        // Note: This requires a GL context to be current
-        // In a real game, you'd want proper resource management
+        // No GL handle here, queue the objects for delete_dropped_meshes
+        if let Some(gl_objects) = self.take_gl_objects() {
+            DROPPED_MESHES.lock().unwrap().push(gl_objects);
+        }
+    }
+}
+
+// How far the selection box sticks out of its voxel, keeps the lines from
+// z-fighting with the voxel's faces
+const SELECTION_BOX_MARGIN: f32 = 2e-3;
+const SELECTION_BOX_COLOR: Color = [0.0, 0.0, 0.0, 0.6];
+
+/// Line mesh of the twelve edges of the unit cube from 0 to 1, meant to be
+/// translated onto the targeted voxel and drawn with render_lines
+pub fn selection_box_mesh(gl: &GlFns) -> Mesh {
+    let (low, high) = (-SELECTION_BOX_MARGIN, 1.0 + SELECTION_BOX_MARGIN);
+    // Corner i has x from bit 0, y from bit 1 and z from bit 2
+    let vertices: Vec<Vertex> = (0..8)
+        .map(|i| {
+            [
+                if i & 1 == 0 { low } else { high },
+                if i & 2 == 0 { low } else { high },
+                if i & 4 == 0 { low } else { high },
+            ]
+        })
+        .collect();
+    // Every edge joins two corners differing in a single bit
+    let mut indices = Vec::with_capacity(24);
+    for corner in 0..8u32 {
+        for bit in [1, 2, 4] {
+            if corner & bit == 0 {
+                indices.extend([corner, corner | bit]);
+            }
+        }
     }
+    let colors = vec![SELECTION_BOX_COLOR; vertices.len()];
+    Mesh::new(
+        gl,
+        &vertices,
+        Some(&indices),
+        None,
+        None,
+        Some(&colors),
+        None,
+        None,
+    )
 }
 
 pub struct MeshParams {
@@ -234,6 +465,7 @@ pub struct MeshParams {
     pub material_ids: Option<Vec<MaterialId>>,
     pub colors: Option<Vec<Color>>,
     pub light: Option<Vec<Color>>,
+    pub normals: Option<Vec<Normal>>,
 }
 
 pub enum MeshEnvelope {
@@ -257,6 +489,7 @@ impl MeshEnvelope {
                     params.material_ids.as_deref(),
                     params.colors.as_deref(),
                     params.light.as_deref(),
+                    params.normals.as_deref(),
                 );
                 *self = MeshEnvelope::Mesh(mesh);
                 if let MeshEnvelope::Mesh(m) = self {
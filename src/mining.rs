@@ -0,0 +1,82 @@
+/// Tracks how long the player has been breaking the voxel they're aiming at
+pub struct MiningProgress {
+    target: Option<[i32; 3]>,
+    elapsed: f32,
+}
+
+impl Default for MiningProgress {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MiningProgress {
+    pub fn new() -> Self {
+        Self {
+            target: None,
+            elapsed: 0.0,
+        }
+    }
+
+    /// Mines `target` for another `delta_time` seconds and returns true once
+    /// the time spent on it reaches `hardness`. Aiming at another voxel, or at
+    /// nothing, starts over from zero.
+    pub fn advance(
+        &mut self,
+        target: Option<[i32; 3]>,
+        hardness: f32,
+        delta_time: f32,
+    ) -> bool {
+        if target != self.target {
+            self.target = target;
+            self.elapsed = 0.0;
+        }
+        if self.target.is_none() {
+            return false;
+        }
+        self.elapsed += delta_time;
+        if self.elapsed >= hardness {
+            self.reset();
+            return true;
+        }
+        false
+    }
+
+    pub fn reset(&mut self) {
+        self.target = None;
+        self.elapsed = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_accumulates_on_the_same_target() {
+        let mut progress = MiningProgress::new();
+        assert!(!progress.advance(Some([1, 2, 3]), 1.0, 0.4));
+        assert!(!progress.advance(Some([1, 2, 3]), 1.0, 0.4));
+        assert!(progress.advance(Some([1, 2, 3]), 1.0, 0.4));
+    }
+
+    #[test]
+    fn changing_target_starts_over() {
+        let mut progress = MiningProgress::new();
+        assert!(!progress.advance(Some([1, 2, 3]), 1.0, 0.8));
+        assert!(!progress.advance(Some([1, 2, 4]), 1.0, 0.8));
+        assert!(!progress.advance(None, 1.0, 0.8));
+        assert!(!progress.advance(Some([1, 2, 4]), 1.0, 0.8));
+        assert!(progress.advance(Some([1, 2, 4]), 1.0, 0.8));
+    }
+
+    #[test]
+    fn completes_at_the_hardness_and_starts_over() {
+        let mut progress = MiningProgress::new();
+        assert!(!progress.advance(Some([0, 0, 0]), 0.5, 0.25));
+        assert!(progress.advance(Some([0, 0, 0]), 0.5, 0.25));
+        // The same voxel again, like a new block placed where the old one was
+        assert!(!progress.advance(Some([0, 0, 0]), 0.5, 0.25));
+        assert!(progress.advance(Some([0, 0, 0]), 0.5, 0.25));
+    }
+}
@@ -6,19 +6,79 @@ use std::{
 use tokio::{spawn, sync::mpsc::UnboundedReceiver};
 
 use crate::{
-    tile::{TileRegistry},
+    tile::{FULL_BLOCK_BOX, TileRegistry},
     utils::QueuedItem,
-    world::{CHUNK_SIZE, CHUNK_SIZE_X, ChunkUpdateMessage, World, WorldView},
+    world::{
+        BlockId, CHUNK_SIZE, CHUNK_SIZE_X, ChunkChange, ChunkUpdateMessage, World,
+        WorldView,
+    },
 };
 
+/// Handle of an object registered with a PhysicsEnvironment
+pub type ObjectId = u32;
+
+#[derive(Clone)]
 pub struct PhysicsObject {
     pub position: [f32; 3],
     pub velocity: [f32; 3],
     pub collision_box: [[f32; 3]; 2],
+    // Tallest ledge walked onto automatically while grounded, 0 disables it
+    pub step_height: f32,
+    pub mode: MovementMode,
+    // Keeps a grounded walking object from moving off the edge it stands on
+    pub sneaking: bool,
+}
+
+/// How an object's movement is simulated by PhysicsObject::update
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MovementMode {
+    /// Affected by gravity and fluids, collides with solid voxels
+    Walking,
+    /// Ignores gravity and fluids but still collides with solid voxels
+    Flying,
+    /// Moves freely through everything
+    Noclip,
 }
 
+// Step height objects are created with
+pub const DEFAULT_STEP_HEIGHT: f32 = 0.6;
+
+pub const DEFAULT_GRAVITY: f32 = 32.6;
+pub const DEFAULT_TERMINAL_VELOCITY: f32 = 78.4;
+
+// Fraction of gravity still pulling on an object inside a fluid
+const FLUID_GRAVITY_SCALE: f32 = 0.25;
+// Fraction of horizontal velocity kept after a second inside a fluid
+const FLUID_DRAG: f32 = 0.02;
+// Fastest an object can sink through a fluid, in blocks per second
+const FLUID_SINK_SPEED: f32 = 3.0;
+
+// Gap kept between a moving box and the voxel face it stopped against
+const COLLISION_SKIN: f32 = 1e-3;
+// Overlaps up to this deep are floating point noise from resting against a
+// face, both is_colliding and sweep treat them as touching
+const OVERLAP_TOLERANCE: f32 = 1e-3;
+// Voxel ranges around a box are padded by this, so voxels whose face the box
+// rests on are always tested
+const VOXEL_RANGE_PADDING: f32 = 1e-3;
+// Distances push_out tries along each axis, nearest first
+const PUSH_OUT_DISTANCES: [f32; 3] = [0.01, 0.1, 0.5];
+
+// How far below the collision box is_on_ground looks, must exceed the skin
+const GROUND_PROBE_DEPTH: f32 = 0.05;
+
 struct VoxelCollisionChunk {
-    pub is_solid: [bool; CHUNK_SIZE as usize],
+    // Per voxel data, on the heap since chunks are built inside futures
+    pub is_solid: Vec<bool>,
+    // Id of the fluid filling each voxel, 0 where there is none
+    pub fluid_ids: Vec<BlockId>,
+    // Copies of the block ids and metadata so hits can be reported without a
+    // world lookup
+    pub block_ids: Vec<BlockId>,
+    pub metadata: Vec<u8>,
+    // Collision boxes of the solid voxels that don't fill their whole voxel,
+    // like slabs and stairs, keyed by voxel index
+    pub partial_boxes: HashMap<usize, &'static [[[f32; 3]; 2]]>,
 }
 
 pub struct RaycastHit {
@@ -28,6 +88,29 @@ pub struct RaycastHit {
     pub uv: [f32; 2],         // UV coordinates on the hit face (0.0-1.0)
     pub distance: f32,        // Distance from origin to hit
     pub face: usize,          // Which face was hit: 0=X, 1=Y, 2=Z
+    pub block_id: BlockId,    // Block that was hit, 0 if its chunk wasn't ready
+    pub metadata: u8,         // Metadata of the block that was hit
+}
+
+impl RaycastHit {
+    /// Outward normal of the face that was hit, pointing from `voxel` to
+    /// `last_voxel`. All zeros when the ray started inside the hit block.
+    pub fn face_normal(&self) -> [i32; 3] {
+        [
+            self.last_voxel[0] - self.voxel[0],
+            self.last_voxel[1] - self.voxel[1],
+            self.last_voxel[2] - self.voxel[2],
+        ]
+    }
+}
+
+// First and last voxel overlapped by the span from `min` to `max` along one
+// axis, padded so both collision tests round the same way
+fn voxel_span(min: f32, max: f32) -> (i32, i32) {
+    (
+        (min - VOXEL_RANGE_PADDING).floor() as i32,
+        (max + VOXEL_RANGE_PADDING).floor() as i32,
+    )
 }
 
 impl VoxelCollisionChunk {
@@ -36,7 +119,13 @@ impl VoxelCollisionChunk {
         tile_registry: Arc<TileRegistry>,
         (chunk_x, chunk_y, chunk_z): (i32, i32, i32),
     ) -> Self {
-        let mut data = [false; CHUNK_SIZE as usize];
+        let mut chunk = VoxelCollisionChunk {
+            is_solid: vec![false; CHUNK_SIZE as usize],
+            fluid_ids: vec![0; CHUNK_SIZE as usize],
+            block_ids: vec![0; CHUNK_SIZE as usize],
+            metadata: vec![0; CHUNK_SIZE as usize],
+            partial_boxes: HashMap::new(),
+        };
 
         let start_x = chunk_x * CHUNK_SIZE_X;
         let start_y = chunk_y * CHUNK_SIZE_X;
@@ -58,19 +147,46 @@ impl VoxelCollisionChunk {
                     if block_id == 0 {
                         continue; // Skip air blocks
                     }
-                    let tile =
-                        tile_registry.get_handler(block_id).expect("Tile not found");
-                    if tile.is_solid() {
-                        let index =
-                            (x + y * CHUNK_SIZE_X + z * CHUNK_SIZE_X * CHUNK_SIZE_X)
-                                as usize;
-                        data[index] = true;
-                    }
+                    let index = (x + y * CHUNK_SIZE_X + z * CHUNK_SIZE_X * CHUNK_SIZE_X)
+                        as usize;
+                    let metadata =
+                        view.get_block_meta(start_x + x, start_y + y, start_z + z);
+                    chunk.set_voxel(&tile_registry, index, block_id, metadata);
                 }
             }
         }
 
-        VoxelCollisionChunk { is_solid: data }
+        chunk
+    }
+
+    /// Replaces the collision data of one voxel with that of `block_id`
+    fn set_voxel(
+        &mut self,
+        tile_registry: &TileRegistry,
+        index: usize,
+        block_id: BlockId,
+        metadata: u8,
+    ) {
+        self.block_ids[index] = block_id;
+        self.metadata[index] = metadata;
+        self.is_solid[index] = false;
+        self.fluid_ids[index] = 0;
+        self.partial_boxes.remove(&index);
+        if block_id == 0 {
+            return;
+        }
+        let tile = tile_registry.get_handler(block_id).expect("Tile not found");
+        let boxes = tile.collision_boxes(metadata);
+        if !boxes.is_empty() {
+            self.is_solid[index] = true;
+            // Full blocks are the common case, so only other shapes are kept
+            if boxes != [FULL_BLOCK_BOX] {
+                self.partial_boxes.insert(index, boxes);
+            }
+        }
+        if tile.is_fluid() {
+            self.fluid_ids[index] = block_id;
+        }
     }
 }
 
@@ -78,6 +194,12 @@ pub struct PhysicsEnvironment {
     collision_chunks:
         Arc<Mutex<HashMap<(i32, i32, i32), QueuedItem<VoxelCollisionChunk>>>>,
     tile_registry: Arc<TileRegistry>,
+    // Downward acceleration in blocks per second squared
+    gravity: Mutex<f32>,
+    // Fastest an object can fall, in blocks per second
+    terminal_velocity: Mutex<f32>,
+    objects: Mutex<HashMap<ObjectId, PhysicsObject>>,
+    next_object_id: Mutex<ObjectId>,
 }
 
 impl PhysicsEnvironment {
@@ -107,6 +229,19 @@ impl PhysicsEnvironment {
                 if !has_chunk {
                     continue;
                 }
+                // A single edit only touches one voxel of ready collision data,
+                // anything else rebuilds the whole chunk
+                if let ChunkChange::Block {
+                    position: (x, y, z),
+                    new_id,
+                    ..
+                } = chunk_update.change
+                {
+                    let metadata = World::get_block_meta(&chunk_update.world, x, y, z);
+                    if env.set_block(x, y, z, new_id, metadata) {
+                        continue;
+                    }
+                }
                 let chunk = VoxelCollisionChunk::from_world(
                     chunk_update.world.clone(),
                     env.tile_registry.clone(),
@@ -144,6 +279,10 @@ impl PhysicsEnvironment {
         let env = Arc::new(PhysicsEnvironment {
             collision_chunks: Arc::new(Mutex::new(HashMap::new())),
             tile_registry,
+            gravity: Mutex::new(DEFAULT_GRAVITY),
+            terminal_velocity: Mutex::new(DEFAULT_TERMINAL_VELOCITY),
+            objects: Mutex::new(HashMap::new()),
+            next_object_id: Mutex::new(0),
         });
         spawn(PhysicsEnvironment::handle_chunk_updates(
             env.clone(),
@@ -152,8 +291,71 @@ impl PhysicsEnvironment {
         env
     }
 
-    pub fn discard_chunk(&mut self, chunk_pos: (i32, i32, i32)) {
-        self.collision_chunks.lock().unwrap().remove(&chunk_pos);
+    /// Applies a single block edit to ready collision data right away, ahead
+    /// of the chunk update the world sends for it. Returns false when the
+    /// chunk's collision data isn't ready, the update rebuilds it then.
+    pub fn set_block(
+        &self,
+        x: i32,
+        y: i32,
+        z: i32,
+        block_id: BlockId,
+        metadata: u8,
+    ) -> bool {
+        let chunk_pos = (
+            x.div_euclid(CHUNK_SIZE_X),
+            y.div_euclid(CHUNK_SIZE_X),
+            z.div_euclid(CHUNK_SIZE_X),
+        );
+        let mut chunks_handle = self.collision_chunks.lock().unwrap();
+        let Some(QueuedItem::Ready(chunk)) = chunks_handle.get_mut(&chunk_pos) else {
+            return false;
+        };
+        let index = (x.rem_euclid(CHUNK_SIZE_X)
+            + y.rem_euclid(CHUNK_SIZE_X) * CHUNK_SIZE_X
+            + z.rem_euclid(CHUNK_SIZE_X) * CHUNK_SIZE_X * CHUNK_SIZE_X)
+            as usize;
+        chunk.set_voxel(&self.tile_registry, index, block_id, metadata);
+        true
+    }
+
+    pub fn gravity(&self) -> f32 {
+        *self.gravity.lock().unwrap()
+    }
+
+    pub fn set_gravity(&self, gravity: f32) {
+        *self.gravity.lock().unwrap() = gravity;
+    }
+
+    pub fn terminal_velocity(&self) -> f32 {
+        *self.terminal_velocity.lock().unwrap()
+    }
+
+    pub fn set_terminal_velocity(&self, terminal_velocity: f32) {
+        *self.terminal_velocity.lock().unwrap() = terminal_velocity;
+    }
+
+    pub fn discard_chunk(&self, chunk_pos: (i32, i32, i32)) {
+        if let Some(chunk) = self.collision_chunks.lock().unwrap().remove(&chunk_pos) {
+            // Nothing will read the result anymore
+            chunk.cancel();
+        }
+    }
+
+    /// Discards the collision data of every chunk further than `keep_radius`
+    /// from `center`
+    pub fn discard_outside(&self, center: (i32, i32, i32), keep_radius: i32) {
+        let out_of_range: Vec<_> = self
+            .collision_chunks
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|&&pos| !World::chunk_in_radius(center, pos, keep_radius))
+            .copied()
+            .collect();
+        for chunk_pos in out_of_range {
+            self.discard_chunk(chunk_pos);
+        }
     }
 
     pub async fn solid_at(&self, x: i32, y: i32, z: i32) -> bool {
@@ -179,6 +381,140 @@ impl PhysicsEnvironment {
         true // Default to solid if chunk not found
     }
 
+    /// Moves `position` back from the faces of the solid voxels next to it so
+    /// it ends up at least `clearance` away from each of them, used to keep
+    /// the camera's near plane out of walls. The clearance is capped at half a
+    /// block so a voxel walled in on both sides still has room for it.
+    /// Positions inside a solid voxel are left alone.
+    pub async fn clear_of_faces(&self, position: [f32; 3], clearance: f32) -> [f32; 3] {
+        let voxel = position.map(|value| value.floor() as i32);
+        if clearance <= 0.0 || self.solid_at(voxel[0], voxel[1], voxel[2]).await {
+            return position;
+        }
+        let clearance = clearance.min(0.5);
+        let mut cleared = position;
+        for axis in 0..3 {
+            let neighbor = |offset: i32| {
+                let mut neighbor = voxel;
+                neighbor[axis] += offset;
+                neighbor
+            };
+            let [bx, by, bz] = neighbor(-1);
+            let below = self.solid_at(bx, by, bz).await;
+            let [ax, ay, az] = neighbor(1);
+            let above = self.solid_at(ax, ay, az).await;
+
+            let low = voxel[axis] as f32 + if below { clearance } else { 0.0 };
+            let high = (voxel[axis] + 1) as f32 - if above { clearance } else { 0.0 };
+            cleared[axis] = position[axis].clamp(low, high);
+        }
+        cleared
+    }
+
+    /// Collision boxes of the given block relative to its minimum corner. Chunks
+    /// whose collision data isn't ready are treated as full blocks.
+    pub fn collision_boxes_at(
+        &self,
+        x: i32,
+        y: i32,
+        z: i32,
+    ) -> &'static [[[f32; 3]; 2]] {
+        let chunk_x = x.div_euclid(CHUNK_SIZE_X);
+        let chunk_y = y.div_euclid(CHUNK_SIZE_X);
+        let chunk_z = z.div_euclid(CHUNK_SIZE_X);
+
+        let mut chunks_handle = self.collision_chunks.lock().unwrap();
+
+        let Some(chunk) = chunks_handle.get_mut(&(chunk_x, chunk_y, chunk_z)) else {
+            return &[FULL_BLOCK_BOX];
+        };
+        let Some(chunk) = chunk.try_get() else {
+            return &[FULL_BLOCK_BOX];
+        };
+        let local_x = x.rem_euclid(CHUNK_SIZE_X);
+        let local_y = y.rem_euclid(CHUNK_SIZE_X);
+        let local_z = z.rem_euclid(CHUNK_SIZE_X);
+        let index = (local_x
+            + local_y * CHUNK_SIZE_X
+            + local_z * CHUNK_SIZE_X * CHUNK_SIZE_X) as usize;
+        if !chunk.is_solid[index] {
+            return &[];
+        }
+        match chunk.partial_boxes.get(&index) {
+            Some(&boxes) => boxes,
+            None => &[FULL_BLOCK_BOX],
+        }
+    }
+
+    /// Id of the fluid at the given block, None for anything else including
+    /// chunks whose collision data isn't ready yet
+    pub fn fluid_at(&self, x: i32, y: i32, z: i32) -> Option<BlockId> {
+        let chunk_x = x.div_euclid(CHUNK_SIZE_X);
+        let chunk_y = y.div_euclid(CHUNK_SIZE_X);
+        let chunk_z = z.div_euclid(CHUNK_SIZE_X);
+
+        let mut chunks_handle = self.collision_chunks.lock().unwrap();
+
+        let chunk = chunks_handle
+            .get_mut(&(chunk_x, chunk_y, chunk_z))?
+            .try_get()?;
+        let local_x = x.rem_euclid(CHUNK_SIZE_X);
+        let local_y = y.rem_euclid(CHUNK_SIZE_X);
+        let local_z = z.rem_euclid(CHUNK_SIZE_X);
+        let fluid_id = chunk.fluid_ids[(local_x
+            + local_y * CHUNK_SIZE_X
+            + local_z * CHUNK_SIZE_X * CHUNK_SIZE_X)
+            as usize];
+        (fluid_id != 0).then_some(fluid_id)
+    }
+
+    /// Block id and metadata at the given block, None if the chunk's collision
+    /// data isn't ready yet
+    pub fn block_at(&self, x: i32, y: i32, z: i32) -> Option<(BlockId, u8)> {
+        let chunk_x = x.div_euclid(CHUNK_SIZE_X);
+        let chunk_y = y.div_euclid(CHUNK_SIZE_X);
+        let chunk_z = z.div_euclid(CHUNK_SIZE_X);
+
+        let mut chunks_handle = self.collision_chunks.lock().unwrap();
+
+        let chunk = chunks_handle
+            .get_mut(&(chunk_x, chunk_y, chunk_z))?
+            .try_get()?;
+        let local_x = x.rem_euclid(CHUNK_SIZE_X);
+        let local_y = y.rem_euclid(CHUNK_SIZE_X);
+        let local_z = z.rem_euclid(CHUNK_SIZE_X);
+        let index = (local_x
+            + local_y * CHUNK_SIZE_X
+            + local_z * CHUNK_SIZE_X * CHUNK_SIZE_X) as usize;
+        Some((chunk.block_ids[index], chunk.metadata[index]))
+    }
+
+    /// Whether any block overlapping the box holds a fluid
+    pub async fn is_in_fluid(
+        &self,
+        position: [f32; 3],
+        collision_box: [[f32; 3]; 2],
+    ) -> bool {
+        let mut min = [0; 3];
+        let mut max = [0; 3];
+        for axis in 0..3 {
+            min[axis] = (position[axis] + collision_box[0][axis]).floor() as i32;
+            // Touching a fluid's face from outside doesn't count
+            max[axis] = ((position[axis] + collision_box[1][axis]).ceil() as i32 - 1)
+                .max(min[axis]);
+        }
+        for x in min[0]..=max[0] {
+            for y in min[1]..=max[1] {
+                for z in min[2]..=max[2] {
+                    if self.fluid_at(x, y, z).is_some() {
+                        return true;
+                    }
+                }
+            }
+        }
+        false
+    }
+
     pub async fn is_colliding(
         &self,
         position: [f32; 3],
@@ -197,37 +533,35 @@ impl PhysicsEnvironment {
             (position[2] + max[2]),
         ];
 
-        // Use a small epsilon to handle floating point edge cases
-        let epsilon = 1e-6;
-
-        // Expand the range slightly to catch edge cases
-        let min_bound = [
-            (min[0] - epsilon).floor() as i32,
-            (min[1] - epsilon).floor() as i32,
-            (min[2] - epsilon).floor() as i32,
-        ];
-        let max_bound = [
-            (max[0] + epsilon).floor() as i32,
-            (max[1] + epsilon).floor() as i32,
-            (max[2] + epsilon).floor() as i32,
-        ];
+        let mut min_bound = [0; 3];
+        let mut max_bound = [0; 3];
+        for axis in 0..3 {
+            (min_bound[axis], max_bound[axis]) = voxel_span(min[axis], max[axis]);
+        }
 
         for x in min_bound[0]..=max_bound[0] {
             for y in min_bound[1]..=max_bound[1] {
                 for z in min_bound[2]..=max_bound[2] {
-                    if self.solid_at(x, y, z).await {
-                        // Double-check that we actually overlap with this block
-                        let block_min = [x as f32, y as f32, z as f32];
-                        let block_max =
-                            [x as f32 + 1.0, y as f32 + 1.0, z as f32 + 1.0];
-
-                        if min[0] < block_max[0]
-                            && max[0] > block_min[0]
-                            && min[1] < block_max[1]
-                            && max[1] > block_min[1]
-                            && min[2] < block_max[2]
-                            && max[2] > block_min[2]
-                        {
+                    for [box_min, box_max] in self.collision_boxes_at(x, y, z) {
+                        // Double-check that we actually overlap with this box
+                        let block_min = [
+                            x as f32 + box_min[0],
+                            y as f32 + box_min[1],
+                            z as f32 + box_min[2],
+                        ];
+                        let block_max = [
+                            x as f32 + box_max[0],
+                            y as f32 + box_max[1],
+                            z as f32 + box_max[2],
+                        ];
+
+                        // Resting against a face isn't a collision, even if
+                        // rounding put the box a hair inside it
+                        let overlaps = (0..3).all(|axis| {
+                            min[axis] < block_max[axis] - OVERLAP_TOLERANCE
+                                && max[axis] > block_min[axis] + OVERLAP_TOLERANCE
+                        });
+                        if overlaps {
                             return true;
                         }
                     }
@@ -243,9 +577,36 @@ impl PhysicsEnvironment {
         tile_registry: Arc<TileRegistry>,
         object: &PhysicsObject,
     ) {
-        let chunk_x = (object.position[0].div_euclid(CHUNK_SIZE_X as f32)) as i32;
-        let chunk_y = (object.position[1].div_euclid(CHUNK_SIZE_X as f32)) as i32;
-        let chunk_z = (object.position[2].div_euclid(CHUNK_SIZE_X as f32)) as i32;
+        self.ensure_around(&world, &tile_registry, object.position);
+    }
+
+    /// Queues collision data for the 3x3x3 chunks around every registered object
+    pub async fn ensure_for_all(
+        &self,
+        world: Arc<World>,
+        tile_registry: Arc<TileRegistry>,
+    ) {
+        let positions: Vec<_> = self
+            .objects
+            .lock()
+            .unwrap()
+            .values()
+            .map(|object| object.position)
+            .collect();
+        for position in positions {
+            self.ensure_around(&world, &tile_registry, position);
+        }
+    }
+
+    fn ensure_around(
+        &self,
+        world: &Arc<World>,
+        tile_registry: &Arc<TileRegistry>,
+        position: [f32; 3],
+    ) {
+        let chunk_x = (position[0].div_euclid(CHUNK_SIZE_X as f32)) as i32;
+        let chunk_y = (position[1].div_euclid(CHUNK_SIZE_X as f32)) as i32;
+        let chunk_z = (position[2].div_euclid(CHUNK_SIZE_X as f32)) as i32;
 
         let mut chunks_handle = self.collision_chunks.lock().unwrap();
 
@@ -253,7 +614,11 @@ impl PhysicsEnvironment {
             for dy in -1..=1 {
                 for dz in -1..=1 {
                     let chunk_coords = (chunk_x + dx, chunk_y + dy, chunk_z + dz);
-                    if !chunks_handle.contains_key(&chunk_coords) {
+                    // Chunks whose generation failed are retried
+                    let missing = chunks_handle
+                        .get(&chunk_coords)
+                        .is_none_or(|chunk| chunk.is_failed());
+                    if missing {
                         chunks_handle.insert(
                             chunk_coords,
                             QueuedItem::enqueue(VoxelCollisionChunk::from_world(
@@ -268,11 +633,73 @@ impl PhysicsEnvironment {
         }
     }
 
+    pub fn register_object(&self, object: PhysicsObject) -> ObjectId {
+        let mut next_object_id = self.next_object_id.lock().unwrap();
+        let id = *next_object_id;
+        *next_object_id += 1;
+        self.objects.lock().unwrap().insert(id, object);
+        id
+    }
+
+    pub fn remove_object(&self, id: ObjectId) -> Option<PhysicsObject> {
+        self.objects.lock().unwrap().remove(&id)
+    }
+
+    /// Snapshot of a registered object's current state
+    pub fn get_object(&self, id: ObjectId) -> Option<PhysicsObject> {
+        self.objects.lock().unwrap().get(&id).cloned()
+    }
+
+    // Whether the collision data of the chunk containing `position` is ready
+    fn has_collision_at(&self, position: [f32; 3]) -> bool {
+        let chunk_x = (position[0].div_euclid(CHUNK_SIZE_X as f32)) as i32;
+        let chunk_y = (position[1].div_euclid(CHUNK_SIZE_X as f32)) as i32;
+        let chunk_z = (position[2].div_euclid(CHUNK_SIZE_X as f32)) as i32;
+        let mut chunks_handle = self.collision_chunks.lock().unwrap();
+        match chunks_handle.get_mut(&(chunk_x, chunk_y, chunk_z)) {
+            Some(chunk) => chunk.try_get().is_some(),
+            None => false,
+        }
+    }
+
+    /// Updates every registered object. Objects whose chunk has no collision
+    /// data yet are left where they are instead of colliding with it.
+    pub async fn step_all(&self, delta_time: f32) {
+        // Taken out so objects can be registered while others update
+        let mut objects = std::mem::take(&mut *self.objects.lock().unwrap());
+        for object in objects.values_mut() {
+            if self.has_collision_at(object.position) {
+                object.update(self, delta_time).await;
+            }
+        }
+        self.objects.lock().unwrap().extend(objects);
+    }
+
+    /// Casts a ray that stops on the first solid block
     pub async fn raycast(
         &self,
         origin: [f32; 3],
         direction: [f32; 3],
         max_distance: f32,
+    ) -> Option<RaycastHit> {
+        let tile_registry = Arc::clone(&self.tile_registry);
+        self.raycast_filtered(origin, direction, max_distance, |block_id| {
+            tile_registry
+                .get_handler(block_id)
+                .is_some_and(|tile| tile.is_solid())
+        })
+        .await
+    }
+
+    /// Casts a ray that stops on the first non-air block `stops_ray` returns true
+    /// for. Chunks whose collision data isn't ready stop the ray too, reported
+    /// with block id 0.
+    pub async fn raycast_filtered<F: Fn(BlockId) -> bool>(
+        &self,
+        origin: [f32; 3],
+        direction: [f32; 3],
+        max_distance: f32,
+        stops_ray: F,
     ) -> Option<RaycastHit> {
         // Normalize direction vector
         let dir_length = (direction[0] * direction[0]
@@ -326,8 +753,13 @@ impl PhysicsEnvironment {
 
         // DDA traversal
         while distance < max_distance {
-            // Check if current voxel is solid
-            if self.solid_at(voxel[0], voxel[1], voxel[2]).await {
+            // Check if current voxel stops the ray
+            let block = self.block_at(voxel[0], voxel[1], voxel[2]);
+            let stops = match block {
+                Some((block_id, _)) => block_id != 0 && stops_ray(block_id),
+                None => true,
+            };
+            if stops {
                 // Calculate exact hit point
                 let hit_point = [
                     origin[0] + dir[0] * distance,
@@ -361,6 +793,8 @@ impl PhysicsEnvironment {
                     if uv[1] < 0.0 { uv[1] + 1.0 } else { uv[1] },
                 ];
 
+                let (block_id, metadata) = block.unwrap_or((0, 0));
+
                 return Some(RaycastHit {
                     hit_point,
                     voxel,
@@ -368,6 +802,8 @@ impl PhysicsEnvironment {
                     uv,
                     distance,
                     face: hit_face,
+                    block_id,
+                    metadata,
                 });
             }
 
@@ -406,122 +842,317 @@ impl PhysicsObject {
             position,
             velocity,
             collision_box,
+            step_height: DEFAULT_STEP_HEIGHT,
+            mode: MovementMode::Walking,
+            sneaking: false,
         }
     }
 
-    fn resolve_axis_collision(
-        current_pos: f32,
-        velocity: f32,
-        collision_box: [[f32; 3]; 2],
-        axis: usize,
-    ) -> f32 {
-        if velocity < 0.0 {
-            let box_edge = current_pos + collision_box[0][axis];
-            let wall_coord = box_edge.floor() as i32;
-            let ideal_pos = wall_coord as f32 - collision_box[0][axis];
-            let penetration = current_pos - ideal_pos;
-
-            if penetration > 1e-2 {
-                // Deep penetration - push out to safe distance
-                let corrected_pos = ideal_pos + 1e-2;
-                let movement = corrected_pos - current_pos;
-                //println!(
-                //    "Deep penetration on axis {}: pushing out by {}",
-                //    axis, movement
-                //);
-                movement
-            } else {
-                // Shallow penetration - just stop, don't push
-                //println!("Shallow contact on axis {}: stopping only", axis);
-                0.0
-            }
-        } else if velocity > 0.0 {
-            let box_edge = current_pos + collision_box[1][axis];
-            let wall_coord = box_edge.ceil() as i32;
-            let ideal_pos = wall_coord as f32 - collision_box[1][axis];
-            let penetration = ideal_pos - current_pos;
-
-            if penetration > 1e-2 {
-                // Deep penetration - push out to safe distance
-                let corrected_pos = ideal_pos - 1e-2;
-                let movement = corrected_pos - current_pos;
-                //println!(
-                //    "Deep penetration on axis {}: pushing out by {}",
-                //    axis, movement
-                //);
-                movement
-            } else {
-                // Shallow penetration - just stop, don't push
-                //println!("Shallow contact on axis {}: stopping only", axis);
-                0.0
-            }
-        } else {
-            0.0
-        }
+    pub async fn is_in_fluid(&self, environment: &PhysicsEnvironment) -> bool {
+        environment
+            .is_in_fluid(self.position, self.collision_box)
+            .await
     }
 
-    pub async fn update(&mut self, environment: &PhysicsEnvironment, delta_time: f32) {
-        if environment
-            .is_colliding(self.position, self.collision_box)
-            .await
+    /// Whether something solid is right below the collision box, probed with a
+    /// thin box under its bottom face. Water and other non-solid tiles don't count.
+    pub async fn is_on_ground(&self, environment: &PhysicsEnvironment) -> bool {
+        self.is_supported_at(environment, self.position).await
+    }
+
+    // is_on_ground for the collision box moved to `position`
+    async fn is_supported_at(
+        &self,
+        environment: &PhysicsEnvironment,
+        position: [f32; 3],
+    ) -> bool {
+        let bottom = self.collision_box[0][1];
+        let probe_box = [
+            [
+                self.collision_box[0][0],
+                bottom - GROUND_PROBE_DEPTH,
+                self.collision_box[0][2],
+            ],
+            [self.collision_box[1][0], bottom, self.collision_box[1][2]],
+        ];
+        environment.is_colliding(position, probe_box).await
+    }
+
+    // Tries to get past a ledge by lifting the box up to step_height, doing the
+    // horizontal movement up there and dropping back down onto the ledge. Leaves
+    // the object untouched and returns false if any part of that is blocked.
+    async fn try_step_up(
+        &mut self,
+        environment: &PhysicsEnvironment,
+        horizontal_movement: [f32; 3],
+    ) -> bool {
+        let mut position = self.position;
+
+        let lift = [0.0, self.step_height, 0.0];
+        let raised =
+            match PhysicsObject::sweep(environment, position, self.collision_box, lift)
+                .await
+            {
+                Some((time, _)) => self.step_height * time - COLLISION_SKIN,
+                None => self.step_height,
+            };
+        if raised <= 0.0 {
+            return false;
+        }
+        position[1] += raised;
+
+        if PhysicsObject::sweep(
+            environment,
+            position,
+            self.collision_box,
+            horizontal_movement,
+        )
+        .await
+        .is_some()
         {
-            //println!(
-            //    "WARNING: Already colliding at start of update! Position: {:?}",
-            //    self.position
-            //);
-            // Try to push out of collision
-            for axis in [1, 0, 2] {
-                let original_pos = self.position[axis];
-                // Try small adjustments in both directions
-                for direction in [-1.0, 1.0] {
-                    for distance in [0.01, 0.1, 0.5] {
-                        self.position[axis] = original_pos + direction * distance;
-                        if !environment
-                            .is_colliding(self.position, self.collision_box)
-                            .await
+            return false;
+        }
+        position[0] += horizontal_movement[0];
+        position[2] += horizontal_movement[2];
+
+        // There has to be floor to land on within the height we climbed
+        let drop = [0.0, -raised, 0.0];
+        let Some((time, _)) =
+            PhysicsObject::sweep(environment, position, self.collision_box, drop).await
+        else {
+            return false;
+        };
+        position[1] -= raised * time - COLLISION_SKIN;
+
+        self.position = position;
+        self.velocity[1] = 0.0;
+        true
+    }
+
+    // Swept test of the box moving by `movement` against the collision boxes of
+    // every voxel it could touch. Returns the earliest time of impact in 0..=1
+    // and the axis that was hit, or None if the whole movement is free.
+    async fn sweep(
+        environment: &PhysicsEnvironment,
+        position: [f32; 3],
+        collision_box: [[f32; 3]; 2],
+        movement: [f32; 3],
+    ) -> Option<(f32, usize)> {
+        let box_min = [
+            position[0] + collision_box[0][0],
+            position[1] + collision_box[0][1],
+            position[2] + collision_box[0][2],
+        ];
+        let box_max = [
+            position[0] + collision_box[1][0],
+            position[1] + collision_box[1][1],
+            position[2] + collision_box[1][2],
+        ];
+
+        // Every voxel touched by the box anywhere along the movement
+        let mut voxel_min = [0; 3];
+        let mut voxel_max = [0; 3];
+        for axis in 0..3 {
+            (voxel_min[axis], voxel_max[axis]) = voxel_span(
+                box_min[axis] + movement[axis].min(0.0),
+                box_max[axis] + movement[axis].max(0.0),
+            );
+        }
+
+        let mut earliest: Option<(f32, usize)> = None;
+        for x in voxel_min[0]..=voxel_max[0] {
+            for y in voxel_min[1]..=voxel_max[1] {
+                for z in voxel_min[2]..=voxel_max[2] {
+                    let voxel = [x as f32, y as f32, z as f32];
+                    for [solid_min, solid_max] in
+                        environment.collision_boxes_at(x, y, z)
+                    {
+                        let solid_min: [f32; 3] =
+                            std::array::from_fn(|axis| voxel[axis] + solid_min[axis]);
+                        let solid_max: [f32; 3] =
+                            std::array::from_fn(|axis| voxel[axis] + solid_max[axis]);
+                        let mut entry = f32::NEG_INFINITY;
+                        let mut exit = f32::INFINITY;
+                        let mut entry_axis = 0;
+                        let mut separated = false;
+                        for axis in 0..3 {
+                            let (axis_entry, axis_exit) = if movement[axis] > 0.0 {
+                                (
+                                    (solid_min[axis] - box_max[axis]) / movement[axis],
+                                    (solid_max[axis] - box_min[axis]) / movement[axis],
+                                )
+                            } else if movement[axis] < 0.0 {
+                                (
+                                    (solid_max[axis] - box_min[axis]) / movement[axis],
+                                    (solid_min[axis] - box_max[axis]) / movement[axis],
+                                )
+                            } else if box_max[axis] <= solid_min[axis]
+                                || box_min[axis] >= solid_max[axis]
+                            {
+                                // Never overlaps on an axis it doesn't move along
+                                separated = true;
+                                break;
+                            } else {
+                                continue;
+                            };
+                            if axis_entry > entry {
+                                entry = axis_entry;
+                                entry_axis = axis;
+                            }
+                            exit = exit.min(axis_exit);
+                        }
+                        if separated || entry >= exit || entry > 1.0 || exit <= 0.0 {
+                            continue;
+                        }
+                        // A box already overlapping the voxel is only blocked if the
+                        // overlap is floating point noise, otherwise it may move out
+                        if entry < 0.0
+                            && -entry * movement[entry_axis].abs() > OVERLAP_TOLERANCE
                         {
-                            //println!(
-                            //    "Pushed out of collision on axis {} by {}",
-                            //    axis,
-                            //    direction * distance
-                            //);
-                            return; // Exit early, don't do normal movement
+                            continue;
+                        }
+                        let entry = entry.max(0.0);
+                        if earliest.is_none_or(|(time, _)| entry < time) {
+                            earliest = Some((entry, entry_axis));
                         }
                     }
                 }
-                self.position[axis] = original_pos; // Restore if no solution found
             }
         }
-        let intended_movement = [
+        earliest
+    }
+
+    // Nudges an object that ended up inside a solid voxel back out by a small
+    // distance along one axis. Returns true if it was moved out, leaves it where
+    // it was if nothing nearby is free.
+    async fn push_out(&mut self, environment: &PhysicsEnvironment) -> bool {
+        for axis in [1, 0, 2] {
+            let original_pos = self.position[axis];
+            // Try small adjustments in both directions
+            for direction in [-1.0, 1.0] {
+                for distance in PUSH_OUT_DISTANCES {
+                    self.position[axis] = original_pos + direction * distance;
+                    if !environment
+                        .is_colliding(self.position, self.collision_box)
+                        .await
+                    {
+                        return true;
+                    }
+                }
+            }
+            self.position[axis] = original_pos; // Restore if no solution found
+        }
+        false
+    }
+
+    /// Moves the object out of any solid voxel it overlaps, like one just
+    /// placed inside it. Returns true if it had to be moved, false if it was
+    /// free already or nothing nearby is.
+    pub async fn unstick(&mut self, environment: &PhysicsEnvironment) -> bool {
+        if self.mode == MovementMode::Noclip
+            || !environment
+                .is_colliding(self.position, self.collision_box)
+                .await
+        {
+            return false;
+        }
+        self.push_out(environment).await
+    }
+
+    pub async fn update(&mut self, environment: &PhysicsEnvironment, delta_time: f32) {
+        if self.mode == MovementMode::Noclip {
+            for axis in 0..3 {
+                self.position[axis] += self.velocity[axis] * delta_time;
+            }
+            return;
+        }
+
+        if self.mode == MovementMode::Flying {
+            // Vertical movement is driven entirely by the caller
+        } else if self.is_in_fluid(environment).await {
+            let drag = FLUID_DRAG.powf(delta_time);
+            self.velocity[0] *= drag;
+            self.velocity[2] *= drag;
+            self.velocity[1] -=
+                environment.gravity() * FLUID_GRAVITY_SCALE * delta_time;
+            self.velocity[1] = self.velocity[1].max(-FLUID_SINK_SPEED);
+        } else {
+            self.velocity[1] -= environment.gravity() * delta_time;
+            self.velocity[1] = self.velocity[1].max(-environment.terminal_velocity());
+        }
+
+        // Also what gets the object out of a block after leaving noclip
+        if self.unstick(environment).await {
+            return; // Exit early, don't do normal movement
+        }
+        let on_ground =
+            self.mode == MovementMode::Walking && self.is_on_ground(environment).await;
+        let grounded = on_ground && self.step_height > 0.0;
+        let mut remaining_movement = [
             self.velocity[0] * delta_time,
             self.velocity[1] * delta_time,
             self.velocity[2] * delta_time,
         ];
 
-        let mut final_movement = intended_movement;
-
-        // Check each axis independently
-        for axis in [1, 0, 2] {
-            let mut test_position = self.position;
-            test_position[axis] += final_movement[axis];
-
-            if environment
-                .is_colliding(test_position, self.collision_box)
-                .await
-            {
-                final_movement[axis] = PhysicsObject::resolve_axis_collision(
-                    self.position[axis],
-                    self.velocity[axis],
-                    self.collision_box,
-                    axis,
-                );
-                self.velocity[axis] = 0.0;
+        if self.sneaking && on_ground {
+            // Drop the horizontal movement along any axis that would leave the
+            // object without ground underneath, so it slides along the edge
+            for axis in [0, 2] {
+                let mut position = self.position;
+                position[axis] += remaining_movement[axis];
+                if !self.is_supported_at(environment, position).await {
+                    remaining_movement[axis] = 0.0;
+                    self.velocity[axis] = 0.0;
+                }
+            }
+            // Moving along both axes at once can still cut the corner
+            let mut position = self.position;
+            position[0] += remaining_movement[0];
+            position[2] += remaining_movement[2];
+            if !self.is_supported_at(environment, position).await {
+                remaining_movement[0] = 0.0;
+                remaining_movement[2] = 0.0;
+                self.velocity[0] = 0.0;
+                self.velocity[2] = 0.0;
             }
         }
 
-        // Apply the resolved movement all at once
-        for axis in 0..3 {
-            self.position[axis] += final_movement[axis];
+        // Move up to the first impact, cancel the blocked axis and keep sliding
+        // along the others with whatever movement is left
+        for _ in 0..3 {
+            let Some((time, axis)) = PhysicsObject::sweep(
+                environment,
+                self.position,
+                self.collision_box,
+                remaining_movement,
+            )
+            .await
+            else {
+                for (position, movement) in
+                    self.position.iter_mut().zip(remaining_movement)
+                {
+                    *position += movement;
+                }
+                return;
+            };
+            if axis != 1 && grounded {
+                let horizontal_movement =
+                    [remaining_movement[0], 0.0, remaining_movement[2]];
+                if self.try_step_up(environment, horizontal_movement).await {
+                    return;
+                }
+            }
+            let direction = remaining_movement[axis].signum();
+            for (position, movement) in
+                self.position.iter_mut().zip(&mut remaining_movement)
+            {
+                *position += *movement * time;
+                *movement *= 1.0 - time;
+            }
+            // Stay a hair away from the face so rounding never puts us inside it
+            self.position[axis] -= direction * COLLISION_SKIN;
+            remaining_movement[axis] = 0.0;
+            self.velocity[axis] = 0.0;
         }
     }
 }
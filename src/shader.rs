@@ -1,10 +1,14 @@
 use gl33::*;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::ffi::CString;
-use ultraviolet::Mat4;
+use ultraviolet::{Mat3, Mat4};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug)]
 pub struct Shader {
     pub program_id: u32,
+    // Uniform locations resolved so far, including missing ones (-1)
+    uniform_locations: RefCell<HashMap<String, i32>>,
 }
 
 impl Shader {
@@ -49,7 +53,10 @@ impl Shader {
             gl.DeleteShader(vertex_shader);
             gl.DeleteShader(fragment_shader);
 
-            Ok(Shader { program_id })
+            Ok(Shader {
+                program_id,
+                uniform_locations: RefCell::new(HashMap::new()),
+            })
         }
     }
 
@@ -94,10 +101,12 @@ impl Shader {
                 } else {
                     "Fragment"
                 };
+                let log = String::from_utf8_lossy(&v);
                 return Err(format!(
-                    "{} Compile Error: {}",
+                    "{} Compile Error: {}{}",
                     shader_type_name,
-                    String::from_utf8_lossy(&v)
+                    log,
+                    Self::source_context(source, &log)
                 ));
             }
 
@@ -105,15 +114,89 @@ impl Shader {
         }
     }
 
+    /// Lines of source shown above and below each line reported in a log
+    const ERROR_CONTEXT_LINES: usize = 2;
+
+    /// Extracts the source line number from a driver log line. Handles the
+    /// `0(12) : error` form and the `0:12(5): error` / `ERROR: 0:12:` forms.
+    fn log_line_number(log_line: &str) -> Option<usize> {
+        let bytes = log_line.as_bytes();
+        let mut i = 0;
+        while i < bytes.len() {
+            if !bytes[i].is_ascii_digit() {
+                i += 1;
+                continue;
+            }
+            // Skip the source string index
+            while i < bytes.len() && bytes[i].is_ascii_digit() {
+                i += 1;
+            }
+            let separator = *bytes.get(i)?;
+            if separator == b'(' || separator == b':' {
+                let start = i + 1;
+                let mut end = start;
+                while end < bytes.len() && bytes[end].is_ascii_digit() {
+                    end += 1;
+                }
+                if end > start {
+                    return log_line[start..end].parse().ok();
+                }
+            }
+        }
+        None
+    }
+
+    /// Formats the source lines referenced by a compile log with a little
+    /// context around each. Empty if no line numbers could be parsed.
+    fn source_context(source: &str, log: &str) -> String {
+        let source_lines: Vec<&str> = source.lines().collect();
+        let mut reported: Vec<usize> = log
+            .lines()
+            .filter_map(Self::log_line_number)
+            .filter(|&line| line >= 1 && line <= source_lines.len())
+            .collect();
+        reported.sort_unstable();
+        reported.dedup();
+
+        let mut context = String::new();
+        let mut last_shown = 0;
+        for &line in &reported {
+            let first = line.saturating_sub(Self::ERROR_CONTEXT_LINES).max(1);
+            let last = (line + Self::ERROR_CONTEXT_LINES).min(source_lines.len());
+            if last_shown > 0 && first > last_shown + 1 {
+                context.push_str("\n...");
+            }
+            for number in first.max(last_shown + 1)..=last {
+                let marker = if reported.contains(&number) { '>' } else { ' ' };
+                context.push_str(&format!(
+                    "\n{} {:4} | {}",
+                    marker,
+                    number,
+                    source_lines[number - 1]
+                ));
+            }
+            last_shown = last_shown.max(last);
+        }
+        context
+    }
+
     pub fn use_program(&self, gl: &GlFns) {
         gl.UseProgram(self.program_id);
     }
 
     pub fn get_uniform_location(&self, gl: &GlFns, name: &str) -> i32 {
-        unsafe {
+        if let Some(&location) = self.uniform_locations.borrow().get(name) {
+            return location;
+        }
+
+        let location = unsafe {
             let c_name = CString::new(name).unwrap();
             gl.GetUniformLocation(self.program_id, c_name.as_ptr().cast())
-        }
+        };
+        self.uniform_locations
+            .borrow_mut()
+            .insert(name.to_string(), location);
+        location
     }
 
     pub fn set_mat4(&self, gl: &GlFns, name: &str, matrix: &Mat4) {
@@ -123,6 +206,20 @@ impl Shader {
         }
     }
 
+    pub fn set_mat3(&self, gl: &GlFns, name: &str, matrix: &Mat3) {
+        unsafe {
+            let location = self.get_uniform_location(gl, name);
+            gl.UniformMatrix3fv(location, 1, GL_FALSE.0 as u8, matrix.as_ptr());
+        }
+    }
+
+    pub fn set_vec2(&self, gl: &GlFns, name: &str, value: &[f32; 2]) {
+        unsafe {
+            let location = self.get_uniform_location(gl, name);
+            gl.Uniform2fv(location, 1, value.as_ptr());
+        }
+    }
+
     pub fn set_vec3(&self, gl: &GlFns, name: &str, value: &[f32; 3]) {
         unsafe {
             let location = self.get_uniform_location(gl, name);
@@ -130,6 +227,13 @@ impl Shader {
         }
     }
 
+    pub fn set_vec4(&self, gl: &GlFns, name: &str, value: &[f32; 4]) {
+        unsafe {
+            let location = self.get_uniform_location(gl, name);
+            gl.Uniform4fv(location, 1, value.as_ptr());
+        }
+    }
+
     pub fn set_float(&self, gl: &GlFns, name: &str, value: f32) {
         unsafe {
             let location = self.get_uniform_location(gl, name);
@@ -144,6 +248,13 @@ impl Shader {
         }
     }
 
+    pub fn set_int_array(&self, gl: &GlFns, name: &str, values: &[i32]) {
+        unsafe {
+            let location = self.get_uniform_location(gl, name);
+            gl.Uniform1iv(location, values.len() as i32, values.as_ptr());
+        }
+    }
+
     pub fn unset_mat4(&self, gl: &GlFns, name: &str) {
         unsafe {
             let location = self.get_uniform_location(gl, name);
@@ -151,3 +262,67 @@ impl Shader {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_numbers_from_driver_logs() {
+        let cases = [
+            // NVIDIA
+            (
+                "0(12) : error C0000: syntax error, unexpected '}'",
+                Some(12),
+            ),
+            // Mesa
+            ("0:7(5): error: `color' undeclared", Some(7)),
+            // AMD and Intel on Windows
+            (
+                "ERROR: 0:3: 'vec5' : no matching overloaded function",
+                Some(3),
+            ),
+            ("WARNING: 0:41: extension not supported", Some(41)),
+            ("error: linking failed", None),
+            ("", None),
+        ];
+        for (log_line, expected) in cases {
+            assert_eq!(Shader::log_line_number(log_line), expected, "{}", log_line);
+        }
+    }
+
+    #[test]
+    fn source_context_marks_reported_lines() {
+        let source = "one\ntwo\nthree\nfour\nfive\nsix\nseven\neight\nnine";
+        let log = "0(1) : error C0000: first\n0:8(2): error: second";
+        assert_eq!(
+            Shader::source_context(source, log),
+            "\n>    1 | one\
+             \n     2 | two\
+             \n     3 | three\
+             \n...\
+             \n     6 | six\
+             \n     7 | seven\
+             \n>    8 | eight\
+             \n     9 | nine"
+        );
+    }
+
+    #[test]
+    fn source_context_merges_nearby_lines() {
+        let source = "one\ntwo\nthree\nfour";
+        let log = "0:2(1): error: a\n0:3(1): error: b\n0:2(4): error: c";
+        assert_eq!(
+            Shader::source_context(source, log),
+            "\n     1 | one\n>    2 | two\n>    3 | three\n     4 | four"
+        );
+    }
+
+    #[test]
+    fn source_context_skips_out_of_range_lines() {
+        let source = "one\ntwo";
+        assert_eq!(Shader::source_context(source, "0(0) : error C0000: a"), "");
+        assert_eq!(Shader::source_context(source, "0:3(1): error: b"), "");
+        assert_eq!(Shader::source_context(source, "linking failed"), "");
+    }
+}
@@ -0,0 +1,34 @@
+use std::f32::consts::TAU;
+
+// Fraction of a day already past at time 0, starts the game mid-morning
+const START_OF_DAY: f32 = 0.125;
+// How far the sun's path leans towards -z, so it never passes straight overhead
+const SUN_PATH_TILT: f32 = 0.4;
+
+// Clear colors at noon and midnight
+const DAY_SKY_COLOR: [f32; 3] = [148.0 / 255.0, 243.0 / 255.0, 255.0 / 255.0];
+const NIGHT_SKY_COLOR: [f32; 3] = [10.0 / 255.0, 12.0 / 255.0, 30.0 / 255.0];
+
+/// Direction sunlight travels in `time` seconds into the game, for a day lasting
+/// `day_length` seconds. The sun rises in +x and sets in -x.
+pub fn sun_direction(time: f32, day_length: f32) -> [f32; 3] {
+    let angle = (time / day_length + START_OF_DAY) * TAU;
+    let towards_sun = [angle.cos(), angle.sin(), -SUN_PATH_TILT];
+    let length = towards_sun.iter().map(|c| c * c).sum::<f32>().sqrt();
+    towards_sun.map(|c| -c / length)
+}
+
+/// How much daylight there is with the sun in the given direction, from 0 at
+/// night to 1 during the day, fading while the sun crosses the horizon
+pub fn daylight(sun_direction: [f32; 3]) -> f32 {
+    let sun_height = -sun_direction[1];
+    let t = ((sun_height + 0.1) / 0.3).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Color the sky is cleared to for the given daylight
+pub fn sky_color(daylight: f32) -> [f32; 3] {
+    std::array::from_fn(|i| {
+        NIGHT_SKY_COLOR[i] + (DAY_SKY_COLOR[i] - NIGHT_SKY_COLOR[i]) * daylight
+    })
+}
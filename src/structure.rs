@@ -0,0 +1,250 @@
+use std::{collections::HashMap, fs, path::Path, sync::Arc};
+
+use crate::{
+    physics::PhysicsEnvironment,
+    tile::TileRegistry,
+    world::{BlockId, World},
+};
+
+/// A predefined multi-block template, like a hut or a well, placed into the
+/// world in one go.
+///
+/// Templates are plain text. Every line is one of:
+/// - `// comment`
+/// - `block <char> <tile name>`, giving a character the tile it places
+/// - `origin <x> <y> <z>`, the template block that lands on the placement
+///   position, defaults to `0 0 0`
+/// - `layer`, starting the next layer up, the first one is the bottom
+/// - a row of the current layer, characters go along x and rows along z
+///
+/// `.` is always air and `_` keeps whatever block was there before.
+pub struct Structure {
+    pub size: (i32, i32, i32),
+    pub origin: (i32, i32, i32),
+    // Blocks in x, y, z order like WorldView, None leaves the world as is
+    blocks: Vec<Option<BlockId>>,
+}
+
+impl Structure {
+    /// Reads a template file, see Structure for the format
+    pub fn load(path: &Path, tile_registry: &TileRegistry) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| {
+            format!("Failed to read structure {}: {}", path.display(), e)
+        })?;
+        Self::parse(&text, tile_registry)
+    }
+
+    /// Parses a template, see Structure for the format
+    pub fn parse(text: &str, tile_registry: &TileRegistry) -> Result<Self, String> {
+        let mut palette: HashMap<char, Option<BlockId>> =
+            HashMap::from([('.', Some(0)), ('_', None)]);
+        let mut origin = (0, 0, 0);
+        let mut layers: Vec<Vec<Vec<Option<BlockId>>>> = Vec::new();
+
+        for (line_number, line) in text.lines().enumerate() {
+            let line_number = line_number + 1;
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with("//") {
+                continue;
+            }
+            let words: Vec<&str> = trimmed.split_whitespace().collect();
+            match words[..] {
+                ["layer"] => layers.push(Vec::new()),
+                ["origin", x, y, z] => {
+                    let parse = |value: &str| {
+                        value.parse::<i32>().map_err(|_| {
+                            format!("Line {}: invalid origin '{}'", line_number, value)
+                        })
+                    };
+                    origin = (parse(x)?, parse(y)?, parse(z)?);
+                }
+                ["block", key, name] => {
+                    let mut chars = key.chars();
+                    let (Some(key), None) = (chars.next(), chars.next()) else {
+                        return Err(format!(
+                            "Line {}: block keys are a single character, got '{}'",
+                            line_number, key
+                        ));
+                    };
+                    let id = tile_registry.get_id_by_name(name).ok_or_else(|| {
+                        format!("Line {}: unknown tile '{}'", line_number, name)
+                    })?;
+                    palette.insert(key, Some(id));
+                }
+                _ => {
+                    let Some(layer) = layers.last_mut() else {
+                        return Err(format!(
+                            "Line {}: row before the first layer",
+                            line_number
+                        ));
+                    };
+                    let row = trimmed
+                        .chars()
+                        .map(|key| {
+                            palette.get(&key).copied().ok_or_else(|| {
+                                format!(
+                                    "Line {}: unknown block key '{}'",
+                                    line_number, key
+                                )
+                            })
+                        })
+                        .collect::<Result<Vec<_>, String>>()?;
+                    layer.push(row);
+                }
+            }
+        }
+
+        let size_y = layers.len();
+        let size_z = layers.first().map_or(0, |layer| layer.len());
+        let size_x = layers
+            .first()
+            .and_then(|layer| layer.first())
+            .map_or(0, |row| row.len());
+        if size_x == 0 || size_z == 0 {
+            return Err("Structure has no blocks".to_string());
+        }
+        let inside = |value: i32, size: usize| (0..size as i32).contains(&value);
+        if !inside(origin.0, size_x)
+            || !inside(origin.1, size_y)
+            || !inside(origin.2, size_z)
+        {
+            return Err(format!(
+                "Origin {:?} is outside the {}x{}x{} structure",
+                origin, size_x, size_y, size_z
+            ));
+        }
+        let mut blocks = vec![None; size_x * size_y * size_z];
+        for (y, layer) in layers.iter().enumerate() {
+            if layer.len() != size_z {
+                return Err(format!(
+                    "Layer {} has {} rows, expected {}",
+                    y,
+                    layer.len(),
+                    size_z
+                ));
+            }
+            for (z, row) in layer.iter().enumerate() {
+                if row.len() != size_x {
+                    return Err(format!(
+                        "Row {} of layer {} is {} blocks long, expected {}",
+                        z,
+                        y,
+                        row.len(),
+                        size_x
+                    ));
+                }
+                for (x, &block) in row.iter().enumerate() {
+                    blocks[x + y * size_x + z * size_x * size_y] = block;
+                }
+            }
+        }
+
+        Ok(Structure {
+            size: (size_x as i32, size_y as i32, size_z as i32),
+            origin,
+            blocks,
+        })
+    }
+
+    /// Writes the structure with its origin at `at`. Each affected chunk is
+    /// locked once and gets a single update message. The blocks are also set in
+    /// `physics_env` right away, so objects can be pushed out of them before
+    /// the update reaches it.
+    pub fn place(
+        &self,
+        world: &Arc<World>,
+        physics_env: &PhysicsEnvironment,
+        at: (i32, i32, i32),
+    ) {
+        let (size_x, size_y, _) = self.size;
+        let blocks = self
+            .blocks
+            .iter()
+            .enumerate()
+            .filter_map(|(index, block)| {
+                let index = index as i32;
+                let position = (
+                    at.0 - self.origin.0 + index % size_x,
+                    at.1 - self.origin.1 + (index / size_x) % size_y,
+                    at.2 - self.origin.2 + index / (size_x * size_y),
+                );
+                block.map(|block_id| (position, block_id))
+            })
+            .collect::<Vec<_>>();
+        World::set_blocks(world, &blocks);
+        for &((x, y, z), block_id) in &blocks {
+            physics_env.set_block(x, y, z, block_id, 0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tile::blocks;
+
+    fn parse(text: &str) -> Result<Structure, String> {
+        Structure::parse(text, &TileRegistry::new())
+    }
+
+    #[test]
+    fn the_well_parses() {
+        let well = parse(include_str!("assets/structures/well.txt")).unwrap();
+        assert_eq!(well.size, (5, 5, 5));
+        assert_eq!(well.origin, (2, 1, 2));
+    }
+
+    #[test]
+    fn rows_go_along_x_and_layers_up() {
+        let structure = parse("block # stone\nlayer\n#.\n__\nlayer\n..\n.#").unwrap();
+        assert_eq!(structure.size, (2, 2, 2));
+        assert_eq!(
+            structure.blocks,
+            [
+                Some(blocks::STONE),
+                Some(0),
+                Some(0),
+                Some(0),
+                None,
+                None,
+                Some(0),
+                Some(blocks::STONE),
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_headers_are_rejected() {
+        for text in [
+            "origin 1 x 0\nlayer\n.",
+            "block ## stone\nlayer\n.",
+            "block # stone extra\nlayer\n.",
+            ".\nlayer\n.",
+        ] {
+            assert!(parse(text).is_err(), "{:?} parsed", text);
+        }
+    }
+
+    #[test]
+    fn unknown_tiles_and_keys_are_rejected() {
+        assert!(parse("block # marble\nlayer\n#").is_err());
+        assert!(parse("layer\n#").is_err());
+    }
+
+    #[test]
+    fn ragged_layers_are_rejected() {
+        assert!(parse("layer\n..\n.").is_err());
+        assert!(parse("layer\n..\n..\nlayer\n..").is_err());
+        assert!(parse("layer").is_err());
+    }
+
+    #[test]
+    fn origins_outside_the_structure_are_rejected() {
+        assert!(parse("origin 0 0 0\nlayer\n..\n..").is_ok());
+        assert!(parse("origin 1 0 1\nlayer\n..\n..").is_ok());
+        for origin in ["2 0 0", "0 1 0", "0 0 2", "-1 0 0"] {
+            let text = format!("origin {}\nlayer\n..\n..", origin);
+            assert!(parse(&text).is_err(), "origin {} parsed", origin);
+        }
+    }
+}
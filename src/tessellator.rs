@@ -1,19 +1,51 @@
 use std::{
-    collections::HashMap,
-    sync::{Arc, Mutex},
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
 };
 
-use gl33::GlFns;
-use rand::Rng;
-use tokio::{spawn, sync::mpsc::UnboundedReceiver};
+use gl33::{GL_FALSE, GL_TRUE, GlFns};
+use tokio::{spawn, sync::mpsc::UnboundedReceiver, task::JoinSet};
 
 use crate::{
     mesh::{MeshEnvelope, MeshParams},
-    tile::{RenderLayer, TileFace, TileRegistry},
+    tile::{LayerBuffers, RenderLayer, TERRAIN_ATLAS, Tile, TileFace, TileRegistry},
     utils::QueuedItem,
-    world::{CHUNK_SIZE_X, ChunkUpdateMessage, World, WorldView},
+    world::{
+        BlockId, CHUNK_SIZE_X, ChunkUpdateMessage, MAX_LIGHT_LEVEL, World, WorldView,
+        WorldViewCache,
+    },
 };
 
+// Merge coplanar opaque faces into larger quads. Set to false to get one quad
+// per exposed voxel face, e.g. to compare vertex counts.
+const GREEDY_MESHING: bool = true;
+
+// How far skirts sit inside the chunk, so they hide behind any real face in
+// the same plane instead of fighting it for depth
+const SKIRT_INSET: f32 = 1e-2;
+
+// How long chunk updates are collected before the affected chunks are remeshed,
+// so a burst of edits remeshes every chunk once
+const REMESH_DEBOUNCE: Duration = Duration::from_millis(50);
+
+// Coarsest LOD chunks are meshed at
+const MAX_LOD: u8 = 16;
+
+/// Most blocks a chunk's mesh reads past its border, for the coarsest LOD and
+/// for block light spreading in from its neighbours
+pub const MAX_MESH_PADDING: i32 = if MAX_LOD as i32 > MAX_LIGHT_LEVEL as i32 {
+    MAX_LOD as i32
+} else {
+    MAX_LIGHT_LEVEL as i32
+};
+
+// Brightness block light adds to a vertex at the highest light level
+const BLOCK_LIGHT_STRENGTH: f32 = 0.4;
+
 const NEIGHBORHOOD_SCAN: [([(i32, i32, i32); 9], TileFace); 6] = [
     // Top face (y = 1) - for z in -1..=1, for x in -1..=1
     (
@@ -107,39 +139,459 @@ const NEIGHBORHOOD_SCAN: [([(i32, i32, i32); 9], TileFace); 6] = [
     ),
 ];
 
+impl LayerBuffers {
+    fn clear(&mut self) {
+        self.vertices.clear();
+        self.indices.clear();
+        self.colors.clear();
+        self.uvs.clear();
+        self.materials.clear();
+        self.lights.clear();
+        self.normals.clear();
+    }
+
+    fn append(&mut self, other: &LayerBuffers) {
+        let vertex_count = self.vertices.len() as u32;
+        self.vertices.extend_from_slice(&other.vertices);
+        self.indices
+            .extend(other.indices.iter().map(|index| index + vertex_count));
+        self.colors.extend_from_slice(&other.colors);
+        self.uvs.extend_from_slice(&other.uvs);
+        self.materials.extend_from_slice(&other.materials);
+        self.lights.extend_from_slice(&other.lights);
+        self.normals.extend_from_slice(&other.normals);
+    }
+
+    fn into_mesh(mut self) -> MeshEnvelope {
+        // Faces are built with uvs inside their tile, the shader expects them
+        // on the atlas
+        for (uv, &material) in self.uvs.iter_mut().zip(&self.materials) {
+            *uv = TERRAIN_ATLAS.atlas_uv(material, *uv);
+        }
+        MeshEnvelope::new(MeshParams {
+            vertices: self.vertices,
+            indices: Some(self.indices),
+            uvs: Some(self.uvs),
+            material_ids: Some(self.materials),
+            colors: Some(self.colors),
+            light: Some(self.lights),
+            normals: Some(self.normals),
+        })
+    }
+}
+
+// Axes of a face as (normal, u, v), where u and v span the face plane
+fn face_axes(face: TileFace) -> (usize, usize, usize) {
+    match face {
+        TileFace::Top | TileFace::Bottom => (1, 0, 2),
+        TileFace::North | TileFace::South => (2, 0, 1),
+        TileFace::West | TileFace::East => (0, 2, 1),
+    }
+}
+
+// A single voxel face quad waiting to be merged with its neighbors
+struct GreedyFace {
+    origin: [f32; 3],
+    offsets: [[f32; 3]; 4], // Vertex positions relative to origin
+    uvs: [[f32; 2]; 4],
+    colors: [[f32; 4]; 4],
+    materials: [[i32; 2]; 4],
+    lights: [[f32; 4]; 4],
+    normals: [[f32; 3]; 4],
+    indices: Vec<u32>, // Relative to the first vertex
+}
+
+// Faces of one direction waiting to be merged, per slice along the face normal,
+// then by cell position within the slice
+type GreedyLayer = BTreeMap<usize, HashMap<(usize, usize), GreedyFace>>;
+
+impl GreedyFace {
+    // Only full-size single quads can be merged, anything else is kept as is
+    fn from_buffers(
+        origin: [f32; 3],
+        face: TileFace,
+        lod: u8,
+        buffers: &LayerBuffers,
+    ) -> Option<Self> {
+        if buffers.vertices.len() != 4 {
+            return None;
+        }
+        let (_, u_axis, v_axis) = face_axes(face);
+        let lod = lod as f32;
+        let offsets: [[f32; 3]; 4] = std::array::from_fn(|i| {
+            let vertex = buffers.vertices[i];
+            [
+                vertex[0] - origin[0],
+                vertex[1] - origin[1],
+                vertex[2] - origin[2],
+            ]
+        });
+        let on_edge = |value: f32| value == 0.0 || value == lod;
+        let is_full_face = offsets
+            .iter()
+            .all(|offset| on_edge(offset[u_axis]) && on_edge(offset[v_axis]));
+        if !is_full_face {
+            return None;
+        }
+        Some(GreedyFace {
+            origin,
+            offsets,
+            uvs: buffers.uvs[..].try_into().ok()?,
+            colors: buffers.colors[..].try_into().ok()?,
+            materials: buffers.materials[..].try_into().ok()?,
+            lights: buffers.lights[..].try_into().ok()?,
+            normals: buffers.normals[..].try_into().ok()?,
+            indices: buffers.indices.clone(),
+        })
+    }
+
+    fn can_merge(&self, other: &GreedyFace) -> bool {
+        self.offsets == other.offsets
+            && self.uvs == other.uvs
+            && self.colors == other.colors
+            && self.materials == other.materials
+            && self.lights == other.lights
+            && self.normals == other.normals
+            && self.indices == other.indices
+    }
+
+    // Emit this face stretched over width x height cells of the face plane
+    fn emit(
+        &self,
+        face: TileFace,
+        width: usize,
+        height: usize,
+        lod: u8,
+        buffers: &mut LayerBuffers,
+    ) {
+        let (_, u_axis, v_axis) = face_axes(face);
+        let lod = lod as f32;
+        let extent = |axis: usize| {
+            if axis == u_axis {
+                width as f32
+            } else if axis == v_axis {
+                height as f32
+            } else {
+                1.0
+            }
+        };
+
+        let vertex_count = buffers.vertices.len() as u32;
+        for offset in self.offsets {
+            let mut vertex = [
+                self.origin[0] + offset[0],
+                self.origin[1] + offset[1],
+                self.origin[2] + offset[2],
+            ];
+            // Vertices on the far edge of the quad move to the far edge of the merge
+            for axis in [u_axis, v_axis] {
+                if offset[axis] > 0.0 {
+                    vertex[axis] += (extent(axis) - 1.0) * lod;
+                }
+            }
+            buffers.vertices.push(vertex);
+        }
+
+        // The uvs have to tile across the merged quad, so stretch each uv
+        // component by the extent along the edge it runs along
+        let edge_axis = |from: [f32; 3], to: [f32; 3]| {
+            (0..3)
+                .find(|&axis| from[axis] != to[axis])
+                .unwrap_or(u_axis)
+        };
+        let scale_u = extent(edge_axis(self.offsets[0], self.offsets[1]));
+        let scale_v = extent(edge_axis(self.offsets[3], self.offsets[0]));
+        for uv in self.uvs {
+            buffers.uvs.push([uv[0] * scale_u, uv[1] * scale_v]);
+        }
+
+        buffers
+            .indices
+            .extend(self.indices.iter().map(|index| index + vertex_count));
+        buffers.colors.extend_from_slice(&self.colors);
+        buffers.materials.extend_from_slice(&self.materials);
+        buffers.lights.extend_from_slice(&self.lights);
+        buffers.normals.extend_from_slice(&self.normals);
+    }
+}
+
+// Brighten freshly emitted vertex lights by a block light level
+fn apply_block_light(lights: &mut [[f32; 4]], level: u8) {
+    let boost = level as f32 / MAX_LIGHT_LEVEL as f32 * BLOCK_LIGHT_STRENGTH;
+    for light in lights {
+        light[0] += boost;
+        light[1] += boost;
+        light[2] += boost;
+    }
+}
+
+// LOD of the chunk at `chunk_pos` while the camera is in `camera_chunk`
+fn desired_lod(camera_chunk: (i32, i32, i32), chunk_pos: (i32, i32, i32)) -> u8 {
+    let distance_to_camera = (chunk_pos.0 - camera_chunk.0).pow(2)
+        + (chunk_pos.1 - camera_chunk.1).pow(2)
+        + (chunk_pos.2 - camera_chunk.2).pow(2);
+    if distance_to_camera < 6 * 6 {
+        1
+    } else if distance_to_camera < 12 * 12 {
+        2
+    } else if distance_to_camera < 18 * 18 {
+        4
+    } else if distance_to_camera < 24 * 24 {
+        8
+    } else {
+        MAX_LOD
+    }
+}
+
+// LODs of the chunks west, east, north and south of `chunk_pos`, the ones
+// skirts can hang towards
+fn neighbour_lods(
+    camera_chunk: (i32, i32, i32),
+    (x, y, z): (i32, i32, i32),
+) -> [u8; 4] {
+    [(x - 1, y, z), (x + 1, y, z), (x, y, z - 1), (x, y, z + 1)]
+        .map(|neighbour| desired_lod(camera_chunk, neighbour))
+}
+
+// Hang a skirt, a vertical apron, below a top face from each chunk border the
+// face touches where the neighbour is meshed at a coarser LOD. The surfaces of
+// the two chunks then don't meet along the border, and the skirts fill those
+// cracks. Snapping the border vertices to the neighbour's surface would avoid
+// the extra quads, but would need the neighbour's blocks at its LOD. Neighbouring
+// chunks are at most one LOD step apart, so a skirt twice as deep as this mesh's
+// LOD covers the difference.
+fn emit_skirts(
+    tile: &dyn Tile,
+    metadata: u8,
+    block: [i32; 3],
+    (local_x, local_z): (i32, i32),
+    lod: u8,
+    neighbour_lods: [u8; 4],
+    buffers: &mut LayerBuffers,
+) {
+    let coarser = neighbour_lods.map(|neighbour_lod| neighbour_lod > lod);
+    let lod = lod as i32;
+    let depth = (2 * lod) as f32;
+    let top = (block[1] + lod) as f32;
+    let min = [block[0] as f32, top - depth, block[2] as f32];
+    let max = [(block[0] + lod) as f32, top, (block[2] + lod) as f32];
+    let borders = [
+        (TileFace::West, coarser[0] && local_x == 0),
+        (TileFace::East, coarser[1] && local_x + lod >= CHUNK_SIZE_X),
+        (TileFace::North, coarser[2] && local_z == 0),
+        (TileFace::South, coarser[3] && local_z + lod >= CHUNK_SIZE_X),
+    ];
+    for (face, on_border) in borders {
+        if !on_border {
+            continue;
+        }
+        let normal = face.normal();
+        let vertex_count = buffers.vertices.len() as u32;
+        for corner in face.corners() {
+            buffers.vertices.push(std::array::from_fn(|axis| {
+                min[axis] + corner[axis] * (max[axis] - min[axis])
+                    - normal[axis] * SKIRT_INSET
+            }));
+        }
+        buffers
+            .indices
+            .extend([0, 1, 2, 2, 3, 0].map(|i| vertex_count + i));
+        buffers.uvs.extend([
+            [0.0, depth],
+            [lod as f32, depth],
+            [lod as f32, 0.0],
+            [0.0, 0.0],
+        ]);
+        buffers
+            .colors
+            .extend([tile.get_color_for_face(face, metadata); 4]);
+        buffers
+            .materials
+            .extend([tile.get_material_for_face(face, metadata); 4]);
+        buffers.lights.extend([tile.occlude_vertex(0); 4]);
+        buffers.normals.extend([normal; 4]);
+    }
+}
+
+// Greedily merge the faces of one slice, keyed by (u, v) cell coordinates
+fn merge_slice(
+    face: TileFace,
+    cells: &mut HashMap<(usize, usize), GreedyFace>,
+    size: usize,
+    lod: u8,
+    buffers: &mut LayerBuffers,
+) {
+    for v in 0..size {
+        for u in 0..size {
+            let Some(start) = cells.remove(&(u, v)) else {
+                continue;
+            };
+
+            let mut width = 1;
+            while u + width < size
+                && cells
+                    .get(&(u + width, v))
+                    .is_some_and(|other| start.can_merge(other))
+            {
+                width += 1;
+            }
+
+            let mut height = 1;
+            'grow: while v + height < size {
+                for du in 0..width {
+                    if !cells
+                        .get(&(u + du, v + height))
+                        .is_some_and(|other| start.can_merge(other))
+                    {
+                        break 'grow;
+                    }
+                }
+                height += 1;
+            }
+
+            for dv in 0..height {
+                for du in 0..width {
+                    cells.remove(&(u + du, v + dv));
+                }
+            }
+            start.emit(face, width, height, lod, buffers);
+        }
+    }
+}
+
+// Whether a block hides everything behind it from every side
+fn is_full_opaque(tile_registry: &TileRegistry, block_id: BlockId) -> bool {
+    tile_registry.get_handler(block_id).is_some_and(|tile| {
+        tile.render_layer() == RenderLayer::Opaque
+            && !tile.has_custom_geometry()
+            && tile.occludes_geometry(RenderLayer::Opaque, block_id)
+            && tile.occludes_geometry(RenderLayer::Transparent, block_id)
+    })
+}
+
+// For each face in NEIGHBORHOOD_SCAN order, whether the chunk's outermost layer
+// of blocks on that side is fully opaque. Always checked at full detail.
+fn sealed_faces(
+    worldview: &WorldView,
+    tile_registry: &TileRegistry,
+    (chunk_basis_x, chunk_basis_y, chunk_basis_z): (i32, i32, i32),
+) -> [bool; 6] {
+    let last = CHUNK_SIZE_X - 1;
+    NEIGHBORHOOD_SCAN.map(|(neighborhood, _)| {
+        let normal = neighborhood[4];
+        // Fixed coordinate along the normal, free coordinates on the other axes
+        let along = |n: i32| match n {
+            1 => last..=last,
+            -1 => 0..=0,
+            _ => 0..=last,
+        };
+        along(normal.0).all(|x| {
+            along(normal.1).all(|y| {
+                along(normal.2).all(|z| {
+                    let block_id = worldview.get_block(
+                        chunk_basis_x + x,
+                        chunk_basis_y + y,
+                        chunk_basis_z + z,
+                    );
+                    is_full_opaque(tile_registry, block_id)
+                })
+            })
+        })
+    })
+}
+
+// Index in NEIGHBORHOOD_SCAN of the face pointing the other way
+fn opposite_face(face_index: usize) -> usize {
+    let (x, y, z) = NEIGHBORHOOD_SCAN[face_index].0[4];
+    NEIGHBORHOOD_SCAN
+        .iter()
+        .position(|(neighborhood, _)| neighborhood[4] == (-x, -y, -z))
+        .expect("Every face has an opposite")
+}
+
 struct TessellatedChunk {
-    mesh: MeshEnvelope,
+    opaque: MeshEnvelope,
+    transparent: MeshEnvelope,
+    // Faces whose outer layer of blocks is fully opaque, see sealed_faces
+    sealed: [bool; 6],
+    // Neighbour LODs the skirts were hung for, see neighbour_lods
+    neighbour_lods: [u8; 4],
+    // Totals over both layers
+    vertex_count: usize,
+    index_count: usize,
+    generation_time: Duration,
+}
+
+/// Snapshot of the meshes a Tessellator holds, for profiling
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TessellatorStats {
+    pub ready_meshes: usize,
+    pub generating_meshes: usize,
+    // Totals over the ready meshes
+    pub vertices: usize,
+    pub triangles: usize,
+    // Mean time the ready meshes took to generate
+    pub average_generation_time: Duration,
 }
 
 impl TessellatedChunk {
     pub async fn from_world(
         world: Arc<World>,
         tile_registry: Arc<TileRegistry>,
+        view_cache: Arc<WorldViewCache>,
         (chunk_x, chunk_y, chunk_z): (i32, i32, i32),
         lod: u8,
+        neighbour_lods: [u8; 4],
     ) -> TessellatedChunk {
-        let mut vertices = Vec::new();
-        let mut indices = Vec::new();
-        let mut colors = Vec::new();
-        let mut uvs = Vec::new();
-        let mut materials = Vec::new();
-        let mut lights = Vec::new();
+        let start_time = Instant::now();
+        let mut opaque = LayerBuffers::default();
+        let mut transparent = LayerBuffers::default();
+
+        // Opaque faces to merge, per face direction
+        let mut greedy_faces: [GreedyLayer; 6] = Default::default();
+        let mut scratch = LayerBuffers::default();
 
         let chunk_basis_x = chunk_x * CHUNK_SIZE_X as i32;
         let chunk_basis_y = chunk_y * CHUNK_SIZE_X as i32;
         let chunk_basis_z = chunk_z * CHUNK_SIZE_X as i32;
 
-        let worldview;
-        worldview = WorldView::from_range(
-            &world,
-            chunk_basis_x - lod as i32,
-            chunk_basis_x + CHUNK_SIZE_X + lod as i32,
-            chunk_basis_y - lod as i32,
-            chunk_basis_y + CHUNK_SIZE_X + lod as i32,
-            chunk_basis_z - lod as i32,
-            chunk_basis_z + CHUNK_SIZE_X + lod as i32,
-        )
-        .await;
+        // Padded far enough for the neighbours, the light reaching in from
+        // further away is already stored in the chunks
+        let padding = lod as i32;
+        let min = (
+            chunk_basis_x - padding,
+            chunk_basis_y - padding,
+            chunk_basis_z - padding,
+        );
+        let max = (
+            chunk_basis_x + CHUNK_SIZE_X + padding,
+            chunk_basis_y + CHUNK_SIZE_X + padding,
+            chunk_basis_z + CHUNK_SIZE_X + padding,
+        );
+        let worldview = view_cache
+            .get_or_load((min, max), || async {
+                let chunks_x =
+                    min.0.div_euclid(CHUNK_SIZE_X)..=max.0.div_euclid(CHUNK_SIZE_X);
+                let chunks_y =
+                    min.1.div_euclid(CHUNK_SIZE_X)..=max.1.div_euclid(CHUNK_SIZE_X);
+                let chunks_z =
+                    min.2.div_euclid(CHUNK_SIZE_X)..=max.2.div_euclid(CHUNK_SIZE_X);
+                for x in chunks_x {
+                    for y in chunks_y.clone() {
+                        for z in chunks_z.clone() {
+                            World::update_light(&world, &tile_registry, x, y, z).await;
+                        }
+                    }
+                }
+                WorldView::from_range(&world, min.0, max.0, min.1, max.1, min.2, max.2)
+                    .await
+            })
+            .await;
+        let sealed = sealed_faces(
+            &worldview,
+            &tile_registry,
+            (chunk_basis_x, chunk_basis_y, chunk_basis_z),
+        );
 
         for x in (0..(CHUNK_SIZE_X as i32)).step_by(lod as usize) {
             for y in (0..(CHUNK_SIZE_X as i32)).step_by(lod as usize) {
@@ -158,8 +610,39 @@ impl TessellatedChunk {
                     let tile_handler = tile_registry
                         .get_handler(block_id)
                         .expect("Tile handler not found");
+                    let metadata = worldview.get_block_meta(block_x, block_y, block_z);
+                    let render_layer = tile_handler.render_layer();
+                    let buffers = match render_layer {
+                        RenderLayer::Opaque => &mut opaque,
+                        RenderLayer::Transparent => &mut transparent,
+                    };
 
-                    for (neighborhood, face) in NEIGHBORHOOD_SCAN.iter() {
+                    if tile_handler.has_custom_geometry() {
+                        if lod == 1 {
+                            let first_light = buffers.lights.len();
+                            tile_handler.tesselate_custom(
+                                block_x as f32,
+                                block_y as f32,
+                                block_z as f32,
+                                metadata,
+                                buffers,
+                            );
+                            apply_block_light(
+                                &mut buffers.lights[first_light..],
+                                worldview.get_light(block_x, block_y, block_z),
+                            );
+                            continue;
+                        }
+                        // LOD meshes draw solid shapes like slabs as full cubes
+                        // and leave out small details like plants
+                        if !tile_handler.is_solid() {
+                            continue;
+                        }
+                    }
+
+                    for (face_index, (neighborhood, face)) in
+                        NEIGHBORHOOD_SCAN.iter().enumerate()
+                    {
                         // see if neighbor 4 is air
                         let neighbor_x = block_x + neighborhood[4].0 * (lod as i32);
                         let neighbor_y = block_y + neighborhood[4].1 * (lod as i32);
@@ -174,7 +657,7 @@ impl TessellatedChunk {
                                     "Unable to find tile handler for neighbor block",
                                 );
                             if direct_neighbor_handler
-                                .occludes_geometry(RenderLayer::Opaque, block_id)
+                                .occludes_geometry(render_layer, block_id)
                             {
                                 continue;
                             }
@@ -193,46 +676,139 @@ impl TessellatedChunk {
                                 worldview.get_block(neighbor_x, neighbor_y, neighbor_z);
                         }
 
+                        // Faces that take part in greedy meshing go to a scratch
+                        // buffer first so they can be merged later
+                        let greedy =
+                            GREEDY_MESHING && render_layer == RenderLayer::Opaque;
+                        let target = if greedy {
+                            scratch.clear();
+                            &mut scratch
+                        } else {
+                            &mut *buffers
+                        };
+
+                        let first_light = target.lights.len();
                         tile_handler.tesselate_face(
                             &tile_registry,
-                            RenderLayer::Opaque,
+                            render_layer,
                             block_id,
                             block_x as f32,
                             block_y as f32,
                             block_z as f32,
                             *face,
                             neighbor_ids,
-                            block_id,
-                            &mut vertices,
-                            &mut indices,
-                            &mut colors,
-                            &mut uvs,
-                            &mut materials,
-                            &mut lights,
+                            metadata,
+                            &mut target.vertices,
+                            &mut target.indices,
+                            &mut target.colors,
+                            &mut target.uvs,
+                            &mut target.materials,
+                            &mut target.lights,
+                            &mut target.normals,
                             lod,
                         );
+                        // Faces are lit by the block they look out into
+                        apply_block_light(
+                            &mut target.lights[first_light..],
+                            worldview.get_light(neighbor_x, neighbor_y, neighbor_z),
+                        );
+
+                        if greedy {
+                            let origin =
+                                [block_x as f32, block_y as f32, block_z as f32];
+                            match GreedyFace::from_buffers(origin, *face, lod, &scratch)
+                            {
+                                Some(greedy_face) => {
+                                    let cell = [
+                                        (x / lod as i32) as usize,
+                                        (y / lod as i32) as usize,
+                                        (z / lod as i32) as usize,
+                                    ];
+                                    let (normal_axis, u_axis, v_axis) =
+                                        face_axes(*face);
+                                    greedy_faces[face_index]
+                                        .entry(cell[normal_axis])
+                                        .or_default()
+                                        .insert(
+                                            (cell[u_axis], cell[v_axis]),
+                                            greedy_face,
+                                        );
+                                }
+                                None => buffers.append(&scratch),
+                            }
+                        }
+
+                        if *face == TileFace::Top && render_layer == RenderLayer::Opaque
+                        {
+                            emit_skirts(
+                                tile_handler,
+                                metadata,
+                                [block_x, block_y, block_z],
+                                (x, z),
+                                lod,
+                                neighbour_lods,
+                                buffers,
+                            );
+                        }
                     }
                 }
             }
         }
 
+        let cells_per_axis = (CHUNK_SIZE_X / lod as i32) as usize;
+        for (face_index, slices) in greedy_faces.iter_mut().enumerate() {
+            let face = NEIGHBORHOOD_SCAN[face_index].1;
+            for cells in slices.values_mut() {
+                merge_slice(face, cells, cells_per_axis, lod, &mut opaque);
+            }
+        }
+
+        let vertex_count = opaque.vertices.len() + transparent.vertices.len();
+        let index_count = opaque.indices.len() + transparent.indices.len();
         return Self {
-            mesh: MeshEnvelope::new(MeshParams {
-                vertices,
-                indices: Some(indices),
-                uvs: Some(uvs),
-                material_ids: Some(materials),
-                colors: Some(colors),
-                light: Some(lights),
-            }),
+            opaque: opaque.into_mesh(),
+            transparent: transparent.into_mesh(),
+            sealed,
+            neighbour_lods,
+            vertex_count,
+            index_count,
+            generation_time: start_time.elapsed(),
         };
     }
 }
 
+// One of the tessellator's mesh generation slots, given back when dropped so a
+// panicking mesh task doesn't keep it forever
+struct InFlightSlot(Arc<AtomicUsize>);
+
+impl InFlightSlot {
+    fn take(meshes_in_flight: &Arc<AtomicUsize>) -> Self {
+        meshes_in_flight.fetch_add(1, Ordering::AcqRel);
+        InFlightSlot(Arc::clone(meshes_in_flight))
+    }
+}
+
+impl Drop for InFlightSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
 pub struct Tessellator {
     tessellated_chunks:
         Arc<Mutex<HashMap<(i32, i32, i32), HashMap<u8, QueuedItem<TessellatedChunk>>>>>,
     render_distance: i32,
+    // Maximum number of missing meshes enqueued for generation per frame
+    mesh_budget: usize,
+    // Maximum number of meshes generating at once, new ones wait for a slot
+    max_meshes_in_flight: usize,
+    // Meshes currently generating on the blocking pool, including ones whose
+    // chunk was discarded since, as their work can't be cancelled
+    meshes_in_flight: Arc<AtomicUsize>,
+    // Padded views shared between the meshes of a chunk and its neighbors
+    view_cache: Arc<WorldViewCache>,
+    // Chunk the camera was in on the last render_chunks, which sets the LODs
+    camera_chunk: Mutex<(i32, i32, i32)>,
     tile_registry: Arc<TileRegistry>,
 }
 
@@ -241,83 +817,109 @@ impl Tessellator {
         tessellator: Arc<Tessellator>,
         mut chunk_updates: UnboundedReceiver<ChunkUpdateMessage>,
     ) {
-        loop {
-            // just print for now
-            if let Some(chunk_update) = chunk_updates.recv().await {
+        while let Some(first_update) = chunk_updates.recv().await {
+            // Edits tend to come in bursts, wait a moment and fold everything
+            // that arrived meanwhile into one set of chunks to remesh
+            tokio::time::sleep(REMESH_DEBOUNCE).await;
+            let mut world = Arc::clone(&first_update.world);
+            let mut dirty_chunks = HashSet::new();
+            let mut update = Some(first_update);
+            while let Some(chunk_update) = update {
                 println!(
                     "[Tessellator] Chunk update received at position ({}, {}, {})",
                     chunk_update.x, chunk_update.y, chunk_update.z
                 );
-                // get current time to measure performance
-                let start_time = std::time::Instant::now();
-                let mut lods_needed = Vec::new();
-                {
-                    let mut chunks_handle =
-                        tessellator.tessellated_chunks.lock().unwrap();
-                    let chunk_pos = (chunk_update.x, chunk_update.y, chunk_update.z);
-                    if let Some(chunk_lods) = chunks_handle.get_mut(&chunk_pos) {
-                        // If we have the chunk, we need to check which lods we need to update
-                        for (&lod, _) in chunk_lods.iter() {
-                            lods_needed.push(lod);
+                // Faces, occlusion and light of the neighbours depend on the chunk
+                for ox in -1..=1 {
+                    for oy in -1..=1 {
+                        for oz in -1..=1 {
+                            dirty_chunks.insert((
+                                chunk_update.x + ox,
+                                chunk_update.y + oy,
+                                chunk_update.z + oz,
+                            ));
                         }
                     }
                 }
-                let mut lod_meshes = Vec::new();
-                for lod in lods_needed {
-                    for ox in -1..=1 {
-                        for oy in -1..=1 {
-                            for oz in -1..=1 {
-                                let mesh_envelope = TessellatedChunk::from_world(
-                                    Arc::clone(&chunk_update.world),
-                                    Arc::clone(&tessellator.tile_registry),
-                                    (
-                                        chunk_update.x + ox,
-                                        chunk_update.y + oy,
-                                        chunk_update.z + oz,
-                                    ),
-                                    lod,
-                                )
-                                .await;
-                                lod_meshes.push((
-                                    (
-                                        chunk_update.x + ox,
-                                        chunk_update.y + oy,
-                                        chunk_update.z + oz,
-                                    ),
-                                    lod,
-                                    mesh_envelope,
-                                ));
-                            }
+                world = chunk_update.world;
+                update = chunk_updates.try_recv().ok();
+            }
+
+            // get current time to measure performance
+            let start_time = std::time::Instant::now();
+            let mut lods_needed = Vec::new();
+            {
+                let chunks_handle = tessellator.tessellated_chunks.lock().unwrap();
+                for &chunk_pos in &dirty_chunks {
+                    // Only chunks that have meshes need them rebuilt
+                    if let Some(chunk_lods) = chunks_handle.get(&chunk_pos) {
+                        for &lod in chunk_lods.keys() {
+                            lods_needed.push((chunk_pos, lod));
                         }
                     }
                 }
-                {
-                    let mut chunks_handle =
-                        tessellator.tessellated_chunks.lock().unwrap();
-                    for (pos, lod, mesh_envelope) in lod_meshes {
-                        if let Some(chunk_lods) = chunks_handle.get_mut(&pos) {
-                            chunk_lods.insert(lod, QueuedItem::Ready(mesh_envelope));
-                        }
+            }
+            // Meshing is CPU bound, so the chunks are meshed side by side on the
+            // blocking pool instead of stalling this runtime's workers
+            let camera_chunk = *tessellator.camera_chunk.lock().unwrap();
+            let runtime = tokio::runtime::Handle::current();
+            let mut remeshes = JoinSet::new();
+            for (chunk_pos, lod) in lods_needed {
+                let world = Arc::clone(&world);
+                let tile_registry = Arc::clone(&tessellator.tile_registry);
+                let view_cache = Arc::clone(&tessellator.view_cache);
+                let neighbour_lods = neighbour_lods(camera_chunk, chunk_pos);
+                let runtime = runtime.clone();
+                remeshes.spawn_blocking(move || {
+                    let mesh_envelope = runtime.block_on(TessellatedChunk::from_world(
+                        world,
+                        tile_registry,
+                        view_cache,
+                        chunk_pos,
+                        lod,
+                        neighbour_lods,
+                    ));
+                    (chunk_pos, lod, mesh_envelope)
+                });
+            }
+            let mut lod_meshes = Vec::new();
+            while let Some(remesh) = remeshes.join_next().await {
+                match remesh {
+                    Ok(lod_mesh) => lod_meshes.push(lod_mesh),
+                    Err(e) => eprintln!("Chunk failed to remesh: {}", e),
+                }
+            }
+            {
+                let mut chunks_handle = tessellator.tessellated_chunks.lock().unwrap();
+                for (pos, lod, mesh_envelope) in lod_meshes {
+                    if let Some(chunk_lods) = chunks_handle.get_mut(&pos) {
+                        chunk_lods.insert(lod, QueuedItem::Ready(mesh_envelope));
                     }
                 }
-                println!(
-                    "[Tessellator] Chunk update processed for position ({}, {}, {}) in {} ms",
-                    chunk_update.x,
-                    chunk_update.y,
-                    chunk_update.z,
-                    start_time.elapsed().as_millis()
-                );
             }
+            println!(
+                "[Tessellator] Remeshed {} chunks in {} ms",
+                dirty_chunks.len(),
+                start_time.elapsed().as_millis()
+            );
         }
     }
     pub fn new(
         render_distance: i32,
+        mesh_budget: usize,
+        max_meshes_in_flight: usize,
         chunk_updates: UnboundedReceiver<ChunkUpdateMessage>,
+        view_cache: Arc<WorldViewCache>,
         tile_registry: Arc<TileRegistry>,
     ) -> Arc<Self> {
         let tessellator = Arc::new(Tessellator {
             tessellated_chunks: Arc::new(Mutex::new(HashMap::new())),
             render_distance,
+            mesh_budget,
+            max_meshes_in_flight,
+            meshes_in_flight: Arc::new(AtomicUsize::new(0)),
+            view_cache,
+            camera_chunk: Mutex::new((0, 0, 0)),
             tile_registry,
         });
         spawn(Self::handle_chunk_updates(
@@ -326,26 +928,108 @@ impl Tessellator {
         ));
         tessellator
     }
-    pub fn discard_chunk(&mut self, chunk_pos: (i32, i32, i32)) {
-        self.tessellated_chunks.lock().unwrap().remove(&chunk_pos);
+    pub fn discard_chunk(&self, chunk_pos: (i32, i32, i32)) {
+        let removed = self.tessellated_chunks.lock().unwrap().remove(&chunk_pos);
+        // Stop meshes that are still generating, nothing will read them anymore
+        for queued_mesh in removed.iter().flat_map(|lods| lods.values()) {
+            queued_mesh.cancel();
+        }
+    }
+    /// Counts the meshes held for every chunk and LOD, and totals the geometry
+    /// of the ones that are ready
+    pub fn stats(&self) -> TessellatorStats {
+        let mut stats = TessellatorStats::default();
+        let mut total_generation_time = Duration::ZERO;
+        let chunks_handle = self.tessellated_chunks.lock().unwrap();
+        for queued_mesh in chunks_handle.values().flat_map(|lods| lods.values()) {
+            match queued_mesh {
+                QueuedItem::Generating(_) => stats.generating_meshes += 1,
+                QueuedItem::Failed => (),
+                QueuedItem::Ready(tessellated) => {
+                    stats.ready_meshes += 1;
+                    stats.vertices += tessellated.vertex_count;
+                    stats.triangles += tessellated.index_count / 3;
+                    total_generation_time += tessellated.generation_time;
+                }
+            }
+        }
+        if stats.ready_meshes > 0 {
+            stats.average_generation_time =
+                total_generation_time / stats.ready_meshes as u32;
+        }
+        stats
+    }
+    /// Counts the chunks within `radius` of `center` that have a ready mesh at
+    /// any LOD, returned along with the number of chunks in that range. Meant
+    /// for waiting until the terrain around a point can be drawn, the meshes
+    /// themselves are still only enqueued by render_chunks. Doesn't await, so
+    /// the mesh map is only locked while counting.
+    pub fn chunks_ready_in(
+        &self,
+        center: (i32, i32, i32),
+        radius: i32,
+    ) -> (usize, usize) {
+        let mut ready = 0;
+        let mut total = 0;
+        let mut chunks_handle = self.tessellated_chunks.lock().unwrap();
+        for x in -radius..=radius {
+            for y in -radius..=radius {
+                for z in -radius..=radius {
+                    total += 1;
+                    let chunk_pos = (center.0 + x, center.1 + y, center.2 + z);
+                    let Some(chunk_lods) = chunks_handle.get_mut(&chunk_pos) else {
+                        continue;
+                    };
+                    if chunk_lods
+                        .values_mut()
+                        .any(|queued_mesh| queued_mesh.try_get().is_some())
+                    {
+                        ready += 1;
+                    }
+                }
+            }
+        }
+        (ready, total)
+    }
+    /// Discards the meshes of every chunk further than `keep_radius` from `center`
+    pub fn discard_outside(&self, center: (i32, i32, i32), keep_radius: i32) {
+        let out_of_range: Vec<_> = self
+            .tessellated_chunks
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|&&pos| !World::chunk_in_radius(center, pos, keep_radius))
+            .copied()
+            .collect();
+        for chunk_pos in out_of_range {
+            self.discard_chunk(chunk_pos);
+        }
     }
     pub async fn render_chunks(
         &self,
         world: Arc<World>,
         tile_registry: Arc<TileRegistry>,
         (camera_pos_x, camera_pos_y, camera_pos_z): (f32, f32, f32),
+        frustum_planes: &[[f32; 4]; 6],
         gl: &GlFns,
     ) -> usize {
         let mut unmet_meshes = 0;
-        let mut queued_meshes = 0;
         let camera_chunk_pos = (
-            (camera_pos_x as i32).div_euclid(CHUNK_SIZE_X),
-            (camera_pos_y as i32).div_euclid(CHUNK_SIZE_X),
-            (camera_pos_z as i32).div_euclid(CHUNK_SIZE_X),
+            (camera_pos_x.floor() as i32).div_euclid(CHUNK_SIZE_X),
+            (camera_pos_y.floor() as i32).div_euclid(CHUNK_SIZE_X),
+            (camera_pos_z.floor() as i32).div_euclid(CHUNK_SIZE_X),
         );
 
+        *self.camera_chunk.lock().unwrap() = camera_chunk_pos;
         let mut chunks_handle = self.tessellated_chunks.lock().unwrap();
 
+        // Chunks whose opaque mesh was drawn, so their transparent mesh can be
+        // drawn afterwards: (distance to camera squared, chunk position, lod)
+        let mut transparent_queue = Vec::new();
+        // Meshes that are wanted but not yet queued, nearest ones get enqueued
+        // first: (distance to camera squared, chunk position, lod)
+        let mut missing_meshes = Vec::new();
+
         for x in -self.render_distance..self.render_distance {
             for z in -self.render_distance..self.render_distance {
                 for y in -self.render_distance..self.render_distance {
@@ -358,43 +1042,46 @@ impl Tessellator {
                         + (chunk_pos.1 - camera_chunk_pos.1).pow(2)
                         + (chunk_pos.2 - camera_chunk_pos.2).pow(2))
                         as f32;
-                    let desired_lod: u8 = if distance_to_camera < (6 * 6) as f32 {
-                        1
-                    } else if distance_to_camera < (12 * 12) as f32 {
-                        2
-                    } else if distance_to_camera < (18 * 18) as f32 {
-                        4
-                    } else if distance_to_camera < (24 * 24) as f32 {
-                        8
-                    } else {
-                        16
-                    };
+                    let desired_lod = desired_lod(camera_chunk_pos, chunk_pos);
+                    let neighbour_lods = neighbour_lods(camera_chunk_pos, chunk_pos);
                     if !chunks_handle.contains_key(&chunk_pos) {
                         //let chunk_mesh = world.tesselate(&gl, &_tile_registry, chunk_pos, 2);
                         //tesselated_chunks.insert(chunk_pos, chunk_mesh);
                         chunks_handle.insert(chunk_pos, HashMap::new());
                     }
-                    let mut rng = rand::rng();
+                    // Meshes that failed to generate are treated as missing and
+                    // enqueued again, and so are meshes missing a skirt towards a
+                    // neighbour that became coarser
+                    chunks_handle.get_mut(&chunk_pos).unwrap().retain(
+                        |&lod, queued_mesh| match queued_mesh {
+                            QueuedItem::Failed => false,
+                            QueuedItem::Ready(tessellated) if lod == desired_lod => {
+                                (0..4).all(|i| {
+                                    neighbour_lods[i] <= lod
+                                        || tessellated.neighbour_lods[i] > lod
+                                })
+                            }
+                            _ => true,
+                        },
+                    );
+                    // Meshes keep generating for chunks outside the frustum so they
+                    // are ready when the camera turns, they just aren't drawn
+                    let visible = Self::chunk_in_frustum(frustum_planes, chunk_pos)
+                        && !Self::chunk_occluded(
+                            &chunks_handle,
+                            chunk_pos,
+                            (camera_pos_x, camera_pos_y, camera_pos_z),
+                        );
 
                     // See if we have the chunk that we want
                     let found_lod =
                         if !chunks_handle[&chunk_pos].contains_key(&desired_lod) {
-                            //if queued_meshes < 6 {
-                            if rng.random_bool(0.1) {
-                                // If not, spawn a thread to generate it
-                                let handle =
-                                    QueuedItem::enqueue(TessellatedChunk::from_world(
-                                        Arc::clone(&world),
-                                        Arc::clone(&tile_registry),
-                                        chunk_pos,
-                                        desired_lod,
-                                    ));
-                                chunks_handle
-                                    .get_mut(&chunk_pos)
-                                    .unwrap()
-                                    .insert(desired_lod, handle);
-                                queued_meshes += 1;
-                            }
+                            // If not, remember it so it can be generated in order
+                            missing_meshes.push((
+                                distance_to_camera,
+                                chunk_pos,
+                                desired_lod,
+                            ));
                             false
                         } else {
                             // If we have the chunk, check if it's ready
@@ -404,9 +1091,20 @@ impl Tessellator {
                                 .get_mut(&desired_lod)
                                 .unwrap();
 
-                            if let Some(mesh_envelope) = queued_mesh.get().await {
+                            if !visible {
+                                // Only whether it's ready matters for culled chunks
+                                queued_mesh.get().await.is_some()
+                            } else if let Some(tessellated) = queued_mesh.get().await {
                                 // If it's ready, render it
-                                mesh_envelope.mesh.get_mesh(&gl).render(&gl);
+                                tessellated.opaque.get_mesh(&gl).render(&gl);
+                                transparent_queue.push((
+                                    Self::distance_to_chunk_center(
+                                        (camera_pos_x, camera_pos_y, camera_pos_z),
+                                        chunk_pos,
+                                    ),
+                                    chunk_pos,
+                                    desired_lod,
+                                ));
                                 true
                             } else {
                                 false
@@ -420,9 +1118,23 @@ impl Tessellator {
                             if let Some(queued_mesh) = chunks_handle.get_mut(&chunk_pos)
                             {
                                 if let Some(queued_mesh) = queued_mesh.get_mut(&lod) {
-                                    if let Some(mesh_envelope) = queued_mesh.get().await
-                                    {
-                                        mesh_envelope.mesh.get_mesh(&gl).render(&gl);
+                                    if let Some(tessellated) = queued_mesh.get().await {
+                                        if !visible {
+                                            break;
+                                        }
+                                        tessellated.opaque.get_mesh(&gl).render(&gl);
+                                        transparent_queue.push((
+                                            Self::distance_to_chunk_center(
+                                                (
+                                                    camera_pos_x,
+                                                    camera_pos_y,
+                                                    camera_pos_z,
+                                                ),
+                                                chunk_pos,
+                                            ),
+                                            chunk_pos,
+                                            lod,
+                                        ));
                                         break;
                                     } else {
                                         // If we are still generating, we can skip this lod
@@ -436,6 +1148,164 @@ impl Tessellator {
                 }
             }
         }
+
+        // Nearest chunks get the free generation slots first, the rest are
+        // picked up again on a later frame
+        missing_meshes.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let free_slots = self
+            .max_meshes_in_flight
+            .saturating_sub(self.meshes_in_flight.load(Ordering::Acquire));
+        let runtime = tokio::runtime::Handle::current();
+        for (_, chunk_pos, lod) in missing_meshes
+            .into_iter()
+            .take(self.mesh_budget.min(free_slots))
+        {
+            let world = Arc::clone(&world);
+            let tile_registry = Arc::clone(&tile_registry);
+            let view_cache = Arc::clone(&self.view_cache);
+            let runtime = runtime.clone();
+            let slot = InFlightSlot::take(&self.meshes_in_flight);
+            let neighbour_lods = neighbour_lods(camera_chunk_pos, chunk_pos);
+            let handle = QueuedItem::enqueue_blocking(move || {
+                let _slot = slot;
+                runtime.block_on(TessellatedChunk::from_world(
+                    world,
+                    tile_registry,
+                    view_cache,
+                    chunk_pos,
+                    lod,
+                    neighbour_lods,
+                ))
+            });
+            chunks_handle
+                .get_mut(&chunk_pos)
+                .unwrap()
+                .insert(lod, handle);
+        }
+
+        // Transparent geometry goes last, furthest first, without writing depth
+        transparent_queue.sort_by(|a, b| b.0.total_cmp(&a.0));
+        unsafe {
+            gl.DepthMask(GL_FALSE.0 as u8);
+        }
+        for (_, chunk_pos, lod) in transparent_queue {
+            if let Some(queued_mesh) = chunks_handle
+                .get_mut(&chunk_pos)
+                .and_then(|chunk_lods| chunk_lods.get_mut(&lod))
+                && let Some(tessellated) = queued_mesh.get().await
+            {
+                tessellated.transparent.get_mesh(gl).render(gl);
+            }
+        }
+        unsafe {
+            gl.DepthMask(GL_TRUE.0 as u8);
+        }
+
         unmet_meshes
     }
+
+    /// Returns false only when the chunk's bounding box lies entirely on the
+    /// outer side of one of the frustum planes.
+    fn chunk_in_frustum(
+        frustum_planes: &[[f32; 4]; 6],
+        (chunk_x, chunk_y, chunk_z): (i32, i32, i32),
+    ) -> bool {
+        let min = [
+            (chunk_x * CHUNK_SIZE_X) as f32,
+            (chunk_y * CHUNK_SIZE_X) as f32,
+            (chunk_z * CHUNK_SIZE_X) as f32,
+        ];
+        let max = [
+            min[0] + CHUNK_SIZE_X as f32,
+            min[1] + CHUNK_SIZE_X as f32,
+            min[2] + CHUNK_SIZE_X as f32,
+        ];
+        frustum_planes.iter().all(|plane| {
+            // Test the corner furthest along the plane normal
+            let corner = [
+                if plane[0] >= 0.0 { max[0] } else { min[0] },
+                if plane[1] >= 0.0 { max[1] } else { min[1] },
+                if plane[2] >= 0.0 { max[2] } else { min[2] },
+            ];
+            plane[0] * corner[0]
+                + plane[1] * corner[1]
+                + plane[2] * corner[2]
+                + plane[3]
+                >= 0.0
+        })
+    }
+
+    /// Returns true when every face of the chunk the camera could see is sealed,
+    /// on both sides of the chunk border. Nothing in the chunk can show then, the
+    /// blocks across the border hide it. Chunks without a ready mesh, or with a
+    /// neighbor without one, are never considered occluded.
+    fn chunk_occluded(
+        chunks: &HashMap<(i32, i32, i32), HashMap<u8, QueuedItem<TessellatedChunk>>>,
+        chunk_pos: (i32, i32, i32),
+        (camera_pos_x, camera_pos_y, camera_pos_z): (f32, f32, f32),
+    ) -> bool {
+        // Sealed faces of any ready mesh of the chunk, they are all made at
+        // full detail so the lod doesn't matter
+        let sealed_faces = |chunk_pos| {
+            chunks
+                .get(&chunk_pos)?
+                .values()
+                .find_map(|queued_mesh| match queued_mesh {
+                    QueuedItem::Ready(tessellated) => Some(tessellated.sealed),
+                    _ => None,
+                })
+        };
+        let Some(sealed) = sealed_faces(chunk_pos) else {
+            return false;
+        };
+
+        let camera = [camera_pos_x, camera_pos_y, camera_pos_z];
+        let min = [
+            (chunk_pos.0 * CHUNK_SIZE_X) as f32,
+            (chunk_pos.1 * CHUNK_SIZE_X) as f32,
+            (chunk_pos.2 * CHUNK_SIZE_X) as f32,
+        ];
+        let mut faces_camera = false;
+        for (face_index, (neighborhood, _)) in NEIGHBORHOOD_SCAN.iter().enumerate() {
+            let (normal_x, normal_y, normal_z) = neighborhood[4];
+            // Only faces with the camera on their outer side can be seen
+            let facing =
+                [normal_x, normal_y, normal_z]
+                    .iter()
+                    .enumerate()
+                    .any(|(axis, &n)| {
+                        (n > 0 && camera[axis] > min[axis] + CHUNK_SIZE_X as f32)
+                            || (n < 0 && camera[axis] < min[axis])
+                    });
+            if !facing {
+                continue;
+            }
+            faces_camera = true;
+            let neighbor_pos = (
+                chunk_pos.0 + normal_x,
+                chunk_pos.1 + normal_y,
+                chunk_pos.2 + normal_z,
+            );
+            let neighbor_sealed = sealed_faces(neighbor_pos)
+                .is_some_and(|neighbor| neighbor[opposite_face(face_index)]);
+            if !sealed[face_index] || !neighbor_sealed {
+                return false;
+            }
+        }
+        // The camera is inside the chunk
+        faces_camera
+    }
+
+    fn distance_to_chunk_center(
+        (camera_pos_x, camera_pos_y, camera_pos_z): (f32, f32, f32),
+        (chunk_x, chunk_y, chunk_z): (i32, i32, i32),
+    ) -> f32 {
+        let half_chunk = CHUNK_SIZE_X as f32 / 2.0;
+        let center_x = (chunk_x * CHUNK_SIZE_X) as f32 + half_chunk;
+        let center_y = (chunk_y * CHUNK_SIZE_X) as f32 + half_chunk;
+        let center_z = (chunk_z * CHUNK_SIZE_X) as f32 + half_chunk;
+        (center_x - camera_pos_x).powi(2)
+            + (center_y - camera_pos_y).powi(2)
+            + (center_z - camera_pos_z).powi(2)
+    }
 }
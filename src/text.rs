@@ -1,8 +1,11 @@
+use std::collections::HashMap;
+use std::collections::hash_map::{DefaultHasher, Entry};
 use std::error::Error;
+use std::hash::{Hash, Hasher};
 
 use gl33::GlFns;
 use ndarray::Array2;
-use rand::{Rng, SeedableRng, rngs::StdRng};
+use rand::{SeedableRng, rngs::StdRng, seq::index};
 
 use crate::{
     mesh::{Mesh, MeshEnvelope, MeshParams},
@@ -98,6 +101,8 @@ enum Glyph {
     Colon,
     Comma,
     Ellipsis,
+    Digit(u8),
+    Minus,
     Logograph(Logograph),
 }
 
@@ -116,6 +121,9 @@ impl Glyph {
             Glyph::Colon => [5, 7],
             Glyph::Comma => [5, 8],
             Glyph::Ellipsis => [5, 9],
+            // Two rows of five below the syllables
+            Glyph::Digit(digit) => [digit % 5, 10 + digit / 5],
+            Glyph::Minus => [5, 10],
             Glyph::Logograph(logograph) => logograph.material_id(),
         }
     }
@@ -143,9 +151,15 @@ impl Glyph {
             if s == "," {
                 return Some(Glyph::Comma);
             }
+            if s == "-" {
+                return Some(Glyph::Minus);
+            }
             if let Some(vowel) = Vowel::from_char(s.chars().next().unwrap()) {
                 return Some(Glyph::Single(vowel));
             }
+            if let Some(digit) = s.chars().next().unwrap().to_digit(10) {
+                return Some(Glyph::Digit(digit as u8));
+            }
         } else if s.len() == 2 {
             if s == "<<" {
                 return Some(Glyph::OpenAngleQuote);
@@ -212,8 +226,42 @@ struct RenderableGlyph {
     glyph: Glyph,
     background_color: [f32; 4],
     foreground_color: [f32; 4],
+    underline: bool,
+    strikethrough: bool,
+    // Color of the decoration lines, kept apart so spaces can carry one
+    decoration_color: [f32; 4],
+}
+
+// Geometry of a text mesh while it's being built
+#[derive(Default)]
+struct TextBuffers {
+    vertices: Vec<[f32; 3]>,
+    indices: Vec<u32>,
+    colors: Vec<[f32; 4]>,
+    materials: Vec<[i32; 2]>,
+    lights: Vec<[f32; 4]>,
+    uvs: Vec<[f32; 2]>,
+}
+
+impl TextBuffers {
+    fn into_mesh(self) -> MeshEnvelope {
+        MeshEnvelope::new(MeshParams {
+            vertices: self.vertices,
+            indices: Some(self.indices),
+            uvs: Some(self.uvs),
+            material_ids: Some(self.materials),
+            colors: Some(self.colors),
+            light: Some(self.lights),
+            normals: None,
+        })
+    }
 }
 
+/// Width of underline and strikethrough quads, one texel of the font
+const DECORATION_THICKNESS: f32 = 1.0 / 8.0;
+const DECORATION_OFFSET: f32 = 0.01;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum MeshOrigin {
     TL, // Top Left
     TC, // Top Center
@@ -221,7 +269,25 @@ pub enum MeshOrigin {
     BL, // Bottom Left
     BC, // Bottom Center
     BR, // Bottom Right
-    CC,
+    CC, // Center
+}
+
+impl MeshOrigin {
+    /// Where the origin sits in the text's bounding box, as fractions of its
+    /// width and height measured from the top right corner. Lines are laid
+    /// out right to left and glyphs top to bottom, so these also scale the
+    /// line and glyph counts.
+    fn anchor(&self) -> (f32, f32) {
+        match self {
+            MeshOrigin::TR => (0.0, 0.0),
+            MeshOrigin::TC => (0.5, 0.0),
+            MeshOrigin::TL => (1.0, 0.0),
+            MeshOrigin::BR => (0.0, 1.0),
+            MeshOrigin::BC => (0.5, 1.0),
+            MeshOrigin::BL => (1.0, 1.0),
+            MeshOrigin::CC => (0.5, 0.5),
+        }
+    }
 }
 
 impl RenderableGlyph {
@@ -230,20 +296,45 @@ impl RenderableGlyph {
             glyph: Glyph::Blank,
             background_color: [0.0, 0.0, 0.0, 0.0],
             foreground_color: [0.0, 0.0, 0.0, 0.0],
+            underline: false,
+            strikethrough: false,
+            decoration_color: [0.0, 0.0, 0.0, 0.0],
         }
     }
-    fn tessellate_glyph(
+    /// Emits a flat colored quad spanning the glyph cell from `x0` to `x1`
+    fn tessellate_decoration(
         &self,
-        x: f32,
+        x0: f32,
+        x1: f32,
         y: f32,
         z: f32,
-        vertices: &mut Vec<[f32; 3]>,
-        indices: &mut Vec<u32>,
-        colors: &mut Vec<[f32; 4]>,
-        materials: &mut Vec<[i32; 2]>,
-        lights: &mut Vec<[f32; 4]>,
-        uvs: &mut Vec<[f32; 2]>,
+        buffers: &mut TextBuffers,
     ) {
+        let vertex_count = buffers.vertices.len() as u32;
+        buffers.vertices.push([x0, y, z]);
+        buffers.vertices.push([x1, y, z]);
+        buffers.vertices.push([x1, y + 1.0, z]);
+        buffers.vertices.push([x0, y + 1.0, z]);
+        buffers
+            .indices
+            .extend([0, 1, 2, 2, 3, 0].map(|i| vertex_count + i));
+        for _ in 0..4 {
+            buffers.colors.push(self.decoration_color);
+            // Negative materials are drawn as flat color by the text shader
+            buffers.materials.push([-1, -1]);
+            buffers.lights.push(self.background_color);
+            buffers.uvs.push([0.0, 0.0]);
+        }
+    }
+    fn tessellate_glyph(&self, x: f32, y: f32, z: f32, buffers: &mut TextBuffers) {
+        let TextBuffers {
+            vertices,
+            indices,
+            colors,
+            materials,
+            lights,
+            uvs,
+        } = buffers;
         let vertex_count = vertices.len() as u32;
         vertices.push([
             BACK_BOTTOM_LEFT_X + x,
@@ -280,56 +371,97 @@ impl RenderableGlyph {
             materials.push(self.glyph.material_id().map(|x| x as i32));
             lights.push(self.background_color);
         }
+
+        // Lines run top to bottom, so the underline sits along the side of
+        // the glyph facing the next line. Decorations are nudged towards the
+        // viewer so they draw over the glyph.
+        let decoration_z = BACK_BOTTOM_LEFT_Z + z - 1.0 + DECORATION_OFFSET;
+        let left = BACK_BOTTOM_LEFT_X + x;
+        if self.underline {
+            self.tessellate_decoration(
+                left,
+                left + DECORATION_THICKNESS,
+                BACK_BOTTOM_LEFT_Y + y,
+                decoration_z,
+                buffers,
+            );
+        }
+        if self.strikethrough {
+            self.tessellate_decoration(
+                left + (1.0 - DECORATION_THICKNESS) / 2.0,
+                left + (1.0 + DECORATION_THICKNESS) / 2.0,
+                BACK_BOTTOM_LEFT_Y + y,
+                decoration_z,
+                buffers,
+            );
+        }
+    }
+    /// Emits a flat `color` quad from (`x0`, `y0`) to (`x1`, `y1`), pushed back
+    /// so the glyphs in front of it draw over it
+    fn tessellate_background(
+        (x0, x1): (f32, f32),
+        (y0, y1): (f32, f32),
+        color: [f32; 4],
+        buffers: &mut TextBuffers,
+    ) {
+        let z = BACK_BOTTOM_LEFT_Z - 1.0 - DECORATION_OFFSET;
+        let vertex_count = buffers.vertices.len() as u32;
+        buffers.vertices.push([x0, y0, z]);
+        buffers.vertices.push([x1, y0, z]);
+        buffers.vertices.push([x1, y1, z]);
+        buffers.vertices.push([x0, y1, z]);
+        buffers
+            .indices
+            .extend([0, 1, 2, 2, 3, 0].map(|i| vertex_count + i));
+        for _ in 0..4 {
+            buffers.colors.push(color);
+            buffers.materials.push([-1, -1]);
+            buffers.lights.push(color);
+            buffers.uvs.push([0.0, 0.0]);
+        }
     }
     fn tessellate_glyphs(
         glyphs: Array2<RenderableGlyph>,
-        origin: &MeshOrigin,
+        options: &TextOptions,
     ) -> MeshEnvelope {
-        let mut vertices = Vec::new();
-        let mut indices = Vec::new();
-        let mut material_ids = Vec::new();
-        let mut colors = Vec::new();
-        let mut light = Vec::new();
-        let mut uvs = Vec::new();
+        let mut buffers = TextBuffers::default();
 
-        let chars_per_line = glyphs.shape()[1] as f32;
-        let lines = glyphs.shape()[0] as f32;
+        let line_step = options.line_spacing;
+        let char_step = options.char_spacing;
+        // Extent of the glyph cells, the last cell is one glyph wide no
+        // matter the spacing
+        let width = ((glyphs.shape()[0] as f32 - 1.0) * line_step + 1.0).max(0.0);
+        let height = ((glyphs.shape()[1] as f32 - 1.0) * char_step + 1.0).max(0.0);
 
-        let (ox, oy) = match origin {
-            MeshOrigin::TR => (0.0, 0.0),
-            MeshOrigin::BL => (lines - 1.0, chars_per_line - 1.0),
-            MeshOrigin::BC => (lines / 2.0, chars_per_line / 2.0),
-            MeshOrigin::BR => (lines, 0.0),
-            MeshOrigin::TL => (0.0, chars_per_line),
-            MeshOrigin::TC => (lines / 2.0, chars_per_line / 2.0),
-            MeshOrigin::CC => (lines / 2.0, chars_per_line / 2.0),
-        };
+        // The first glyph's cell ends at (ox, oy) and the rest extend towards
+        // negative x and y, so shifting by the anchor puts the origin in place
+        let (anchor_x, anchor_y) = options.origin.anchor();
+        let (ox, oy) = (width * anchor_x, height * anchor_y);
+
+        // Glyph cells cover ox - width..ox and oy - height..oy, the band goes
+        // first so it's under every glyph
+        if let Some(color) = options.background {
+            Self::tessellate_background(
+                (ox - width, ox),
+                (oy - height, oy),
+                color,
+                &mut buffers,
+            );
+        }
 
         for char in 0..glyphs.shape()[1] {
             for line in 0..glyphs.shape()[0] {
                 let glyph = &glyphs[[line, char]];
                 glyph.tessellate_glyph(
-                    -(line as f32) + ox - 1.0,
-                    -(char as f32) + oy - 1.0,
+                    -(line as f32) * line_step + ox - 1.0,
+                    -(char as f32) * char_step + oy - 1.0,
                     0.0,
-                    &mut vertices,
-                    &mut indices,
-                    &mut colors,
-                    &mut material_ids,
-                    &mut light,
-                    &mut uvs,
+                    &mut buffers,
                 );
             }
         }
 
-        MeshEnvelope::new(MeshParams {
-            vertices,
-            indices: Some(indices),
-            uvs: Some(uvs),
-            material_ids: Some(material_ids),
-            colors: Some(colors),
-            light: Some(light),
-        })
+        buffers.into_mesh()
     }
 }
 
@@ -349,6 +481,7 @@ impl Word {
 enum TextPiece {
     Word(Word),
     LineBreak,
+    AlignmentChange(Alignment),
 }
 
 #[derive(Debug)]
@@ -370,7 +503,7 @@ impl TypesettingElement {
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Alignment {
     Top, // Would be left in a left-to-right language but here it's top
     Center,
@@ -383,22 +516,21 @@ struct TypesettedLine {
 }
 
 impl TypesettedLine {
+    /// Typesets the next line of `text`. Alignment changes update
+    /// `alignment` for the lines that follow, and also for this one if no
+    /// word has been placed on it yet. `line_index` seeds the spacing of
+    /// justified lines.
     fn from_text(
         mut text: Text,
-        alignment: Alignment,
+        alignment: &mut Alignment,
         max_width: usize,
+        continuation_marker: bool,
+        line_index: usize,
     ) -> (Option<Self>, Text) {
         if text.words.is_empty() {
             return (None, text);
         }
-        if text.words.len() == 1 {
-            if let TextPiece::Word(word) = &text.words[0] {
-                if word.len() > max_width {
-                    // If the word is longer than max width, we can't typeset it
-                    return (None, Text { words: Vec::new() });
-                }
-            }
-        }
+        let mut line_alignment = *alignment;
         let mut elements = Vec::new();
         let mut current_line_width: usize = 0;
         // General algorithm is, we start by assuming left alignment,
@@ -407,8 +539,37 @@ impl TypesettedLine {
         // so for now we grab words until we fill max_width
         while current_line_width < max_width && !text.words.is_empty() {
             let with_space = match text.words.remove(0) {
-                TextPiece::Word(word) => {
+                TextPiece::Word(mut word) => {
                     let word_width = word.len();
+                    let available = max_width - current_line_width;
+                    if word_width > max_width && available > 0 {
+                        // The word would never fit on a line, so we break it
+                        // at the end of this one and carry the rest over
+                        let with_marker = continuation_marker && available > 1;
+                        let split_at = if with_marker {
+                            available - 1
+                        } else {
+                            available
+                        };
+                        let rest = word.syllables.split_off(split_at);
+                        if with_marker {
+                            let last = *word.syllables.last().unwrap();
+                            word.syllables.push(RenderableGlyph {
+                                glyph: Glyph::Ellipsis,
+                                ..last
+                            });
+                        }
+                        text.words.insert(
+                            0,
+                            TextPiece::Word(Word {
+                                syllables: rest,
+                                with_space: word.with_space,
+                            }),
+                        );
+                        elements.push(TypesettingElement::WordElement(word));
+                        current_line_width = max_width;
+                        break;
+                    }
                     if current_line_width + word_width > max_width {
                         // If adding this word exceeds max width, we stop here
                         text.words.insert(0, TextPiece::Word(word));
@@ -423,6 +584,13 @@ impl TypesettedLine {
                     // If we encounter a line break, we stop the current line
                     break;
                 }
+                TextPiece::AlignmentChange(new_alignment) => {
+                    *alignment = new_alignment;
+                    if elements.is_empty() {
+                        line_alignment = new_alignment;
+                    }
+                    continue;
+                }
             };
             // If we still have text, add a space
             if !text.words.is_empty() && current_line_width < max_width && with_space {
@@ -438,7 +606,7 @@ impl TypesettedLine {
         }
 
         // Now we have the content of the line, we need to handle alignment
-        match alignment {
+        match line_alignment {
             Alignment::Top => {
                 while current_line_width < max_width {
                     elements.push(TypesettingElement::SpaceElement);
@@ -465,46 +633,98 @@ impl TypesettedLine {
             }
             Alignment::Justify => {
                 let spaces_needed = max_width - current_line_width;
-                // Convert random spaces into double spaces until we fill the line
-                for _ in 0..spaces_needed {
-                    // Find a space
-                    let space_positions = elements
-                        .iter()
-                        .enumerate()
-                        .filter_map(|(i, el)| {
-                            if matches!(el, TypesettingElement::SpaceElement) {
-                                Some(i)
-                            } else {
-                                None
-                            }
-                        })
-                        .collect::<Vec<_>>();
-                    if space_positions.is_empty() {
-                        break; // Can't double any spaces
+                let space_positions = elements
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, el)| {
+                        matches!(el, TypesettingElement::SpaceElement).then_some(i)
+                    })
+                    .collect::<Vec<_>>();
+                if space_positions.is_empty() {
+                    // Nothing to stretch, so pad it like a top aligned line
+                    for _ in 0..spaces_needed {
+                        elements.push(TypesettingElement::SpaceElement);
+                    }
+                } else {
+                    // Every space grows by the same amount, and the remainder
+                    // goes to distinct spaces picked by a generator seeded from
+                    // the line, so the same text always justifies the same way
+                    let mut extra = vec![
+                        spaces_needed / space_positions.len();
+                        space_positions.len()
+                    ];
+                    let mut rng = StdRng::seed_from_u64(Self::justify_seed(
+                        line_index, &elements,
+                    ));
+                    let remainder = spaces_needed % space_positions.len();
+                    for picked in
+                        index::sample(&mut rng, space_positions.len(), remainder)
+                    {
+                        extra[picked] += 1;
+                    }
+                    // Back to front so the positions still to come stay valid
+                    for (&space_pos, &count) in space_positions.iter().zip(&extra).rev()
+                    {
+                        for _ in 0..count {
+                            elements.insert(
+                                space_pos + 1,
+                                TypesettingElement::SpaceElement,
+                            );
+                        }
                     }
-                    // use line lenght as seed
-                    let mut rng = StdRng::seed_from_u64(current_line_width as u64);
-                    // Pick a random space position
-                    let random_index = rng.random_range(0..space_positions.len());
-                    let space_pos = space_positions[random_index];
-                    // Double the space
-                    elements.insert(space_pos + 1, TypesettingElement::SpaceElement);
-                    // Update current line width
-                    current_line_width += 1;
                 }
             }
         }
 
         (Some(TypesettedLine { elements }), text)
     }
+    // Hashes the line's index and glyphs, so justified spacing only changes
+    // when the text does
+    fn justify_seed(line_index: usize, elements: &[TypesettingElement]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        line_index.hash(&mut hasher);
+        for element in elements {
+            match element {
+                TypesettingElement::WordElement(word) => {
+                    for syllable in &word.syllables {
+                        syllable.glyph.material_id().hash(&mut hasher);
+                    }
+                }
+                TypesettingElement::SpaceElement => {
+                    Glyph::Blank.material_id().hash(&mut hasher)
+                }
+            }
+        }
+        hasher.finish()
+    }
+    fn width(&self) -> usize {
+        self.elements.iter().map(|el| el.get_width()).sum()
+    }
     fn into_glyphs(self) -> Vec<RenderableGlyph> {
-        self.elements
+        let mut glyphs: Vec<RenderableGlyph> = self
+            .elements
             .into_iter()
             .flat_map(|el| match el {
                 TypesettingElement::WordElement(word) => word.syllables,
                 TypesettingElement::SpaceElement => vec![RenderableGlyph::space()],
             })
-            .collect()
+            .collect();
+
+        // Decorations continue across spaces between two decorated glyphs
+        let is_space = |glyph: &&RenderableGlyph| matches!(glyph.glyph, Glyph::Blank);
+        for i in 0..glyphs.len() {
+            if !matches!(glyphs[i].glyph, Glyph::Blank) {
+                continue;
+            }
+            let previous = glyphs[..i].iter().rev().find(|g| !is_space(g)).copied();
+            let next = glyphs[i + 1..].iter().find(|g| !is_space(g)).copied();
+            if let (Some(previous), Some(next)) = (previous, next) {
+                glyphs[i].underline = previous.underline && next.underline;
+                glyphs[i].strikethrough = previous.strikethrough && next.strikethrough;
+                glyphs[i].decoration_color = previous.decoration_color;
+            }
+        }
+        glyphs
     }
 }
 
@@ -553,6 +773,8 @@ impl Text {
     pub fn from_spec(spec: &str) -> Result<Self, Box<dyn Error>> {
         let mut current_foreground = [1.0, 1.0, 1.0, 1.0];
         let mut current_background = [0.3, 0.3, 0.3, 1.0];
+        let mut current_underline = false;
+        let mut current_strikethrough = false;
         let lines = spec.lines().collect::<Vec<_>>();
         let mut pieces = Vec::new();
         for (i, line) in lines.iter().enumerate() {
@@ -574,10 +796,25 @@ impl Text {
                     let color = parse_color(color_str)?;
                     current_background = color;
                     continue;
+                } else if word.starts_with("a:") || word.starts_with("A:") {
+                    // Change alignment of the following lines
+                    let alignment = parse_alignment(&word["a:".len()..])?;
+                    pieces.push(TextPiece::AlignmentChange(alignment));
+                    continue;
+                } else if word.starts_with("u:") || word.starts_with("U:") {
+                    // Toggle underline
+                    current_underline = parse_toggle(&word["u:".len()..])?;
+                    continue;
+                } else if word.starts_with("s:") || word.starts_with("S:") {
+                    // Toggle strikethrough
+                    current_strikethrough = parse_toggle(&word["s:".len()..])?;
+                    continue;
                 } else if word.starts_with("reset") || word.starts_with("RESET") {
-                    // Reset colors to default
+                    // Reset colors and decorations to default
                     current_foreground = [1.0, 1.0, 1.0, 1.0];
                     current_background = [0.3, 0.3, 0.3, 1.0];
+                    current_underline = false;
+                    current_strikethrough = false;
                     continue;
                 }
                 let word_glyphs = Glyph::parse_latin(word)?;
@@ -587,6 +824,9 @@ impl Text {
                         glyph,
                         background_color: current_background,
                         foreground_color: current_foreground,
+                        underline: current_underline,
+                        strikethrough: current_strikethrough,
+                        decoration_color: current_foreground,
                     });
                 }
                 if syllables.is_empty() {
@@ -622,15 +862,63 @@ impl Text {
     }
 }
 
+fn parse_toggle(toggle_str: &str) -> Result<bool, Box<dyn Error>> {
+    match toggle_str.to_lowercase().as_str() {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        _ => Err(format!("Expected on or off, got: {}", toggle_str).into()),
+    }
+}
+
+fn parse_alignment(alignment_str: &str) -> Result<Alignment, Box<dyn Error>> {
+    match alignment_str.to_lowercase().as_str() {
+        "top" => Ok(Alignment::Top),
+        "center" => Ok(Alignment::Center),
+        "bottom" => Ok(Alignment::Bottom),
+        "justify" => Ok(Alignment::Justify),
+        _ => Err(format!("Unknown alignment: {}", alignment_str).into()),
+    }
+}
+
+fn named_color(name: &str) -> Option<[f32; 4]> {
+    let color = match name.to_lowercase().as_str() {
+        "white" => [1.0, 1.0, 1.0, 1.0],
+        "black" => [0.0, 0.0, 0.0, 1.0],
+        "gray" | "grey" => [0.5, 0.5, 0.5, 1.0],
+        "red" => [1.0, 0.0, 0.0, 1.0],
+        "green" => [0.0, 1.0, 0.0, 1.0],
+        "blue" => [0.0, 0.0, 1.0, 1.0],
+        "yellow" => [1.0, 1.0, 0.0, 1.0],
+        "cyan" => [0.0, 1.0, 1.0, 1.0],
+        "magenta" => [1.0, 0.0, 1.0, 1.0],
+        "orange" => [1.0, 0.5, 0.0, 1.0],
+        "transparent" => [0.0, 0.0, 0.0, 0.0],
+        _ => return None,
+    };
+    Some(color)
+}
+
 fn parse_color(color_str: &str) -> Result<[f32; 4], Box<dyn Error>> {
     // Parse as html
     if color_str.starts_with('#') {
         let hex = &color_str[1..];
-        if hex.len() == 8 {
+        // Expand the short #rgb form to #rrggbb
+        let hex = if hex.len() == 3 {
+            hex.chars().flat_map(|c| [c, c]).collect::<String>()
+        } else {
+            hex.to_string()
+        };
+        // from_str_radix alone would also take a sign in front of a digit
+        let is_hex = hex.chars().all(|c| c.is_ascii_hexdigit());
+        if is_hex && (hex.len() == 6 || hex.len() == 8) {
             let r = u8::from_str_radix(&hex[0..2], 16)?;
             let g = u8::from_str_radix(&hex[2..4], 16)?;
             let b = u8::from_str_radix(&hex[4..6], 16)?;
-            let a = u8::from_str_radix(&hex[6..8], 16)?;
+            let a = if hex.len() == 8 {
+                u8::from_str_radix(&hex[6..8], 16)?
+            } else {
+                255
+            };
             Ok([
                 r as f32 / 255.0,
                 g as f32 / 255.0,
@@ -638,10 +926,12 @@ fn parse_color(color_str: &str) -> Result<[f32; 4], Box<dyn Error>> {
                 a as f32 / 255.0,
             ])
         } else {
-            Err("Invalid hex color format".into())
+            Err(format!("Invalid hex color format: {}", color_str).into())
         }
+    } else if let Some(color) = named_color(color_str) {
+        Ok(color)
     } else {
-        Err("Only hex color format is supported".into())
+        Err(format!("Unknown color: {}", color_str).into())
     }
 }
 
@@ -653,6 +943,14 @@ pub struct TextOptions {
     pub alignment: Alignment,
     pub origin: MeshOrigin,
     pub max_width: usize,
+    /// End words that are broken across lines with an ellipsis glyph
+    pub continuation_marker: bool,
+    /// Color of a band drawn behind the whole text, like a highlight
+    pub background: Option<[f32; 4]>,
+    /// Distance from one line to the next in glyphs, 1 packs them edge to edge
+    pub line_spacing: f32,
+    /// Distance from one glyph to the next along a line, in glyphs
+    pub char_spacing: f32,
 }
 
 impl TextOptions {
@@ -661,6 +959,10 @@ impl TextOptions {
             alignment: Alignment::Top,
             origin: MeshOrigin::TR,
             max_width,
+            continuation_marker: false,
+            background: None,
+            line_spacing: 1.0,
+            char_spacing: 1.0,
         }
     }
     pub fn set_alignment(mut self, alignment: Alignment) -> Self {
@@ -675,14 +977,35 @@ impl TextOptions {
         self.origin = origin;
         self
     }
-    pub fn render_spec(&self, spec: &str) -> Result<RenderableText, Box<dyn Error>> {
+    pub fn set_continuation_marker(mut self, continuation_marker: bool) -> Self {
+        self.continuation_marker = continuation_marker;
+        self
+    }
+    pub fn set_background(mut self, color: [f32; 4]) -> Self {
+        self.background = Some(color);
+        self
+    }
+    pub fn set_line_spacing(mut self, line_spacing: f32) -> Self {
+        self.line_spacing = line_spacing;
+        self
+    }
+    pub fn set_char_spacing(mut self, char_spacing: f32) -> Self {
+        self.char_spacing = char_spacing;
+        self
+    }
+    fn typeset(&self, spec: &str) -> Result<Vec<TypesettedLine>, Box<dyn Error>> {
         let text = Text::from_spec(spec)?;
         let mut remaining_text = text;
         let mut lines = Vec::new();
+        let mut alignment = self.alignment;
 
-        while let (Some(line), rest) =
-            TypesettedLine::from_text(remaining_text, self.alignment, self.max_width)
-        {
+        while let (Some(line), rest) = TypesettedLine::from_text(
+            remaining_text,
+            &mut alignment,
+            self.max_width,
+            self.continuation_marker,
+            lines.len(),
+        ) {
             remaining_text = rest;
             lines.push(line);
         }
@@ -690,7 +1013,18 @@ impl TextOptions {
         if lines.is_empty() {
             return Err("No valid lines to render".into());
         }
-
+        Ok(lines)
+    }
+    /// Size of the laid out text as (columns, lines) in glyphs, without
+    /// building a mesh. Includes alignment padding, so it matches the
+    /// extent of the mesh `render_spec` would produce.
+    pub fn measure(&self, spec: &str) -> Result<(usize, usize), Box<dyn Error>> {
+        let lines = self.typeset(spec)?;
+        let columns = lines.iter().map(|line| line.width()).max().unwrap_or(0);
+        Ok((columns, lines.len()))
+    }
+    pub fn render_spec(&self, spec: &str) -> Result<RenderableText, Box<dyn Error>> {
+        let lines = self.typeset(spec)?;
         let num_lines = lines.len();
 
         // reverse lines because we render from bottom to top
@@ -704,7 +1038,7 @@ impl TextOptions {
             Array2::from_shape_vec((num_lines, self.max_width), glyphs)
                 .map_err(|e| format!("Failed to create glyph array: {}", e))?;
 
-        let mesh = RenderableGlyph::tessellate_glyphs(glyph_array, &self.origin);
+        let mesh = RenderableGlyph::tessellate_glyphs(glyph_array, self);
         Ok(RenderableText { mesh })
     }
 }
@@ -715,6 +1049,62 @@ impl RenderableText {
     }
 }
 
+// Colors and spacings are keyed by their bits, since floats aren't hashable
+type TextCacheKey = (
+    String,
+    Alignment,
+    MeshOrigin,
+    usize,
+    bool,
+    Option<[u32; 4]>,
+    [u32; 2],
+);
+
+/// Memoizes rendered text by spec and layout options, so labels that are
+/// drawn every frame only get typeset and meshed once
+pub struct TextCache {
+    entries: HashMap<TextCacheKey, RenderableText>,
+}
+
+impl Default for TextCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TextCache {
+    pub fn new() -> Self {
+        TextCache {
+            entries: HashMap::new(),
+        }
+    }
+    pub fn render_spec(
+        &mut self,
+        options: &TextOptions,
+        spec: &str,
+    ) -> Result<&mut RenderableText, Box<dyn Error>> {
+        let key = (
+            spec.to_string(),
+            options.alignment,
+            options.origin,
+            options.max_width,
+            options.continuation_marker,
+            options.background.map(|color| color.map(f32::to_bits)),
+            [options.line_spacing, options.char_spacing].map(f32::to_bits),
+        );
+        match self.entries.entry(key) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => Ok(entry.insert(options.render_spec(spec)?)),
+        }
+    }
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// Turns plain latin text into a spec, uppercasing letters so they're read
+/// as syllables and doubling spaces so they show. Digits, signs and
+/// punctuation have no case and pass through as they are.
 pub fn into_syllabic(text: &str) -> String {
     // All lowercase to uppercase
     let text = text.to_uppercase();
@@ -722,3 +1112,167 @@ pub fn into_syllabic(text: &str) -> String {
     let text = text.replace(' ', "  ");
     return text;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Bounding box of the mesh as (min x, min y, max x, max y)
+    fn bounds(mesh: &MeshEnvelope) -> (f32, f32, f32, f32) {
+        let MeshEnvelope::Parameters(params) = mesh else {
+            panic!("Text meshes start out as parameters");
+        };
+        params.vertices.iter().fold(
+            (f32::MAX, f32::MAX, f32::MIN, f32::MIN),
+            |(min_x, min_y, max_x, max_y), vertex| {
+                (
+                    min_x.min(vertex[0]),
+                    min_y.min(vertex[1]),
+                    max_x.max(vertex[0]),
+                    max_y.max(vertex[1]),
+                )
+            },
+        )
+    }
+
+    #[test]
+    fn origins_place_the_bounding_box() {
+        // 2 lines of 4 glyphs, 2 wide and 4 tall on screen
+        let cases = [
+            (MeshOrigin::TR, (-2.0, -4.0, 0.0, 0.0)),
+            (MeshOrigin::TC, (-1.0, -4.0, 1.0, 0.0)),
+            (MeshOrigin::TL, (0.0, -4.0, 2.0, 0.0)),
+            (MeshOrigin::BR, (-2.0, 0.0, 0.0, 4.0)),
+            (MeshOrigin::BC, (-1.0, 0.0, 1.0, 4.0)),
+            (MeshOrigin::BL, (0.0, 0.0, 2.0, 4.0)),
+            (MeshOrigin::CC, (-1.0, -2.0, 1.0, 2.0)),
+        ];
+        for (origin, expected) in cases {
+            let glyphs = Array2::from_elem((2, 4), RenderableGlyph::space());
+            let options = TextOptions::new(4).set_origin(origin);
+            let mesh = RenderableGlyph::tessellate_glyphs(glyphs, &options);
+            assert_eq!(bounds(&mesh), expected, "{:?}", origin);
+        }
+    }
+
+    #[test]
+    fn spacing_spreads_the_glyphs() {
+        let glyphs = Array2::from_elem((2, 4), RenderableGlyph::space());
+        let options = TextOptions::new(4)
+            .set_origin(MeshOrigin::BL)
+            .set_line_spacing(2.0)
+            .set_char_spacing(1.5);
+        let mesh = RenderableGlyph::tessellate_glyphs(glyphs, &options);
+        assert_eq!(bounds(&mesh), (0.0, 0.0, 3.0, 5.5));
+    }
+
+    // Spaces before the first word of a typeset line
+    fn leading_spaces(line: &TypesettedLine) -> usize {
+        line.elements
+            .iter()
+            .take_while(|element| matches!(element, TypesettingElement::SpaceElement))
+            .count()
+    }
+
+    #[test]
+    fn alignment_directives_apply_to_the_lines_after_them() {
+        let options = TextOptions::new(5);
+        let lines = options.typeset("A\na:bottom  E\nI  a:center\nO").unwrap();
+        let padding = lines.iter().map(leading_spaces).collect::<Vec<_>>();
+        // The directive at the start of a line aligns that line too, one
+        // after a word only takes effect on the next line
+        assert_eq!(padding, [0, 4, 4, 2]);
+    }
+
+    #[test]
+    fn unknown_alignments_are_rejected() {
+        assert!(Text::from_spec("a:left  A").is_err());
+        assert!(Text::from_spec("a:  A").is_err());
+    }
+
+    fn vertex_count(text: &RenderableText) -> usize {
+        let MeshEnvelope::Parameters(params) = &text.mesh else {
+            panic!("Text meshes start out as parameters");
+        };
+        params.vertices.len()
+    }
+
+    #[test]
+    fn decorations_add_a_quad_per_glyph() {
+        let options = TextOptions::new(3);
+        // A, a space and E, with one quad each
+        let plain = options.render_spec("A  E").unwrap();
+        assert_eq!(vertex_count(&plain), 3 * 4);
+        let underlined = options.render_spec("u:on  A  E").unwrap();
+        assert_eq!(vertex_count(&underlined), 2 * 3 * 4);
+        let both = options.render_spec("u:on  s:on  A  E").unwrap();
+        assert_eq!(vertex_count(&both), 3 * 3 * 4);
+    }
+
+    #[test]
+    fn decorations_continue_across_spaces_between_decorated_glyphs() {
+        let options = TextOptions::new(6);
+        let lines = options.typeset("u:on  A  E  u:off  I").unwrap();
+        let glyphs = lines.into_iter().next().unwrap().into_glyphs();
+        let underlined = glyphs
+            .iter()
+            .map(|glyph| glyph.underline)
+            .collect::<Vec<_>>();
+        // The space after E and the alignment padding border an undecorated
+        // glyph or nothing
+        assert_eq!(underlined, [true, true, true, false, false, false]);
+    }
+
+    #[test]
+    fn decoration_toggles_are_on_or_off() {
+        assert!(Text::from_spec("u:on  s:OFF  A").is_ok());
+        assert!(Text::from_spec("u:yes  A").is_err());
+        assert!(Text::from_spec("s:  A").is_err());
+    }
+
+    #[test]
+    fn hex_colors_of_every_length() {
+        let cases = [
+            ("#f80", [1.0, 0x88 as f32 / 255.0, 0.0, 1.0]),
+            ("#FF8800", [1.0, 0x88 as f32 / 255.0, 0.0, 1.0]),
+            (
+                "#ff880080",
+                [1.0, 0x88 as f32 / 255.0, 0.0, 0x80 as f32 / 255.0],
+            ),
+            ("#00000000", [0.0, 0.0, 0.0, 0.0]),
+        ];
+        for (color_str, expected) in cases {
+            assert_eq!(parse_color(color_str).unwrap(), expected, "{}", color_str);
+        }
+    }
+
+    #[test]
+    fn named_colors() {
+        assert_eq!(parse_color("red").unwrap(), [1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(parse_color("Orange").unwrap(), [1.0, 0.5, 0.0, 1.0]);
+        assert_eq!(parse_color("grey").unwrap(), parse_color("gray").unwrap());
+        assert_eq!(parse_color("transparent").unwrap(), [0.0; 4]);
+    }
+
+    #[test]
+    fn malformed_colors_are_rejected() {
+        let cases = [
+            "#",
+            "#f",
+            "#ff",
+            "#ffff",
+            "#fffff",
+            "#fffffff",
+            "#fffffffff",
+            "#ggg",
+            "#12345z",
+            "#+f+f+f",
+            "#ffé",
+            "purple",
+            "",
+        ];
+        for color_str in cases {
+            assert!(parse_color(color_str).is_err(), "{:?}", color_str);
+        }
+    }
+}
@@ -1,4 +1,6 @@
-use std::collections::HashMap;
+use std::{
+    cell::RefCell, collections::HashMap, ffi::CStr, fs, path::Path, sync::OnceLock,
+};
 
 use gl33::*;
 use imagine::{Bitmap, png::png_try_bitmap_rgba};
@@ -6,6 +8,51 @@ use pixel_formats::r8g8b8a8_Srgb;
 
 use crate::shader::Shader;
 
+// Directory a texture pack is read from, any texture missing there falls back
+// to the one built into the game
+const TEXTURE_PACK_DIR: &str = "textures";
+
+// Highest anisotropy the driver supports, None without the extension. Asked
+// once, the game only ever has one GL context.
+static MAX_ANISOTROPY: OnceLock<Option<f32>> = OnceLock::new();
+
+fn max_anisotropy(gl: &GlFns) -> Option<f32> {
+    *MAX_ANISOTROPY.get_or_init(|| unsafe {
+        let mut extension_count = 0;
+        gl.GetIntegerv(GL_NUM_EXTENSIONS, &mut extension_count);
+        let supported = (0..extension_count.max(0) as u32).any(|index| {
+            let name = gl.GetStringi(GL_EXTENSIONS, index);
+            !name.is_null()
+                && matches!(
+                    CStr::from_ptr(name.cast()).to_bytes(),
+                    b"GL_EXT_texture_filter_anisotropic"
+                        | b"GL_ARB_texture_filter_anisotropic"
+                )
+        });
+        supported.then(|| {
+            let mut max = 1.0;
+            gl.GetFloatv(GL_MAX_TEXTURE_MAX_ANISOTROPY, &mut max);
+            max
+        })
+    })
+}
+
+// Decodes a PNG into its width, height and RGBA bytes
+fn decode_png(bytes: &[u8]) -> Result<(i32, i32, Vec<u8>), String> {
+    let bitmap: Bitmap<r8g8b8a8_Srgb> =
+        png_try_bitmap_rgba(bytes, true).map_err(|e| format!("{:?}", e))?;
+
+    let mut output_data =
+        Vec::with_capacity(bitmap.width as usize * bitmap.height as usize * 4);
+    for pixel in bitmap.pixels {
+        output_data.push(pixel.r);
+        output_data.push(pixel.g);
+        output_data.push(pixel.b);
+        output_data.push(pixel.a);
+    }
+    Ok((bitmap.width as i32, bitmap.height as i32, output_data))
+}
+
 pub struct Texture {
     pub id: u32,
     pub texture_type: GLenum,
@@ -23,12 +70,17 @@ impl Texture {
         }
     }
 
+    /// Upload RGBA data. With `max_mip_level` set, mipmaps are generated up to
+    /// that level and minification blends between them; magnification stays
+    /// nearest for the pixel-art look. For atlases the level should not exceed
+    /// log2 of the tile size, otherwise a mip texel covers several tiles.
     pub fn from_data(
         gl: &GlFns,
         width: i32,
         height: i32,
         data: &[u8],
         format: GLenum,
+        max_mip_level: Option<i32>,
     ) -> Self {
         let texture = Self::new(gl);
         texture.bind(gl);
@@ -45,9 +97,8 @@ impl Texture {
                 GL_UNSIGNED_BYTE,
                 data.as_ptr().cast(),
             );
-            // Remove gl.GenerateMipmap(GL_TEXTURE_2D); - don't need mipmaps for pixel art
-
-            // Set texture parameters for pixel art
+            // Set texture parameters for pixel art. Atlas tiles are wrapped in
+            // the shader, so the wrap mode only matters at the texture border.
             gl.TexParameteri(
                 GL_TEXTURE_2D,
                 GL_TEXTURE_WRAP_S,
@@ -58,7 +109,24 @@ impl Texture {
                 GL_TEXTURE_WRAP_T,
                 GL_CLAMP_TO_EDGE.0 as i32,
             );
-            gl.TexParameteri(GL_TEXTURE_2D, GL_TEXTURE_MIN_FILTER, GL_NEAREST.0 as i32);
+            match max_mip_level {
+                Some(level) => {
+                    gl.TexParameteri(GL_TEXTURE_2D, GL_TEXTURE_MAX_LEVEL, level);
+                    gl.GenerateMipmap(GL_TEXTURE_2D);
+                    gl.TexParameteri(
+                        GL_TEXTURE_2D,
+                        GL_TEXTURE_MIN_FILTER,
+                        GL_NEAREST_MIPMAP_LINEAR.0 as i32,
+                    );
+                }
+                None => {
+                    gl.TexParameteri(
+                        GL_TEXTURE_2D,
+                        GL_TEXTURE_MIN_FILTER,
+                        GL_NEAREST.0 as i32,
+                    );
+                }
+            }
             gl.TexParameteri(GL_TEXTURE_2D, GL_TEXTURE_MAG_FILTER, GL_NEAREST.0 as i32);
         }
 
@@ -67,7 +135,7 @@ impl Texture {
 
     pub fn create_solid_color(gl: &GlFns, r: u8, g: u8, b: u8, a: u8) -> Self {
         let data = [r, g, b, a];
-        Self::from_data(gl, 1, 1, &data, GL_RGBA)
+        Self::from_data(gl, 1, 1, &data, GL_RGBA, None)
     }
 
     pub fn bind(&self, gl: &GlFns) {
@@ -88,37 +156,175 @@ impl Texture {
             gl.BindTexture(self.texture_type, 0);
         }
     }
+
+    /// Keeps the texture sharp at grazing angles with as much anisotropic
+    /// filtering as the driver allows. Does nothing when the extension is
+    /// missing, and only helps mipmapped textures.
+    pub fn enable_anisotropic_filtering(&self, gl: &GlFns) {
+        let Some(max) = max_anisotropy(gl) else {
+            return;
+        };
+        self.bind(gl);
+        unsafe {
+            gl.TexParameterf(self.texture_type, GL_TEXTURE_MAX_ANISOTROPY, max);
+        }
+    }
+
+    /// Frees the GL texture. Dropping a Texture can't do it without the GL
+    /// context, so textures that aren't deleted leak until the context goes.
+    pub fn delete(self, gl: &GlFns) {
+        unsafe {
+            gl.DeleteTextures(1, &self.id);
+        }
+    }
+}
+
+/// Describes a texture laid out as a uniform grid of tiles. Tiles are
+/// addressed by column and row, or by a region name registered on the atlas.
+pub struct TextureAtlas {
+    pub texture_name: String,
+    pub cols: u32,
+    pub rows: u32,
+    regions: HashMap<String, [i32; 2]>,
 }
 
-impl Drop for Texture {
-    fn drop(&mut self) {
-        // Note: This requires a GL context to be current
-        // In a real game, you'd want proper resource management
+impl TextureAtlas {
+    pub fn from_grid(texture_name: &str, cols: u32, rows: u32) -> Self {
+        TextureAtlas {
+            texture_name: texture_name.to_string(),
+            cols,
+            rows,
+            regions: HashMap::new(),
+        }
+    }
+
+    /// Name the tile at (col, row)
+    pub fn with_region(mut self, name: &str, col: u32, row: u32) -> Self {
+        if col >= self.cols || row >= self.rows {
+            panic!(
+                "Region '{}' at ({}, {}) is outside the {}x{} atlas '{}'",
+                name, col, row, self.cols, self.rows, self.texture_name
+            );
+        }
+        self.regions
+            .insert(name.to_string(), [col as i32, row as i32]);
+        self
+    }
+
+    /// Grid coordinates of a named region, as used for material ids
+    pub fn region(&self, name: &str) -> [i32; 2] {
+        match self.regions.get(name) {
+            Some(&region) => region,
+            None => panic!(
+                "Region '{}' is not defined in atlas '{}'",
+                name, self.texture_name
+            ),
+        }
+    }
+
+    /// Normalized texture rectangle of a tile as [u_min, v_min, u_max, v_max]
+    pub fn uv_rect(&self, col: u32, row: u32) -> [f32; 4] {
+        let width = 1.0 / self.cols as f32;
+        let height = 1.0 / self.rows as f32;
+        [
+            col as f32 * width,
+            row as f32 * height,
+            (col + 1) as f32 * width,
+            (row + 1) as f32 * height,
+        ]
+    }
+
+    /// Maps a uv inside a tile onto the atlas, with 0 and 1 landing on the
+    /// edges of the tile's uv_rect. Uvs past 1, from faces merged across
+    /// several blocks, run on into the next tiles and the shader wraps them
+    /// back into the rect.
+    pub fn atlas_uv(&self, [col, row]: [i32; 2], [u, v]: [f32; 2]) -> [f32; 2] {
+        let [u_min, v_min, u_max, v_max] = self.uv_rect(col as u32, row as u32);
+        [u_min + u * (u_max - u_min), v_min + v * (v_max - v_min)]
+    }
+
+    /// Upload the grid size so the shader can map material ids to sub-rects
+    pub fn set_grid_uniform(&self, gl: &GlFns, shader: &Shader, uniform_name: &str) {
+        shader.set_vec2(gl, uniform_name, &[self.cols as f32, self.rows as f32]);
+    }
+}
+
+/// Most animated regions a texture can pass to a shader at once
+pub const MAX_ANIMATIONS: usize = 8;
+
+/// A region whose frames are stacked below it as a vertical strip
+struct TextureAnimation {
+    region: [i32; 2],
+    frames: u32,
+    frame_time: f32,
+    elapsed: f32,
+}
+
+impl TextureAnimation {
+    fn current_frame(&self) -> u32 {
+        (self.elapsed / self.frame_time) as u32 % self.frames
     }
 }
 
 pub struct TextureManager {
     textures: HashMap<String, Texture>,
+    // Texture names in the order they were first loaded, indices point here
+    names: Vec<String>,
+    animations: HashMap<String, Vec<TextureAnimation>>,
+    // Sampler, animations and animation count uniform locations by shader
+    // program and sampler name, so the derived names are only built once
+    uniform_locations: RefCell<HashMap<u32, HashMap<String, [i32; 3]>>>,
 }
 
 impl TextureManager {
     pub fn new(gl: &GlFns) -> Self {
         let mut manager = TextureManager {
             textures: HashMap::new(),
+            names: Vec::new(),
+            animations: HashMap::new(),
+            uniform_locations: RefCell::new(HashMap::new()),
         };
-        manager.load_png_texture(
+        // terrain.png has 16px tiles, so at most 4 mip levels keep them apart
+        manager.load_pack_texture(
             gl,
             "terrain",
             include_bytes!("assets/textures/terrain.png"),
+            Some(4),
         );
-        manager.load_png_texture(
+        // Terrain is mostly seen at an angle, the font always head on
+        if let Some(terrain) = manager.get_texture_by_name("terrain") {
+            terrain.enable_anisotropic_filtering(gl);
+        }
+        manager.load_pack_texture(
             gl,
             "font",
             include_bytes!("assets/textures/font.png"),
+            None,
         );
         manager
     }
 
+    // Loads `<name>.png` from the texture pack, or `embedded` when the pack
+    // doesn't have it or it can't be decoded
+    fn load_pack_texture(
+        &mut self,
+        gl: &GlFns,
+        name: &str,
+        embedded: &[u8],
+        max_mip_level: Option<i32>,
+    ) -> usize {
+        let path = Path::new(TEXTURE_PACK_DIR).join(format!("{}.png", name));
+        if path.exists() {
+            match self.load_png_file(gl, name, &path, max_mip_level) {
+                Ok(index) => return index,
+                Err(e) => eprintln!("{}, using the built in texture", e),
+            }
+        }
+        self.load_png_texture(gl, name, embedded, max_mip_level)
+    }
+
+    /// Loads a texture under `name` and returns its index. Reloading a name
+    /// keeps the index it had.
     pub fn load_texture(
         &mut self,
         gl: &GlFns,
@@ -127,14 +333,41 @@ impl TextureManager {
         height: i32,
         data: &[u8],
         format: GLenum,
+        max_mip_level: Option<i32>,
     ) -> usize {
-        let texture = Texture::from_data(gl, width, height, data, format);
-        self.textures.insert(name.to_string(), texture);
-        self.textures.len() - 1
+        let texture =
+            Texture::from_data(gl, width, height, data, format, max_mip_level);
+        let (index, old_texture) = self.insert_texture(name, texture);
+        // Reloading a name replaces the texture, free the one it had
+        if let Some(old_texture) = old_texture {
+            old_texture.delete(gl);
+        }
+        index
+    }
+
+    // Files `texture` under `name` and returns its index, along with the
+    // texture the name had before for the caller to delete
+    fn insert_texture(
+        &mut self,
+        name: &str,
+        texture: Texture,
+    ) -> (usize, Option<Texture>) {
+        let old_texture = self.textures.insert(name.to_string(), texture);
+        if old_texture.is_none() {
+            self.names.push(name.to_string());
+        }
+        let index = self
+            .names
+            .iter()
+            .position(|loaded| loaded == name)
+            .expect("Loaded textures always have a name");
+        (index, old_texture)
     }
 
     pub fn get_texture(&self, index: usize) -> Option<&Texture> {
-        self.textures.values().nth(index)
+        self.names
+            .get(index)
+            .and_then(|name| self.textures.get(name))
     }
 
     pub fn get_texture_by_name(&self, name: &str) -> Option<&Texture> {
@@ -153,46 +386,200 @@ impl TextureManager {
         }
     }
 
-    pub fn load_png_texture(&mut self, gl: &GlFns, name: &str, bytes: &[u8]) -> usize {
-        let bitmap: Bitmap<r8g8b8a8_Srgb> =
-            png_try_bitmap_rgba(bytes, true).expect("Failed to decode PNG texture");
-
-        let width = bitmap.width;
-        let height = bitmap.height;
-        let data = bitmap.pixels;
-
-        let mut output_data = Vec::with_capacity(width as usize * height as usize * 4);
-
-        for pixel in data {
-            output_data.push(pixel.r);
-            output_data.push(pixel.g);
-            output_data.push(pixel.b);
-            output_data.push(pixel.a);
-        }
+    /// Reads a PNG from disk at runtime, like load_png_texture does with
+    /// embedded bytes. Nothing is loaded if the file can't be read or decoded.
+    pub fn load_png_file(
+        &mut self,
+        gl: &GlFns,
+        name: &str,
+        path: &Path,
+        max_mip_level: Option<i32>,
+    ) -> Result<usize, String> {
+        let bytes = fs::read(path)
+            .map_err(|e| format!("Failed to read texture {}: {}", path.display(), e))?;
+        let (width, height, data) = decode_png(&bytes).map_err(|e| {
+            format!("Failed to decode texture {}: {}", path.display(), e)
+        })?;
+        Ok(self.load_texture(
+            gl,
+            name,
+            width,
+            height,
+            &data,
+            GL_SRGB8_ALPHA8,
+            max_mip_level,
+        ))
+    }
 
+    pub fn load_png_texture(
+        &mut self,
+        gl: &GlFns,
+        name: &str,
+        bytes: &[u8],
+        max_mip_level: Option<i32>,
+    ) -> usize {
+        let (width, height, data) =
+            decode_png(bytes).expect("Failed to decode PNG texture");
         self.load_texture(
             gl,
             name,
-            width as i32,
-            height as i32,
-            &output_data,
+            width,
+            height,
+            &data,
             GL_SRGB8_ALPHA8,
+            max_mip_level,
         )
     }
 
+    /// Animate `region` of a texture through `frames` tiles stacked below it,
+    /// showing each for `frame_time` seconds
+    pub fn declare_animation(
+        &mut self,
+        texture_name: &str,
+        region: [i32; 2],
+        frames: u32,
+        frame_time: f32,
+    ) {
+        let animations = self.animations.entry(texture_name.to_string()).or_default();
+        if animations.len() >= MAX_ANIMATIONS {
+            panic!(
+                "Texture '{}' already has {} animations",
+                texture_name, MAX_ANIMATIONS
+            );
+        }
+        animations.push(TextureAnimation {
+            region,
+            frames: frames.max(1),
+            frame_time,
+            elapsed: 0.0,
+        });
+    }
+
+    pub fn advance_animations(&mut self, delta_time: f32) {
+        for animation in self.animations.values_mut().flatten() {
+            let cycle = animation.frame_time * animation.frames as f32;
+            animation.elapsed = (animation.elapsed + delta_time) % cycle;
+        }
+    }
+
+    /// Frame currently shown for an animated region, 0 if it isn't animated
+    pub fn current_frame(&self, texture_name: &str, region: [i32; 2]) -> u32 {
+        self.animations
+            .get(texture_name)
+            .and_then(|animations| {
+                animations
+                    .iter()
+                    .find(|animation| animation.region == region)
+            })
+            .map_or(0, |animation| animation.current_frame())
+    }
+
+    // Locations of a sampler uniform and its animation uniforms in a shader
+    fn uniform_locations(
+        &self,
+        gl: &GlFns,
+        shader_program: &Shader,
+        uniform_name: &str,
+    ) -> [i32; 3] {
+        let mut uniform_locations = self.uniform_locations.borrow_mut();
+        let program_locations = uniform_locations
+            .entry(shader_program.program_id)
+            .or_default();
+        if let Some(&locations) = program_locations.get(uniform_name) {
+            return locations;
+        }
+        let locations = [
+            shader_program.get_uniform_location(gl, uniform_name),
+            shader_program
+                .get_uniform_location(gl, &format!("{}Animations", uniform_name)),
+            shader_program
+                .get_uniform_location(gl, &format!("{}AnimationCount", uniform_name)),
+        ];
+        program_locations.insert(uniform_name.to_string(), locations);
+        locations
+    }
+
+    /// Bind a texture to a unit and point the sampler uniform at it. Animated
+    /// regions are passed as `<uniform>Animations` (column, row and frame for
+    /// each) and `<uniform>AnimationCount`, so the shader can offset them.
     pub fn set_texture_uniform(
         &self,
         gl: &GlFns,
         texture_name: &str,
-        shader_program: Shader,
+        shader_program: &Shader,
         uniform_name: &str,
         texture_unit: u32,
     ) {
         if let Some(texture) = self.get_texture_by_name(&texture_name) {
             texture.bind_to_unit(gl, texture_unit);
-            shader_program.set_int(gl, uniform_name, texture_unit as i32);
+            let [sampler, animations_location, count_location] =
+                self.uniform_locations(gl, shader_program, uniform_name);
+
+            let animations = self
+                .animations
+                .get(texture_name)
+                .map_or(&[][..], |animations| animations.as_slice());
+            let mut animation_data = [0; 3 * MAX_ANIMATIONS];
+            for (data, animation) in animation_data.chunks_mut(3).zip(animations) {
+                data[0] = animation.region[0];
+                data[1] = animation.region[1];
+                data[2] = animation.current_frame() as i32;
+            }
+            unsafe {
+                gl.Uniform1i(sampler, texture_unit as i32);
+                gl.Uniform1iv(
+                    animations_location,
+                    3 * animations.len() as i32,
+                    animation_data.as_ptr(),
+                );
+                gl.Uniform1i(count_location, animations.len() as i32);
+            }
         } else {
             eprintln!("Texture '{}' not found", texture_name);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A manager with no textures, none of the bookkeeping needs a GL context
+    fn empty_manager() -> TextureManager {
+        TextureManager {
+            textures: HashMap::new(),
+            names: Vec::new(),
+            animations: HashMap::new(),
+            uniform_locations: RefCell::new(HashMap::new()),
+        }
+    }
+
+    fn texture(id: u32) -> Texture {
+        Texture {
+            id,
+            texture_type: GL_TEXTURE_2D,
+        }
+    }
+
+    #[test]
+    fn reloading_a_name_replaces_its_texture() {
+        let mut manager = empty_manager();
+        assert!(manager.insert_texture("terrain", texture(1)).1.is_none());
+        let (_, old_texture) = manager.insert_texture("terrain", texture(2));
+        assert_eq!(old_texture.map(|old| old.id), Some(1));
+        assert_eq!(manager.get_texture_by_name("terrain").unwrap().id, 2);
+    }
+
+    #[test]
+    fn reloading_a_name_keeps_its_index() {
+        let mut manager = empty_manager();
+        let (terrain, _) = manager.insert_texture("terrain", texture(1));
+        let (font, _) = manager.insert_texture("font", texture(2));
+        assert_ne!(terrain, font);
+
+        let (reloaded, _) = manager.insert_texture("terrain", texture(3));
+        assert_eq!(reloaded, terrain);
+        assert_eq!(manager.get_texture(terrain).unwrap().id, 3);
+        assert_eq!(manager.get_texture(font).unwrap().id, 2);
+    }
+}
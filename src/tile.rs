@@ -1,4 +1,76 @@
-use crate::utils::*;
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+use crate::{
+    texture::TextureAtlas,
+    utils::*,
+    world::{BlockId, ItemId, MAX_LIGHT_LEVEL},
+};
+
+/// Tiles of terrain.png as [col, row] grid coordinates, the material ids tiles
+/// return. Constants so the mesher never looks regions up by name.
+pub mod region {
+    pub const GRASS_TOP: [i32; 2] = [0, 0];
+    pub const STONE: [i32; 2] = [1, 0];
+    pub const DIRT: [i32; 2] = [2, 0];
+    pub const GRASS_SIDE: [i32; 2] = [3, 0];
+    pub const LOG_SIDE: [i32; 2] = [4, 1];
+    pub const LOG_TOP: [i32; 2] = [5, 1];
+    pub const LEAVES: [i32; 2] = [4, 3];
+    // Its animation frames take up the two tiles below it
+    pub const WATER: [i32; 2] = [15, 13];
+    // No sand tile yet, sand tints the speckled grass top instead
+    pub const SAND: [i32; 2] = GRASS_TOP;
+    // Plants reuse the leaves cutout until the atlas has plant tiles
+    pub const PLANT: [i32; 2] = LEAVES;
+}
+
+/// Ids TileRegistry::new registers the built in tiles at, for code that
+/// places blocks without a registry at hand, like world generation
+pub mod blocks {
+    use crate::world::BlockId;
+
+    pub const STONE: BlockId = 1;
+    pub const DIRT: BlockId = 2;
+    pub const GRASS: BlockId = 3;
+    pub const WATER: BlockId = 4;
+    pub const LOG: BlockId = 5;
+    pub const LEAVES: BlockId = 6;
+    pub const SAND: BlockId = 7;
+    pub const COAL_ORE: BlockId = 8;
+    pub const IRON_ORE: BlockId = 9;
+    pub const DIAMOND_ORE: BlockId = 10;
+    pub const TALL_GRASS: BlockId = 11;
+    pub const FLOWER: BlockId = 12;
+    pub const STONE_SLAB: BlockId = 13;
+    pub const STONE_STAIRS: BlockId = 14;
+    pub const LAMP: BlockId = 15;
+}
+
+// Names the regions above are registered under on the atlas
+const TERRAIN_REGIONS: [(&str, [i32; 2]); 10] = [
+    ("grass_top", region::GRASS_TOP),
+    ("stone", region::STONE),
+    ("dirt", region::DIRT),
+    ("grass_side", region::GRASS_SIDE),
+    ("log_side", region::LOG_SIDE),
+    ("log_top", region::LOG_TOP),
+    ("leaves", region::LEAVES),
+    ("water", region::WATER),
+    ("sand", region::SAND),
+    ("plant", region::PLANT),
+];
+
+/// Layout of terrain.png, with the regions above registered by name
+pub static TERRAIN_ATLAS: LazyLock<TextureAtlas> = LazyLock::new(|| {
+    TERRAIN_REGIONS.iter().fold(
+        TextureAtlas::from_grid("terrain", 16, 16),
+        |atlas, &(name, [col, row])| atlas.with_region(name, col as u32, row as u32),
+    )
+});
+
+/// Collision box of a block filling its whole voxel, in block local coordinates
+pub const FULL_BLOCK_BOX: [[f32; 3]; 2] = [[0.0, 0.0, 0.0], [1.0, 1.0, 1.0]];
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TileFace {
@@ -10,18 +82,143 @@ pub enum TileFace {
     East,
 }
 
+impl TileFace {
+    /// Unit vector pointing out of the face
+    pub fn normal(self) -> [f32; 3] {
+        match self {
+            TileFace::Top => [0.0, 1.0, 0.0],
+            TileFace::Bottom => [0.0, -1.0, 0.0],
+            TileFace::North => [0.0, 0.0, -1.0],
+            TileFace::West => [-1.0, 0.0, 0.0],
+            TileFace::South => [0.0, 0.0, 1.0],
+            TileFace::East => [1.0, 0.0, 0.0],
+        }
+    }
+
+    /// Corners of this face of the unit cube, counter-clockwise from outside
+    pub fn corners(self) -> [[f32; 3]; 4] {
+        match self {
+            TileFace::Top => [
+                [BACK_TOP_LEFT_X, BACK_TOP_LEFT_Y, BACK_TOP_LEFT_Z],
+                [BACK_TOP_RIGHT_X, BACK_TOP_RIGHT_Y, BACK_TOP_RIGHT_Z],
+                [FRONT_TOP_RIGHT_X, FRONT_TOP_RIGHT_Y, FRONT_TOP_RIGHT_Z],
+                [FRONT_TOP_LEFT_X, FRONT_TOP_LEFT_Y, FRONT_TOP_LEFT_Z],
+            ],
+            TileFace::Bottom => [
+                [
+                    FRONT_BOTTOM_LEFT_X,
+                    FRONT_BOTTOM_LEFT_Y,
+                    FRONT_BOTTOM_LEFT_Z,
+                ],
+                [
+                    FRONT_BOTTOM_RIGHT_X,
+                    FRONT_BOTTOM_RIGHT_Y,
+                    FRONT_BOTTOM_RIGHT_Z,
+                ],
+                [
+                    BACK_BOTTOM_RIGHT_X,
+                    BACK_BOTTOM_RIGHT_Y,
+                    BACK_BOTTOM_RIGHT_Z,
+                ],
+                [BACK_BOTTOM_LEFT_X, BACK_BOTTOM_LEFT_Y, BACK_BOTTOM_LEFT_Z],
+            ],
+            TileFace::North => [
+                [
+                    FRONT_BOTTOM_RIGHT_X,
+                    FRONT_BOTTOM_RIGHT_Y,
+                    FRONT_BOTTOM_RIGHT_Z,
+                ],
+                [
+                    FRONT_BOTTOM_LEFT_X,
+                    FRONT_BOTTOM_LEFT_Y,
+                    FRONT_BOTTOM_LEFT_Z,
+                ],
+                [FRONT_TOP_LEFT_X, FRONT_TOP_LEFT_Y, FRONT_TOP_LEFT_Z],
+                [FRONT_TOP_RIGHT_X, FRONT_TOP_RIGHT_Y, FRONT_TOP_RIGHT_Z],
+            ],
+            TileFace::West => [
+                [
+                    FRONT_BOTTOM_LEFT_X,
+                    FRONT_BOTTOM_LEFT_Y,
+                    FRONT_BOTTOM_LEFT_Z,
+                ],
+                [BACK_BOTTOM_LEFT_X, BACK_BOTTOM_LEFT_Y, BACK_BOTTOM_LEFT_Z],
+                [BACK_TOP_LEFT_X, BACK_TOP_LEFT_Y, BACK_TOP_LEFT_Z],
+                [FRONT_TOP_LEFT_X, FRONT_TOP_LEFT_Y, FRONT_TOP_LEFT_Z],
+            ],
+            TileFace::South => [
+                [BACK_BOTTOM_LEFT_X, BACK_BOTTOM_LEFT_Y, BACK_BOTTOM_LEFT_Z],
+                [
+                    BACK_BOTTOM_RIGHT_X,
+                    BACK_BOTTOM_RIGHT_Y,
+                    BACK_BOTTOM_RIGHT_Z,
+                ],
+                [BACK_TOP_RIGHT_X, BACK_TOP_RIGHT_Y, BACK_TOP_RIGHT_Z],
+                [BACK_TOP_LEFT_X, BACK_TOP_LEFT_Y, BACK_TOP_LEFT_Z],
+            ],
+            TileFace::East => [
+                [
+                    BACK_BOTTOM_RIGHT_X,
+                    BACK_BOTTOM_RIGHT_Y,
+                    BACK_BOTTOM_RIGHT_Z,
+                ],
+                [
+                    FRONT_BOTTOM_RIGHT_X,
+                    FRONT_BOTTOM_RIGHT_Y,
+                    FRONT_BOTTOM_RIGHT_Z,
+                ],
+                [FRONT_TOP_RIGHT_X, FRONT_TOP_RIGHT_Y, FRONT_TOP_RIGHT_Z],
+                [BACK_TOP_RIGHT_X, BACK_TOP_RIGHT_Y, BACK_TOP_RIGHT_Z],
+            ],
+        }
+    }
+}
+
+/// Geometry buffers for a single render layer of a chunk, which tiles with
+/// custom geometry emit their blocks into
+#[derive(Default)]
+pub struct LayerBuffers {
+    pub vertices: Vec<[f32; 3]>,
+    pub indices: Vec<u32>,
+    pub colors: Vec<[f32; 4]>,
+    pub uvs: Vec<[f32; 2]>,
+    pub materials: Vec<[i32; 2]>,
+    pub lights: Vec<[f32; 4]>,
+    pub normals: Vec<[f32; 3]>,
+}
+
+/// An atlas region cycling through `frames` tiles stacked below it, showing
+/// each for `frame_time` seconds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TileAnimation {
+    pub region: [i32; 2],
+    pub frames: u32,
+    pub frame_time: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum RenderLayer {
     Opaque,
+    Transparent, // Drawn after all opaque geometry, back to front, without depth writes
 }
 
 pub trait Tile: Sync + Send {
-    fn occludes_geometry(&self, render_layer: RenderLayer, target: u8) -> bool {
+    /// Unique name used to look the tile up in the registry
+    fn name(&self) -> &str;
+
+    fn occludes_geometry(&self, render_layer: RenderLayer, target: BlockId) -> bool {
         // Default occlusion logic, can be overridden
         match render_layer {
             RenderLayer::Opaque => false, // Opaque tiles occlude geometry
+            RenderLayer::Transparent => false,
         }
     }
 
+    /// Which render pass this tile's faces are tessellated into
+    fn render_layer(&self) -> RenderLayer {
+        RenderLayer::Opaque
+    }
+
     fn is_dual_sided(&self) -> bool {
         false
     }
@@ -38,6 +235,49 @@ pub trait Tile: Sync + Send {
         false
     }
 
+    /// Fluids slow down and buoy up objects inside them
+    fn is_fluid(&self) -> bool {
+        false
+    }
+
+    /// Seconds the mouse has to be held on the tile to break it
+    fn hardness(&self) -> f32 {
+        1.0
+    }
+
+    /// Items given when the block breaks as (item id, count) pairs, the block
+    /// itself by default
+    fn drops(&self, block_id: BlockId, _metadata: u8) -> Vec<(ItemId, u8)> {
+        vec![(block_id, 1)]
+    }
+
+    /// Block light level given off by the tile, from 0 up to MAX_LIGHT_LEVEL
+    fn light_emission(&self) -> u8 {
+        0
+    }
+
+    /// Whether block light stops at this tile instead of spreading through it
+    fn blocks_light(&self) -> bool {
+        self.is_solid()
+    }
+
+    /// Animation to declare on the terrain texture for the tile's region
+    fn animation(&self) -> Option<TileAnimation> {
+        None
+    }
+
+    /// Boxes objects collide with, as (min, max) corners inside the voxel.
+    /// Solid tiles fill the whole voxel unless they override this.
+    fn collision_boxes(&self, _metadata: u8) -> &'static [[[f32; 3]; 2]] {
+        if self.is_solid() {
+            &[FULL_BLOCK_BOX]
+        } else {
+            &[]
+        }
+    }
+
+    /// Light of a vertex from how closed off its corner is, 0 for open up to 3
+    /// for a corner between two blocks
     fn occlude_vertex(&self, occluded_neighbors: i32) -> [f32; 4] {
         // Default occlusion logic, can be overridden
         //if occluded_neighbors > 1 {
@@ -45,10 +285,11 @@ pub trait Tile: Sync + Send {
         //} else {
         //    [1.0, 1.0, 1.0, 1.0] // Normal color for non-occluded vertices
         //}
+        // Evenly spaced steps so the gradient across a face doesn't band
         match occluded_neighbors {
             0 => [0.975, 0.975, 0.975, 1.0], // Fully lit
-            1 => [0.8, 0.8, 0.8, 1.0],       // Slightly occluded
-            2 => [0.7, 0.7, 0.7, 1.0],       // More occluded
+            1 => [0.865, 0.865, 0.865, 1.0], // Slightly occluded
+            2 => [0.755, 0.755, 0.755, 1.0], // More occluded
             _ => [0.65, 0.65, 0.65, 1.0],    // Heavily occluded
         }
     }
@@ -59,16 +300,34 @@ pub trait Tile: Sync + Send {
         *input_color
     }
 
+    /// Tiles that are not cubes return true and build all of their geometry
+    /// in `tesselate_custom` instead of face by face
+    fn has_custom_geometry(&self) -> bool {
+        false
+    }
+
+    /// Emit the whole block at (x, y, z). Only called for full detail meshes,
+    /// custom shapes are left out of LOD meshes.
+    fn tesselate_custom(
+        &self,
+        _x: f32,
+        _y: f32,
+        _z: f32,
+        _metadata: u8,
+        _buffers: &mut LayerBuffers,
+    ) {
+    }
+
     fn tesselate_face(
         &self,
         tile_registry: &TileRegistry,
         render_layer: RenderLayer,
-        block_id: u8,
+        block_id: BlockId,
         x: f32,
         y: f32,
         z: f32,
         face: TileFace,
-        neigbor_ids: [u8; 9],
+        neigbor_ids: [BlockId; 9],
         metadata: u8,
         vertices: &mut Vec<[f32; 3]>,
         indices: &mut Vec<u32>,
@@ -76,6 +335,7 @@ pub trait Tile: Sync + Send {
         uvs: &mut Vec<[f32; 2]>,
         materials: &mut Vec<[i32; 2]>,
         lights: &mut Vec<[f32; 4]>,
+        normals: &mut Vec<[f32; 3]>,
         lod: u8,
     ) {
         let lod = lod as f32;
@@ -94,187 +354,41 @@ pub trait Tile: Sync + Send {
             }
         }
         let vertex_count = vertices.len() as u32;
-        match face {
-            TileFace::Top => {
-                vertices.push([
-                    BACK_TOP_LEFT_X * lod + x as f32,
-                    BACK_TOP_LEFT_Y * lod + y as f32,
-                    BACK_TOP_LEFT_Z * lod + z as f32,
-                ]);
-                vertices.push([
-                    BACK_TOP_RIGHT_X * lod + x as f32,
-                    BACK_TOP_RIGHT_Y * lod + y as f32,
-                    BACK_TOP_RIGHT_Z * lod + z as f32,
-                ]);
-                vertices.push([
-                    FRONT_TOP_RIGHT_X * lod + x as f32,
-                    FRONT_TOP_RIGHT_Y * lod + y as f32,
-                    FRONT_TOP_RIGHT_Z * lod + z as f32,
-                ]);
-                vertices.push([
-                    FRONT_TOP_LEFT_X * lod + x as f32,
-                    FRONT_TOP_LEFT_Y * lod + y as f32,
-                    FRONT_TOP_LEFT_Z * lod + z as f32,
-                ]);
-            }
-            TileFace::Bottom => {
-                vertices.push([
-                    FRONT_BOTTOM_LEFT_X * lod + x as f32,
-                    FRONT_BOTTOM_LEFT_Y * lod + y as f32,
-                    FRONT_BOTTOM_LEFT_Z * lod + z as f32,
-                ]);
-                vertices.push([
-                    FRONT_BOTTOM_RIGHT_X * lod + x as f32,
-                    FRONT_BOTTOM_RIGHT_Y * lod + y as f32,
-                    FRONT_BOTTOM_RIGHT_Z * lod + z as f32,
-                ]);
-                vertices.push([
-                    BACK_BOTTOM_RIGHT_X * lod + x as f32,
-                    BACK_BOTTOM_RIGHT_Y * lod + y as f32,
-                    BACK_BOTTOM_RIGHT_Z * lod + z as f32,
-                ]);
-                vertices.push([
-                    BACK_BOTTOM_LEFT_X * lod + x as f32,
-                    BACK_BOTTOM_LEFT_Y * lod + y as f32,
-                    BACK_BOTTOM_LEFT_Z * lod + z as f32,
-                ]);
-            }
-            TileFace::North => {
-                vertices.push([
-                    FRONT_BOTTOM_RIGHT_X * lod + x as f32,
-                    FRONT_BOTTOM_RIGHT_Y * lod + y as f32,
-                    FRONT_BOTTOM_RIGHT_Z * lod + z as f32,
-                ]);
-                vertices.push([
-                    FRONT_BOTTOM_LEFT_X * lod + x as f32,
-                    FRONT_BOTTOM_LEFT_Y * lod + y as f32,
-                    FRONT_BOTTOM_LEFT_Z * lod + z as f32,
-                ]);
-                vertices.push([
-                    FRONT_TOP_LEFT_X * lod + x as f32,
-                    FRONT_TOP_LEFT_Y * lod + y as f32,
-                    FRONT_TOP_LEFT_Z * lod + z as f32,
-                ]);
-                vertices.push([
-                    FRONT_TOP_RIGHT_X * lod + x as f32,
-                    FRONT_TOP_RIGHT_Y * lod + y as f32,
-                    FRONT_TOP_RIGHT_Z * lod + z as f32,
-                ]);
-            }
-            TileFace::West => {
-                vertices.push([
-                    FRONT_BOTTOM_LEFT_X * lod + x as f32,
-                    FRONT_BOTTOM_LEFT_Y * lod + y as f32,
-                    FRONT_BOTTOM_LEFT_Z * lod + z as f32,
-                ]);
-                vertices.push([
-                    BACK_BOTTOM_LEFT_X * lod + x as f32,
-                    BACK_BOTTOM_LEFT_Y * lod + y as f32,
-                    BACK_BOTTOM_LEFT_Z * lod + z as f32,
-                ]);
-                vertices.push([
-                    BACK_TOP_LEFT_X * lod + x as f32,
-                    BACK_TOP_LEFT_Y * lod + y as f32,
-                    BACK_TOP_LEFT_Z * lod + z as f32,
-                ]);
-                vertices.push([
-                    FRONT_TOP_LEFT_X * lod + x as f32,
-                    FRONT_TOP_LEFT_Y * lod + y as f32,
-                    FRONT_TOP_LEFT_Z * lod + z as f32,
-                ]);
-            }
-            TileFace::South => {
-                vertices.push([
-                    BACK_BOTTOM_LEFT_X * lod + x as f32,
-                    BACK_BOTTOM_LEFT_Y * lod + y as f32,
-                    BACK_BOTTOM_LEFT_Z * lod + z as f32,
-                ]);
-                vertices.push([
-                    BACK_BOTTOM_RIGHT_X * lod + x as f32,
-                    BACK_BOTTOM_RIGHT_Y * lod + y as f32,
-                    BACK_BOTTOM_RIGHT_Z * lod + z as f32,
-                ]);
-                vertices.push([
-                    BACK_TOP_RIGHT_X * lod + x as f32,
-                    BACK_TOP_RIGHT_Y * lod + y as f32,
-                    BACK_TOP_RIGHT_Z * lod + z as f32,
-                ]);
-                vertices.push([
-                    BACK_TOP_LEFT_X * lod + x as f32,
-                    BACK_TOP_LEFT_Y * lod + y as f32,
-                    BACK_TOP_LEFT_Z * lod + z as f32,
-                ]);
-            }
-            TileFace::East => {
-                vertices.push([
-                    BACK_BOTTOM_RIGHT_X * lod + x as f32,
-                    BACK_BOTTOM_RIGHT_Y * lod + y as f32,
-                    BACK_BOTTOM_RIGHT_Z * lod + z as f32,
-                ]);
-                vertices.push([
-                    FRONT_BOTTOM_RIGHT_X * lod + x as f32,
-                    FRONT_BOTTOM_RIGHT_Y * lod + y as f32,
-                    FRONT_BOTTOM_RIGHT_Z * lod + z as f32,
-                ]);
-                vertices.push([
-                    FRONT_TOP_RIGHT_X * lod + x as f32,
-                    FRONT_TOP_RIGHT_Y * lod + y as f32,
-                    FRONT_TOP_RIGHT_Z * lod + z as f32,
-                ]);
-                vertices.push([
-                    BACK_TOP_RIGHT_X * lod + x as f32,
-                    BACK_TOP_RIGHT_Y * lod + y as f32,
-                    BACK_TOP_RIGHT_Z * lod + z as f32,
-                ]);
-            }
+        for corner in face.corners() {
+            vertices.push([
+                corner[0] * lod + x as f32,
+                corner[1] * lod + y as f32,
+                corner[2] * lod + z as f32,
+            ]);
         }
-        // compute ambient occlusion
-        let ao_bottom_left_coords: i32 = [
-            if neigbor_ids[3] == 0 { 0 } else { 1 },
-            if neigbor_ids[4] == 0 { 0 } else { 1 },
-            if neigbor_ids[6] == 0 { 0 } else { 1 },
-            if neigbor_ids[7] == 0 { 0 } else { 1 },
-        ]
-        .iter()
-        .sum();
-        let ao_bottom_right_coords: i32 = [
-            if neigbor_ids[4] == 0 { 0 } else { 1 },
-            if neigbor_ids[5] == 0 { 0 } else { 1 },
-            if neigbor_ids[7] == 0 { 0 } else { 1 },
-            if neigbor_ids[8] == 0 { 0 } else { 1 },
-        ]
-        .iter()
-        .sum();
-        let ao_top_right_coords: i32 = [
-            if neigbor_ids[1] == 0 { 0 } else { 1 },
-            if neigbor_ids[2] == 0 { 0 } else { 1 },
-            if neigbor_ids[4] == 0 { 0 } else { 1 },
-            if neigbor_ids[5] == 0 { 0 } else { 1 },
-        ]
-        .iter()
-        .sum();
-        let ao_top_left_coords: i32 = [
-            if neigbor_ids[0] == 0 { 0 } else { 1 },
-            if neigbor_ids[1] == 0 { 0 } else { 1 },
-            if neigbor_ids[3] == 0 { 0 } else { 1 },
-            if neigbor_ids[4] == 0 { 0 } else { 1 },
-        ]
-        .iter()
-        .sum();
-
-        indices.push(vertex_count);
-        indices.push(vertex_count + 1);
-        indices.push(vertex_count + 2);
-        indices.push(vertex_count + 2);
-        indices.push(vertex_count + 3);
-        indices.push(vertex_count);
+        // compute ambient occlusion from the two blocks beside each corner and
+        // the one diagonal to it, in the 3x3 slice in front of the face. Two
+        // sides already close the corner off, whatever the diagonal holds.
+        let corner_occlusion = |side_a: usize, side_b: usize, corner: usize| -> i32 {
+            let occupied = |index: usize| (neigbor_ids[index] != 0) as i32;
+            if occupied(side_a) == 1 && occupied(side_b) == 1 {
+                3
+            } else {
+                occupied(side_a) + occupied(side_b) + occupied(corner)
+            }
+        };
+        let ao_bottom_left_coords = corner_occlusion(3, 7, 6);
+        let ao_bottom_right_coords = corner_occlusion(5, 7, 8);
+        let ao_top_right_coords = corner_occlusion(1, 5, 2);
+        let ao_top_left_coords = corner_occlusion(1, 3, 0);
+
+        // Split the quad along the diagonal that keeps the occlusion gradient
+        // consistent, otherwise the darkening changes with the quad's rotation
+        let (front, back) = if ao_bottom_left_coords + ao_top_right_coords
+            > ao_bottom_right_coords + ao_top_left_coords
+        {
+            ([1, 2, 3, 3, 0, 1], [3, 2, 1, 1, 0, 3])
+        } else {
+            ([0, 1, 2, 2, 3, 0], [2, 1, 0, 0, 3, 2])
+        };
+        indices.extend(front.map(|i| vertex_count + i));
         if self.is_dual_sided() {
-            indices.push(vertex_count + 3);
-            indices.push(vertex_count + 2);
-            indices.push(vertex_count + 1);
-            indices.push(vertex_count + 1);
-            indices.push(vertex_count);
-            indices.push(vertex_count + 3);
+            indices.extend(back.map(|i| vertex_count + i));
         }
         colors.push(self.get_color_for_face(face, metadata));
         colors.push(self.get_color_for_face(face, metadata));
@@ -299,82 +413,176 @@ pub trait Tile: Sync + Send {
         lights.push(run_filter(&self.occlude_vertex(ao_bottom_right_coords)));
         lights.push(run_filter(&self.occlude_vertex(ao_top_right_coords)));
         lights.push(run_filter(&self.occlude_vertex(ao_top_left_coords)));
+        normals.extend([face.normal(); 4]);
+    }
+}
+
+/// Emits all six faces of an axis aligned box inside the block at `[x, y, z]`,
+/// for custom geometry made out of boxes. Texture coordinates follow the box's
+/// position in the block so partial faces show the matching part of the tile.
+pub fn tesselate_box(
+    tile: &dyn Tile,
+    [x, y, z]: [f32; 3],
+    metadata: u8,
+    [min, max]: [[f32; 3]; 2],
+    buffers: &mut LayerBuffers,
+) {
+    let LayerBuffers {
+        vertices,
+        indices,
+        colors,
+        uvs,
+        materials,
+        lights,
+        normals,
+    } = buffers;
+    let faces = [
+        TileFace::Top,
+        TileFace::Bottom,
+        TileFace::North,
+        TileFace::West,
+        TileFace::South,
+        TileFace::East,
+    ];
+    for face in faces {
+        let corners = face.corners();
+        let vertex_count = vertices.len() as u32;
+        for corner in corners {
+            let local: [f32; 3] = std::array::from_fn(|axis| {
+                min[axis] + corner[axis] * (max[axis] - min[axis])
+            });
+            vertices.push([x + local[0], y + local[1], z + local[2]]);
+            // Same orientation as tesselate_face: u runs from the first corner to
+            // the second and v from the fourth corner to the first
+            let offset: [f32; 3] =
+                std::array::from_fn(|axis| local[axis] - corners[0][axis]);
+            let dot = |edge_end: [f32; 3]| {
+                (0..3)
+                    .map(|axis| offset[axis] * (edge_end[axis] - corners[0][axis]))
+                    .sum::<f32>()
+            };
+            uvs.push([dot(corners[1]), 1.0 - dot(corners[3])]);
+        }
+        indices.extend([0, 1, 2, 2, 3, 0].map(|i| vertex_count + i));
+        colors.extend([tile.get_color_for_face(face, metadata); 4]);
+        materials.extend([tile.get_material_for_face(face, metadata); 4]);
+        lights.extend([tile.occlude_vertex(0); 4]);
+        normals.extend([face.normal(); 4]);
     }
 }
 
 pub struct TileRegistry {
-    handlers: [Option<Box<dyn Tile>>; 256], // Fixed size array
+    handlers: Vec<Option<Box<dyn Tile>>>, // Indexed by block id, grows on demand
+    ids_by_name: HashMap<String, BlockId>,
 }
 
 pub struct StoneTile;
 impl Tile for StoneTile {
+    fn name(&self) -> &str {
+        "stone"
+    }
+
+    fn hardness(&self) -> f32 {
+        1.5
+    }
     fn get_material_for_face(&self, _face: TileFace, _metadata: u8) -> [i32; 2] {
-        [1, 0]
+        region::STONE
     }
 
     fn is_solid(&self) -> bool {
         true
     }
 
-    fn occludes_geometry(&self, render_layer: RenderLayer, target: u8) -> bool {
+    fn occludes_geometry(&self, render_layer: RenderLayer, target: BlockId) -> bool {
         match render_layer {
-            RenderLayer::Opaque => true,
+            RenderLayer::Opaque | RenderLayer::Transparent => true,
         }
     }
 }
 pub struct DirtTile;
 impl Tile for DirtTile {
+    fn name(&self) -> &str {
+        "dirt"
+    }
+
+    fn hardness(&self) -> f32 {
+        0.5
+    }
     fn get_material_for_face(&self, _face: TileFace, _metadata: u8) -> [i32; 2] {
-        [2, 0] // Example material ID for dirt
+        region::DIRT
     }
 
     fn is_solid(&self) -> bool {
         true
     }
 
-    fn occludes_geometry(&self, render_layer: RenderLayer, target: u8) -> bool {
+    fn occludes_geometry(&self, render_layer: RenderLayer, target: BlockId) -> bool {
         match render_layer {
-            RenderLayer::Opaque => true,
+            RenderLayer::Opaque | RenderLayer::Transparent => true,
         }
     }
 }
 pub struct GrassTile;
 impl Tile for GrassTile {
+    fn name(&self) -> &str {
+        "grass"
+    }
+
+    fn hardness(&self) -> f32 {
+        0.6
+    }
+    fn drops(&self, _block_id: BlockId, _metadata: u8) -> Vec<(ItemId, u8)> {
+        vec![(blocks::DIRT, 1)]
+    }
     fn get_color_for_face(&self, _face: TileFace, _metadata: u8) -> [f32; 4] {
         [0.36, 0.62, 0.1, 1.0] // Green color for grass
     }
     fn get_material_for_face(&self, face: TileFace, _metadata: u8) -> [i32; 2] {
         match face {
-            TileFace::Top => [0, 0],
-            TileFace::Bottom => [2, 0],
-            _ => [3, 0],
+            TileFace::Top => region::GRASS_TOP,
+            TileFace::Bottom => region::DIRT,
+            _ => region::GRASS_SIDE,
         }
     }
     fn is_solid(&self) -> bool {
         true
     }
-    fn occludes_geometry(&self, render_layer: RenderLayer, target: u8) -> bool {
+    fn occludes_geometry(&self, render_layer: RenderLayer, target: BlockId) -> bool {
         match render_layer {
-            RenderLayer::Opaque => true,
+            RenderLayer::Opaque | RenderLayer::Transparent => true,
         }
     }
 }
 pub struct WaterTile;
 impl Tile for WaterTile {
+    fn name(&self) -> &str {
+        "water"
+    }
+
+    fn drops(&self, _block_id: BlockId, _metadata: u8) -> Vec<(ItemId, u8)> {
+        Vec::new()
+    }
     fn get_material_for_face(&self, face: TileFace, _metadata: u8) -> [i32; 2] {
         match face {
-            _ => [15, 13],
+            _ => region::WATER,
         }
     }
     fn is_solid(&self) -> bool {
         false // Water is not solid
     }
-    fn occludes_geometry(&self, render_layer: RenderLayer, target: u8) -> bool {
+    fn is_fluid(&self) -> bool {
+        true
+    }
+    fn occludes_geometry(&self, render_layer: RenderLayer, target: BlockId) -> bool {
         match render_layer {
-            RenderLayer::Opaque => target == 4,
+            RenderLayer::Opaque | RenderLayer::Transparent => target == 4,
         }
     }
 
+    fn render_layer(&self) -> RenderLayer {
+        RenderLayer::Transparent
+    }
+
     fn occlusion_filter(&self, input_color: &[f32; 4]) -> [f32; 4] {
         // Apply a blue tint for water
         [
@@ -388,42 +596,370 @@ impl Tile for WaterTile {
     fn is_dual_sided(&self) -> bool {
         true // Water is dual-sided
     }
+
+    fn animation(&self) -> Option<TileAnimation> {
+        Some(TileAnimation {
+            region: region::WATER,
+            frames: 3,
+            frame_time: 0.25,
+        })
+    }
 }
 
 pub struct LogTile;
 impl Tile for LogTile {
+    fn name(&self) -> &str {
+        "log"
+    }
+
+    fn hardness(&self) -> f32 {
+        2.0
+    }
     fn get_material_for_face(&self, _face: TileFace, _metadata: u8) -> [i32; 2] {
         match _face {
-            TileFace::Top | TileFace::Bottom => [5, 1],
-            _ => [4, 1], // Example material ID for log sides
+            TileFace::Top | TileFace::Bottom => region::LOG_TOP,
+            _ => region::LOG_SIDE,
+        }
+    }
+    fn is_solid(&self) -> bool {
+        true
+    }
+    fn occludes_geometry(&self, render_layer: RenderLayer, target: BlockId) -> bool {
+        match render_layer {
+            RenderLayer::Opaque | RenderLayer::Transparent => true,
         }
     }
+}
+
+pub struct SandTile;
+impl Tile for SandTile {
+    fn name(&self) -> &str {
+        "sand"
+    }
+
+    fn hardness(&self) -> f32 {
+        0.5
+    }
+    fn get_color_for_face(&self, _face: TileFace, _metadata: u8) -> [f32; 4] {
+        [1.0, 0.92, 0.65, 1.0]
+    }
+    fn get_material_for_face(&self, _face: TileFace, _metadata: u8) -> [i32; 2] {
+        region::SAND
+    }
     fn is_solid(&self) -> bool {
         true
     }
-    fn occludes_geometry(&self, render_layer: RenderLayer, target: u8) -> bool {
+    fn occludes_geometry(&self, render_layer: RenderLayer, _target: BlockId) -> bool {
         match render_layer {
-            RenderLayer::Opaque => true,
+            RenderLayer::Opaque | RenderLayer::Transparent => true,
         }
     }
 }
 
+/// Stone containing an ore, told apart by the ore's tint
+pub struct OreTile {
+    pub name: &'static str,
+    pub color: [f32; 4],
+}
+impl Tile for OreTile {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn hardness(&self) -> f32 {
+        3.0
+    }
+    fn get_color_for_face(&self, _face: TileFace, _metadata: u8) -> [f32; 4] {
+        self.color
+    }
+    fn get_material_for_face(&self, _face: TileFace, _metadata: u8) -> [i32; 2] {
+        region::STONE
+    }
+    fn is_solid(&self) -> bool {
+        true
+    }
+    fn occludes_geometry(&self, render_layer: RenderLayer, _target: BlockId) -> bool {
+        match render_layer {
+            RenderLayer::Opaque | RenderLayer::Transparent => true,
+        }
+    }
+}
+
+/// Plants drawn as two quads crossing diagonally through the block
+pub struct CrossTile {
+    pub name: &'static str,
+    pub color: [f32; 4],
+}
+impl Tile for CrossTile {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn hardness(&self) -> f32 {
+        0.0
+    }
+    fn get_color_for_face(&self, _face: TileFace, _metadata: u8) -> [f32; 4] {
+        self.color
+    }
+    fn get_material_for_face(&self, _face: TileFace, _metadata: u8) -> [i32; 2] {
+        region::PLANT
+    }
+    fn is_dual_sided(&self) -> bool {
+        true
+    }
+    fn has_custom_geometry(&self) -> bool {
+        true
+    }
+    fn tesselate_custom(
+        &self,
+        x: f32,
+        y: f32,
+        z: f32,
+        metadata: u8,
+        buffers: &mut LayerBuffers,
+    ) {
+        let LayerBuffers {
+            vertices,
+            indices,
+            colors,
+            uvs,
+            materials,
+            lights,
+            normals,
+        } = buffers;
+        // Each diagonal as (start, end) corners on the xz plane
+        let diagonals = [([0.0, 0.0], [1.0, 1.0]), ([1.0, 0.0], [0.0, 1.0])];
+        for (start, end) in diagonals {
+            let vertex_count = vertices.len() as u32;
+            vertices.push([x + start[0], y, z + start[1]]);
+            vertices.push([x + end[0], y, z + end[1]]);
+            vertices.push([x + end[0], y + 1.0, z + end[1]]);
+            vertices.push([x + start[0], y + 1.0, z + start[1]]);
+            // Both sides, since the quads are seen from either direction
+            indices.extend([0, 1, 2, 2, 3, 0].map(|i| vertex_count + i));
+            indices.extend([3, 2, 1, 1, 0, 3].map(|i| vertex_count + i));
+            uvs.extend([[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]]);
+            colors.extend([self.get_color_for_face(TileFace::Top, metadata); 4]);
+            materials.extend([self.get_material_for_face(TileFace::Top, metadata); 4]);
+            lights.extend([self.occlude_vertex(0); 4]);
+            // Lit like the ground they stand on
+            normals.extend([TileFace::Top.normal(); 4]);
+        }
+    }
+}
+
+/// Half a block, in the bottom half unless bit 0 of the metadata is set
+pub struct SlabTile {
+    pub name: &'static str,
+    pub material: [i32; 2],
+}
+impl SlabTile {
+    fn shape(metadata: u8) -> &'static [[[f32; 3]; 2]] {
+        if metadata & 1 == 0 {
+            &[[[0.0, 0.0, 0.0], [1.0, 0.5, 1.0]]]
+        } else {
+            &[[[0.0, 0.5, 0.0], [1.0, 1.0, 1.0]]]
+        }
+    }
+}
+impl Tile for SlabTile {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn hardness(&self) -> f32 {
+        1.5
+    }
+    fn get_material_for_face(&self, _face: TileFace, _metadata: u8) -> [i32; 2] {
+        self.material
+    }
+    fn is_solid(&self) -> bool {
+        true
+    }
+    fn collision_boxes(&self, metadata: u8) -> &'static [[[f32; 3]; 2]] {
+        Self::shape(metadata)
+    }
+    // Lit through the open half so its faces aren't left dark
+    fn blocks_light(&self) -> bool {
+        false
+    }
+    // Never hides a neighbor's face, part of it always shows past the slab
+    fn occludes_geometry(&self, _render_layer: RenderLayer, _target: BlockId) -> bool {
+        false
+    }
+    fn has_custom_geometry(&self) -> bool {
+        true
+    }
+    fn tesselate_custom(
+        &self,
+        x: f32,
+        y: f32,
+        z: f32,
+        metadata: u8,
+        buffers: &mut LayerBuffers,
+    ) {
+        for &bounds in Self::shape(metadata) {
+            tesselate_box(self, [x, y, z], metadata, bounds, buffers);
+        }
+    }
+}
+
+/// A bottom slab with a second half-height box on one side. The lowest two
+/// bits of the metadata pick that side: 0 north, 1 east, 2 south, 3 west.
+pub struct StairsTile {
+    pub name: &'static str,
+    pub material: [i32; 2],
+}
+impl StairsTile {
+    fn shape(metadata: u8) -> &'static [[[f32; 3]; 2]] {
+        const BOTTOM: [[f32; 3]; 2] = [[0.0, 0.0, 0.0], [1.0, 0.5, 1.0]];
+        match metadata & 3 {
+            0 => &[BOTTOM, [[0.0, 0.5, 0.0], [1.0, 1.0, 0.5]]],
+            1 => &[BOTTOM, [[0.5, 0.5, 0.0], [1.0, 1.0, 1.0]]],
+            2 => &[BOTTOM, [[0.0, 0.5, 0.5], [1.0, 1.0, 1.0]]],
+            _ => &[BOTTOM, [[0.0, 0.5, 0.0], [0.5, 1.0, 1.0]]],
+        }
+    }
+}
+impl Tile for StairsTile {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn hardness(&self) -> f32 {
+        1.5
+    }
+    fn get_material_for_face(&self, _face: TileFace, _metadata: u8) -> [i32; 2] {
+        self.material
+    }
+    fn is_solid(&self) -> bool {
+        true
+    }
+    fn collision_boxes(&self, metadata: u8) -> &'static [[[f32; 3]; 2]] {
+        Self::shape(metadata)
+    }
+    fn blocks_light(&self) -> bool {
+        false
+    }
+    fn occludes_geometry(&self, _render_layer: RenderLayer, _target: BlockId) -> bool {
+        false
+    }
+    fn has_custom_geometry(&self) -> bool {
+        true
+    }
+    fn tesselate_custom(
+        &self,
+        x: f32,
+        y: f32,
+        z: f32,
+        metadata: u8,
+        buffers: &mut LayerBuffers,
+    ) {
+        for &bounds in Self::shape(metadata) {
+            tesselate_box(self, [x, y, z], metadata, bounds, buffers);
+        }
+    }
+}
+
+/// Everything needed to describe a plain cube tile, so simple tiles can be
+/// registered as a GenericTile without writing a Tile impl for each
+#[derive(Debug, Clone)]
+pub struct TileDescriptor {
+    pub name: String,
+    pub top_material: [i32; 2],
+    pub bottom_material: [i32; 2],
+    pub side_material: [i32; 2],
+    pub color: [f32; 4],
+    pub solid: bool,
+    // Hides the faces of neighbouring blocks that touch it
+    pub occludes: bool,
+    pub hardness: f32,
+    pub light_emission: u8,
+}
+
+impl TileDescriptor {
+    /// Solid, occluding, untinted cube with the same material on every face.
+    /// Override the rest with struct update syntax.
+    pub fn new(name: &str, material: [i32; 2]) -> Self {
+        Self {
+            name: name.to_string(),
+            top_material: material,
+            bottom_material: material,
+            side_material: material,
+            color: [1.0, 1.0, 1.0, 1.0],
+            solid: true,
+            occludes: true,
+            hardness: 1.0,
+            light_emission: 0,
+        }
+    }
+}
+
+/// Cube tile that behaves as its TileDescriptor says
+pub struct GenericTile {
+    descriptor: TileDescriptor,
+}
+
+impl GenericTile {
+    pub fn new(descriptor: TileDescriptor) -> Self {
+        Self { descriptor }
+    }
+}
+
+impl Tile for GenericTile {
+    fn name(&self) -> &str {
+        &self.descriptor.name
+    }
+
+    fn hardness(&self) -> f32 {
+        self.descriptor.hardness
+    }
+    fn get_color_for_face(&self, _face: TileFace, _metadata: u8) -> [f32; 4] {
+        self.descriptor.color
+    }
+    fn get_material_for_face(&self, face: TileFace, _metadata: u8) -> [i32; 2] {
+        match face {
+            TileFace::Top => self.descriptor.top_material,
+            TileFace::Bottom => self.descriptor.bottom_material,
+            _ => self.descriptor.side_material,
+        }
+    }
+    fn is_solid(&self) -> bool {
+        self.descriptor.solid
+    }
+    fn light_emission(&self) -> u8 {
+        self.descriptor.light_emission
+    }
+    fn occludes_geometry(&self, _render_layer: RenderLayer, _target: BlockId) -> bool {
+        self.descriptor.occludes
+    }
+}
+
 pub struct LeavesTile;
 impl Tile for LeavesTile {
+    fn name(&self) -> &str {
+        "leaves"
+    }
+
+    fn hardness(&self) -> f32 {
+        0.2
+    }
+    fn drops(&self, _block_id: BlockId, _metadata: u8) -> Vec<(ItemId, u8)> {
+        Vec::new()
+    }
     fn get_color_for_face(&self, _face: TileFace, _metadata: u8) -> [f32; 4] {
         // green-yellowish
         [141.0 / 255.0, 191.0 / 255.0, 43.0 / 255.0, 1.0]
     }
     fn get_material_for_face(&self, _face: TileFace, _metadata: u8) -> [i32; 2] {
-        [4, 3]
-        //[0, 5]
+        region::LEAVES
     }
     fn is_solid(&self) -> bool {
         true
     }
-    fn occludes_geometry(&self, render_layer: RenderLayer, target: u8) -> bool {
+    fn occludes_geometry(&self, render_layer: RenderLayer, target: BlockId) -> bool {
         match render_layer {
-            RenderLayer::Opaque => {
+            RenderLayer::Opaque | RenderLayer::Transparent => {
                 // Only occludes if it's myself (target == 6)
                 target == 6
             }
@@ -432,27 +968,141 @@ impl Tile for LeavesTile {
     fn is_dual_sided(&self) -> bool {
         true
     }
+    // Light filters through the gaps between leaves
+    fn blocks_light(&self) -> bool {
+        false
+    }
 }
 
 impl TileRegistry {
     pub fn new() -> Self {
-        const INIT: Option<Box<dyn Tile>> = None;
         let mut registry = TileRegistry {
-            handlers: [INIT; 256],
+            handlers: Vec::new(),
+            ids_by_name: HashMap::new(),
         };
 
-        // Register default tiles
-        registry.handlers[1] = Some(Box::new(StoneTile));
-        registry.handlers[2] = Some(Box::new(DirtTile));
-        registry.handlers[3] = Some(Box::new(GrassTile));
-        registry.handlers[4] = Some(Box::new(WaterTile));
-        registry.handlers[5] = Some(Box::new(LogTile));
-        registry.handlers[6] = Some(Box::new(LeavesTile));
+        // Register default tiles, world generation relies on these ids
+        registry.register_at(blocks::STONE, Box::new(StoneTile));
+        registry.register_at(blocks::DIRT, Box::new(DirtTile));
+        registry.register_at(blocks::GRASS, Box::new(GrassTile));
+        registry.register_at(blocks::WATER, Box::new(WaterTile));
+        registry.register_at(blocks::LOG, Box::new(LogTile));
+        registry.register_at(blocks::LEAVES, Box::new(LeavesTile));
+        registry.register_at(blocks::SAND, Box::new(SandTile));
+        registry.register_at(
+            blocks::COAL_ORE,
+            Box::new(OreTile {
+                name: "coal_ore",
+                color: [0.75, 0.75, 0.75, 1.0],
+            }),
+        );
+        registry.register_at(
+            blocks::IRON_ORE,
+            Box::new(OreTile {
+                name: "iron_ore",
+                color: [1.0, 0.85, 0.7, 1.0],
+            }),
+        );
+        registry.register_at(
+            blocks::DIAMOND_ORE,
+            Box::new(OreTile {
+                name: "diamond_ore",
+                color: [0.7, 1.0, 1.0, 1.0],
+            }),
+        );
+        registry.register_at(
+            blocks::TALL_GRASS,
+            Box::new(CrossTile {
+                name: "tall_grass",
+                color: [0.36, 0.62, 0.1, 1.0],
+            }),
+        );
+        registry.register_at(
+            blocks::FLOWER,
+            Box::new(CrossTile {
+                name: "flower",
+                color: [0.95, 0.8, 0.2, 1.0],
+            }),
+        );
+        registry.register_at(
+            blocks::STONE_SLAB,
+            Box::new(SlabTile {
+                name: "stone_slab",
+                material: region::STONE,
+            }),
+        );
+        registry.register_at(
+            blocks::STONE_STAIRS,
+            Box::new(StairsTile {
+                name: "stone_stairs",
+                material: region::STONE,
+            }),
+        );
+        registry.register_at(
+            blocks::LAMP,
+            Box::new(GenericTile::new(TileDescriptor {
+                color: [1.0, 0.85, 0.5, 1.0],
+                hardness: 0.3,
+                light_emission: MAX_LIGHT_LEVEL,
+                ..TileDescriptor::new("lamp", region::STONE)
+            })),
+        );
 
         registry
     }
 
-    pub fn get_handler(&self, id: u8) -> Option<&dyn Tile> {
-        self.handlers[id as usize].as_deref()
+    /// Register a tile at the first free id and return that id
+    pub fn register(&mut self, tile: Box<dyn Tile>) -> BlockId {
+        // Id 0 is always air
+        let id = (1..self.handlers.len())
+            .find(|&index| self.handlers[index].is_none())
+            .unwrap_or(self.handlers.len().max(1));
+        let id = BlockId::try_from(id).expect("Ran out of block ids");
+        self.register_at(id, tile);
+        id
+    }
+
+    /// Register a tile at a specific id, replacing whatever was there
+    pub fn register_at(&mut self, id: BlockId, tile: Box<dyn Tile>) {
+        if id == 0 {
+            panic!("Block id 0 is reserved for air");
+        }
+        let name = tile.name().to_string();
+        if let Some(&existing_id) = self.ids_by_name.get(&name)
+            && existing_id != id
+        {
+            panic!(
+                "Tile name '{}' is already registered with id {}",
+                name, existing_id
+            );
+        }
+
+        let index = id as usize;
+        if index >= self.handlers.len() {
+            self.handlers.resize_with(index + 1, || None);
+        }
+        if let Some(old_tile) = &self.handlers[index] {
+            self.ids_by_name.remove(old_tile.name());
+        }
+        self.handlers[index] = Some(tile);
+        self.ids_by_name.insert(name, id);
+    }
+
+    pub fn get_handler(&self, id: BlockId) -> Option<&dyn Tile> {
+        self.handlers
+            .get(id as usize)
+            .and_then(|handler| handler.as_deref())
+    }
+
+    pub fn get_id_by_name(&self, name: &str) -> Option<BlockId> {
+        self.ids_by_name.get(name).copied()
+    }
+
+    /// Animations of every registered tile
+    pub fn animations(&self) -> impl Iterator<Item = TileAnimation> + '_ {
+        self.handlers
+            .iter()
+            .flatten()
+            .filter_map(|tile| tile.animation())
     }
 }
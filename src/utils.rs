@@ -1,3 +1,9 @@
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
 use tokio::task::JoinHandle;
 
 pub const FRONT_X: f32 = 0.0;
@@ -46,6 +52,9 @@ pub const FRONT_BOTTOM_RIGHT_Z: f32 = FRONT_Z + DOWN_Z + RIGHT_Z;
 pub enum QueuedItem<T> {
     Generating(JoinHandle<T>),
     Ready(T),
+    // The task generating the item panicked or was cancelled, the owner should
+    // drop it and enqueue a new one
+    Failed,
 }
 
 impl<T: Send + 'static> QueuedItem<T> {
@@ -56,12 +65,43 @@ impl<T: Send + 'static> QueuedItem<T> {
         QueuedItem::Generating(tokio::spawn(f))
     }
 
+    /// Like enqueue, but for CPU bound work. The closure runs on tokio's
+    /// blocking thread pool instead of tying up the async workers.
+    pub fn enqueue_blocking<F>(f: F) -> Self
+    where
+        F: FnOnce() -> T + Send + 'static,
+    {
+        QueuedItem::Generating(tokio::task::spawn_blocking(f))
+    }
+
+    /// Aborts the task generating the item, if it's still running. Blocking
+    /// tasks can only be stopped before they start, one already running
+    /// finishes and its result is dropped.
+    pub fn cancel(&self) {
+        if let QueuedItem::Generating(handle) = self {
+            handle.abort();
+        }
+    }
+
+    pub fn is_failed(&self) -> bool {
+        matches!(self, QueuedItem::Failed)
+    }
+
+    /// The item once it's ready. None while it's still generating, and from
+    /// then on if generating it failed, see is_failed.
     pub async fn get(&mut self) -> Option<&mut T> {
         match self {
             QueuedItem::Generating(handle) => {
                 if handle.is_finished() {
-                    let element = handle.await.expect("Failed to join handle");
-                    *self = QueuedItem::Ready(element);
+                    match handle.await {
+                        Ok(element) => *self = QueuedItem::Ready(element),
+                        Err(e) => {
+                            // A failed task shouldn't take the caller down with it
+                            eprintln!("Queued item failed to generate: {}", e);
+                            *self = QueuedItem::Failed;
+                            return None;
+                        }
+                    }
                     if let QueuedItem::Ready(item) = self {
                         return Some(item);
                     }
@@ -71,6 +111,81 @@ impl<T: Send + 'static> QueuedItem<T> {
                 }
             }
             QueuedItem::Ready(item) => return Some(item),
+            QueuedItem::Failed => None,
         }
     }
+
+    /// Like get, but without awaiting, for callers holding a std lock. A
+    /// finished task is resolved by polling it once, which can't block.
+    pub fn try_get(&mut self) -> Option<&mut T> {
+        if let QueuedItem::Generating(handle) = self {
+            if !handle.is_finished() {
+                return None;
+            }
+            let mut context = Context::from_waker(Waker::noop());
+            match Pin::new(handle).poll(&mut context) {
+                Poll::Ready(Ok(element)) => *self = QueuedItem::Ready(element),
+                Poll::Ready(Err(e)) => {
+                    eprintln!("Queued item failed to generate: {}", e);
+                    *self = QueuedItem::Failed;
+                }
+                // Tokio's cooperative budget can defer it to a later call
+                Poll::Pending => (),
+            }
+        }
+        match self {
+            QueuedItem::Ready(item) => Some(item),
+            _ => None,
+        }
+    }
+}
+
+/// Rolling average over the last few frame times, for an FPS counter
+pub struct FrameTimer {
+    frame_times: VecDeque<f32>,
+    capacity: usize,
+    total: f32,
+}
+
+impl FrameTimer {
+    /// Averages over the last `capacity` frames
+    pub fn new(capacity: usize) -> Self {
+        FrameTimer {
+            frame_times: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1),
+            total: 0.0,
+        }
+    }
+
+    pub fn record(&mut self, delta_time: f32) {
+        if self.frame_times.len() == self.capacity
+            && let Some(oldest) = self.frame_times.pop_front()
+        {
+            self.total -= oldest;
+        }
+        self.frame_times.push_back(delta_time);
+        self.total += delta_time;
+    }
+
+    /// Mean frame time in seconds, 0 until a frame has been recorded
+    pub fn average_frame_time(&self) -> f32 {
+        if self.frame_times.is_empty() {
+            return 0.0;
+        }
+        self.total / self.frame_times.len() as f32
+    }
+
+    pub fn fps(&self) -> f32 {
+        let average = self.average_frame_time();
+        if average > 0.0 { 1.0 / average } else { 0.0 }
+    }
+
+    /// Text spec with the FPS and the frame time in milliseconds
+    pub fn summary(&self) -> String {
+        format!(
+            "{:.0}  :  {:.1}",
+            self.fps(),
+            self.average_frame_time() * 1000.0
+        )
+    }
 }
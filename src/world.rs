@@ -1,26 +1,140 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeSet, HashMap, VecDeque},
+    fs,
     hash::{DefaultHasher, Hash, Hasher},
-    sync::{Arc, RwLock, RwLockWriteGuard},
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock, RwLockWriteGuard},
 };
 
 use rand::{Rng, SeedableRng};
 use simdnoise::NoiseBuilder;
 use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
 
-use crate::akasha::{self, Akasha, AkashaChunk, ChunkNoises};
+use crate::{
+    akasha::{
+        self, Akasha, ChunkNoises, TerrainConfig,
+        biome::Biome,
+        decoration::Decoration,
+        generator::{DefaultGenerator, WorldGenerator},
+    },
+    tile::TileRegistry,
+};
 
 pub const CHUNK_SIZE_X: i32 = 32;
 pub const CHUNK_SIZE: i32 = CHUNK_SIZE_X * CHUNK_SIZE_X * CHUNK_SIZE_X; // CHUNK_SIZE_XxCHUNK_SIZE_XxCHUNK_SIZE_X = 4096 blocks per chunk
 
-struct ChunkData {
-    pub block_ids: [u8; CHUNK_SIZE as usize],
+/// Brightest block light level, light loses one level for every block it spreads
+pub const MAX_LIGHT_LEVEL: u8 = 15;
+
+/// Type used for block ids everywhere, widen this to support more tile types
+pub type BlockId = u16;
+
+/// Items share the block id space, the item of a block has the block's id
+pub type ItemId = BlockId;
+
+// Header written at the start of every chunk file, followed by the RLE runs
+const CHUNK_FILE_MAGIC: &[u8; 4] = b"MKCH";
+const CHUNK_FILE_VERSION: u8 = 3;
+
+pub struct ChunkData {
+    pub block_ids: [BlockId; CHUNK_SIZE as usize],
+    pub metadata: [u8; CHUNK_SIZE as usize],
+    // Block light, computed by World::update_light and never saved
+    pub light_levels: [u8; CHUNK_SIZE as usize],
+}
+
+// Run-length encode `values` as (u16 run length, value) pairs
+fn encode_runs<T: Copy + PartialEq>(
+    values: &[T],
+    bytes: &mut Vec<u8>,
+    write_value: fn(T, &mut Vec<u8>),
+) {
+    let mut run_value = values[0];
+    let mut run_length: u16 = 0;
+    for &value in values.iter() {
+        if value == run_value && run_length < u16::MAX {
+            run_length += 1;
+        } else {
+            bytes.extend_from_slice(&run_length.to_le_bytes());
+            write_value(run_value, bytes);
+            run_value = value;
+            run_length = 1;
+        }
+    }
+    bytes.extend_from_slice(&run_length.to_le_bytes());
+    write_value(run_value, bytes);
+}
+
+// Decode runs until `values` is full, returning how many bytes were consumed
+fn decode_runs<T: Copy>(
+    bytes: &[u8],
+    values: &mut [T],
+    value_size: usize,
+    read_value: fn(&[u8]) -> T,
+) -> Result<usize, String> {
+    let run_size = 2 + value_size;
+    let mut index = 0;
+    let mut consumed = 0;
+    while index < values.len() {
+        let Some(run) = bytes.get(consumed..consumed + run_size) else {
+            return Err("Truncated chunk file".to_string());
+        };
+        let run_length = u16::from_le_bytes([run[0], run[1]]) as usize;
+        if index + run_length > values.len() {
+            return Err("Chunk file has too many blocks".to_string());
+        }
+        values[index..index + run_length].fill(read_value(&run[2..]));
+        index += run_length;
+        consumed += run_size;
+    }
+    Ok(consumed)
+}
+
+fn write_u8(value: u8, bytes: &mut Vec<u8>) {
+    bytes.push(value);
+}
+
+fn read_u8(bytes: &[u8]) -> u8 {
+    bytes[0]
+}
+
+fn write_block_id(value: BlockId, bytes: &mut Vec<u8>) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+fn read_block_id(bytes: &[u8]) -> BlockId {
+    BlockId::from_le_bytes(
+        bytes[..size_of::<BlockId>()]
+            .try_into()
+            .expect("Block id slice has the wrong length"),
+    )
 }
 
 impl ChunkData {
-    pub fn new(basis_x: i32, basis_y: i32, basis_z: i32, noises: &ChunkNoises) -> Self {
+    pub fn new(
+        basis_x: i32,
+        basis_y: i32,
+        basis_z: i32,
+        noises: &ChunkNoises,
+        config: &WorldConfig,
+    ) -> Self {
+        let sea_level = config.terrain.sea_level;
         let mut block_ids = [0; CHUNK_SIZE as usize];
 
+        // Sampled in global coordinates so caves continue across chunks
+        let (cave_noise, _, _) = NoiseBuilder::fbm_3d_offset(
+            (basis_x * CHUNK_SIZE_X) as f32,
+            CHUNK_SIZE_X as usize,
+            (basis_y * CHUNK_SIZE_X) as f32,
+            CHUNK_SIZE_X as usize,
+            (basis_z * CHUNK_SIZE_X) as f32,
+            CHUNK_SIZE_X as usize,
+        )
+        .with_freq(config.cave_frequency)
+        .with_octaves(3)
+        .with_seed(46)
+        .generate();
         // do some stuff for now using sine to generate some blocks
         for x in 0..CHUNK_SIZE_X {
             for y in 0..CHUNK_SIZE_X {
@@ -36,35 +150,46 @@ impl ChunkData {
 
                     let target_height =
                         noises.target_height[(x + z * CHUNK_SIZE_X) as usize];
+                    let biome = noises.biomes[(x + z * CHUNK_SIZE_X) as usize];
 
-                    let dirt_height = target_height + 2;
-                    let grass_height = dirt_height + 1;
+                    let grass_height = config.terrain.surface_height(target_height);
+                    let dirt_height = grass_height - 1;
 
+                    // Anything below the terrain is overwritten next, so this
+                    // only leaves water above it, up to a flat sea level
                     block_ids[index as usize] = 0;
-                    if global_y <= 0 {
+                    if global_y <= sea_level {
                         block_ids[index as usize] = 4;
                     }
                     if global_y == grass_height as i32 {
-                        if global_y >= 0 {
-                            block_ids[index as usize] = 3;
+                        if global_y >= sea_level {
+                            block_ids[index as usize] = biome.surface_block();
                         } else {
-                            block_ids[index as usize] = 2; // Dirt
+                            block_ids[index as usize] = biome.subsurface_block();
                         }
                     }
                     if global_y <= dirt_height as i32 {
-                        block_ids[index as usize] = 2;
+                        block_ids[index as usize] = biome.subsurface_block();
                     }
                     if global_y <= target_height as i32 {
                         block_ids[index as usize] = 1;
+                        // Caves only carve the stone, never the ground surface
+                        if cave_noise[index as usize] > config.cave_threshold {
+                            block_ids[index as usize] = 0;
+                        }
                     }
                 }
             }
         }
 
-        ChunkData { block_ids }
+        ChunkData {
+            block_ids,
+            metadata: [0; CHUNK_SIZE as usize],
+            light_levels: [0; CHUNK_SIZE as usize],
+        }
     }
 
-    pub fn set_block(&mut self, x: usize, y: usize, z: usize, block_id: u8) {
+    pub fn set_block(&mut self, x: usize, y: usize, z: usize, block_id: BlockId) {
         let usize_c = CHUNK_SIZE_X as usize;
         let index = x + y * usize_c + z * usize_c * usize_c;
         if index < (CHUNK_SIZE as usize) {
@@ -72,7 +197,7 @@ impl ChunkData {
         }
     }
 
-    pub fn get_block(&self, x: usize, y: usize, z: usize) -> u8 {
+    pub fn get_block(&self, x: usize, y: usize, z: usize) -> BlockId {
         let usize_c = CHUNK_SIZE_X as usize;
         let index = x + y * usize_c + z * usize_c * usize_c;
         if index < (CHUNK_SIZE as usize) {
@@ -81,10 +206,92 @@ impl ChunkData {
             0 // Return air or empty block
         }
     }
+
+    pub fn set_block_meta(&mut self, x: usize, y: usize, z: usize, metadata: u8) {
+        let usize_c = CHUNK_SIZE_X as usize;
+        let index = x + y * usize_c + z * usize_c * usize_c;
+        if index < (CHUNK_SIZE as usize) {
+            self.metadata[index] = metadata;
+        }
+    }
+
+    pub fn get_block_meta(&self, x: usize, y: usize, z: usize) -> u8 {
+        let usize_c = CHUNK_SIZE_X as usize;
+        let index = x + y * usize_c + z * usize_c * usize_c;
+        if index < (CHUNK_SIZE as usize) {
+            self.metadata[index]
+        } else {
+            0
+        }
+    }
+
+    /// Serialize the chunk into the on-disk chunk format.
+    /// The block id array (little endian `BlockId`s) and then the metadata
+    /// array are run-length encoded,
+    /// since most chunks are long runs of air or stone.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(CHUNK_FILE_MAGIC);
+        bytes.push(CHUNK_FILE_VERSION);
+        encode_runs(&self.block_ids, &mut bytes, write_block_id);
+        encode_runs(&self.metadata, &mut bytes, write_u8);
+        bytes
+    }
+
+    /// Rebuild chunk data from bytes produced by `to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let header_len = CHUNK_FILE_MAGIC.len() + 1;
+        if bytes.len() < header_len
+            || &bytes[..CHUNK_FILE_MAGIC.len()] != CHUNK_FILE_MAGIC
+        {
+            return Err("Not a chunk file".to_string());
+        }
+        let version = bytes[CHUNK_FILE_MAGIC.len()];
+        if version == 0 || version > CHUNK_FILE_VERSION {
+            return Err(format!("Unsupported chunk file version {}", version));
+        }
+
+        let runs = &bytes[header_len..];
+        let mut block_ids = [0; CHUNK_SIZE as usize];
+        let mut metadata = [0; CHUNK_SIZE as usize];
+        let mut consumed = if version >= 3 {
+            decode_runs(runs, &mut block_ids, size_of::<BlockId>(), read_block_id)?
+        } else {
+            // Older files stored block ids as single bytes
+            let mut narrow_ids = [0u8; CHUNK_SIZE as usize];
+            let consumed = decode_runs(runs, &mut narrow_ids, 1, read_u8)?;
+            for (block_id, narrow_id) in block_ids.iter_mut().zip(narrow_ids) {
+                *block_id = narrow_id as BlockId;
+            }
+            consumed
+        };
+        // Version 1 files predate metadata, so it stays zeroed
+        if version >= 2 {
+            consumed += decode_runs(&runs[consumed..], &mut metadata, 1, read_u8)?;
+        }
+        if consumed != runs.len() {
+            return Err("Trailing data in chunk file".to_string());
+        }
+
+        Ok(ChunkData {
+            block_ids,
+            metadata,
+            light_levels: [0; CHUNK_SIZE as usize],
+        })
+    }
 }
 
 pub struct ChunkState {
     pub data: Option<ChunkData>,
+    // Whether this chunk's own decorations have been written into the world
+    pub decorated: bool,
+    // Whether blocks were edited since the chunk was formed, loaded or saved.
+    // Such a chunk can't be generated again without losing the edits.
+    pub modified: bool,
+    // Bumped by every change to the blocks within light's reach of the chunk.
+    // The light in its data is up to date while lit_generation matches it.
+    pub light_generation: u64,
+    pub lit_generation: Option<u64>,
     pub x: i32,
     pub y: i32,
     pub z: i32,
@@ -94,25 +301,21 @@ impl ChunkState {
     pub fn new(x: i32, y: i32, z: i32) -> Self {
         Self {
             data: None,
+            decorated: false,
+            modified: false,
+            light_generation: 0,
+            lit_generation: None,
             x,
             y,
             z,
         }
     }
 
-    pub fn ensure_formed(&mut self, akasha_chunk: &AkashaChunk) {
-        if self.data.is_none() {
-            //let noises = self.noises.as_ref().expect("Noises must be initialized");
-            self.data =
-                Some(ChunkData::new(self.x, self.y, self.z, &akasha_chunk.noises));
-        }
-    }
-
     pub fn is_formed(&self) -> bool {
         self.data.is_some()
     }
 
-    pub fn set_block(&mut self, x: usize, y: usize, z: usize, block_id: u8) {
+    pub fn set_block(&mut self, x: usize, y: usize, z: usize, block_id: BlockId) {
         if let Some(data) = &mut self.data {
             data.set_block(x, y, z, block_id);
         } else {
@@ -120,30 +323,95 @@ impl ChunkState {
         }
     }
 
-    pub fn get_block(&self, x: usize, y: usize, z: usize) -> u8 {
+    pub fn get_block(&self, x: usize, y: usize, z: usize) -> BlockId {
         if let Some(data) = &self.data {
             data.get_block(x, y, z)
         } else {
             panic!("Chunk data must be initialized before getting a block");
         }
     }
+
+    pub fn set_block_meta(&mut self, x: usize, y: usize, z: usize, metadata: u8) {
+        if let Some(data) = &mut self.data {
+            data.set_block_meta(x, y, z, metadata);
+        } else {
+            panic!("Chunk data must be initialized before setting block metadata");
+        }
+    }
+
+    pub fn get_block_meta(&self, x: usize, y: usize, z: usize) -> u8 {
+        if let Some(data) = &self.data {
+            data.get_block_meta(x, y, z)
+        } else {
+            panic!("Chunk data must be initialized before getting block metadata");
+        }
+    }
 }
 
+// Chunks below the generator's guess surface_height searches before giving
+// up, deep enough to get past caves under the surface
+const SURFACE_SCAN_DEPTH: i32 = 4;
+
 pub struct ChunkUpdateMessage {
     pub world: Arc<World>,
     pub x: i32,
     pub y: i32,
     pub z: i32,
+    pub change: ChunkChange,
+}
+
+/// What changed inside the chunk a ChunkUpdateMessage is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkChange {
+    /// A single block at a world position, with its id before and after
+    Block {
+        position: (i32, i32, i32),
+        old_id: BlockId,
+        new_id: BlockId,
+    },
+    /// Any number of blocks, the whole chunk should be treated as dirty
+    Chunk,
+}
+
+/// Settings that shape world generation
+#[derive(Debug, Clone, Copy)]
+pub struct WorldConfig {
+    pub terrain: TerrainConfig,
+    /// Frequency of the 3D cave noise, lower values make larger caves
+    pub cave_frequency: f32,
+    /// Stone is carved out where the cave noise exceeds this, higher values
+    /// make caves rarer
+    pub cave_threshold: f32,
+}
+
+impl Default for WorldConfig {
+    fn default() -> Self {
+        WorldConfig {
+            terrain: TerrainConfig::default(),
+            cave_frequency: 1.0 / 32.0,
+            cave_threshold: 0.07,
+        }
+    }
 }
 
 pub struct World {
     pub chunks: Arc<RwLock<HashMap<(i32, i32, i32), Arc<RwLock<ChunkState>>>>>,
     pub chunk_update_listeners: Vec<UnboundedSender<ChunkUpdateMessage>>,
     pub akasha: Arc<Akasha>,
+    pub config: WorldConfig,
+    pub generator: Box<dyn WorldGenerator>,
 }
 
 impl World {
-    pub fn new() -> Self {
+    pub fn new(config: WorldConfig) -> Self {
+        Self::with_generator(config, Box::new(DefaultGenerator))
+    }
+
+    /// Creates a world whose terrain comes from `generator`
+    pub fn with_generator(
+        config: WorldConfig,
+        generator: Box<dyn WorldGenerator>,
+    ) -> Self {
         let mut colors = HashMap::new();
         // Set random colors for blocks
         let mut rng = rand::rng();
@@ -156,7 +424,9 @@ impl World {
         World {
             chunks: Arc::new(RwLock::new(HashMap::new())),
             chunk_update_listeners: Vec::new(),
-            akasha: Arc::new(Akasha::new()),
+            akasha: Arc::new(Akasha::new(config.terrain)),
+            config,
+            generator,
         }
     }
 
@@ -248,25 +518,175 @@ impl World {
         chunk_arcs
     }
 
+    /// Biome of the column at world coordinates (x, z)
+    pub fn biome_at(world: &Arc<World>, x: i32, z: i32) -> Biome {
+        let akasha_chunk = Akasha::ensure_chunk(
+            &world.akasha,
+            x.div_euclid(CHUNK_SIZE_X),
+            0,
+            z.div_euclid(CHUNK_SIZE_X),
+        );
+        let local_x = x.rem_euclid(CHUNK_SIZE_X);
+        let local_z = z.rem_euclid(CHUNK_SIZE_X);
+        akasha_chunk.read().unwrap().noises.biomes
+            [(local_x + local_z * CHUNK_SIZE_X) as usize]
+    }
+
+    /// Y of the highest solid block in the column at world coordinates (x, z),
+    /// forming the chunks it needs. The generator gives a first guess that is
+    /// then checked against the actual blocks, so trees and edits count.
+    /// Returns None if nothing solid is found down to a few chunks below it.
+    pub fn surface_height(
+        world: &Arc<World>,
+        tile_registry: &TileRegistry,
+        x: i32,
+        z: i32,
+    ) -> Option<i32> {
+        let terrain_height = world.generator.surface_guess(&world.akasha, x, z);
+        let is_solid = |block_id| {
+            tile_registry
+                .get_handler(block_id)
+                .is_some_and(|tile| tile.is_solid())
+        };
+
+        // Start a chunk above the guess and keep going up while the column is
+        // still solid at the top of the range
+        let mut top_chunk = terrain_height.div_euclid(CHUNK_SIZE_X) + 1;
+        while is_solid(Self::get_block(
+            world,
+            x,
+            (top_chunk + 1) * CHUNK_SIZE_X - 1,
+            z,
+        )) {
+            top_chunk += 1;
+        }
+        let bottom_chunk = terrain_height.div_euclid(CHUNK_SIZE_X) - SURFACE_SCAN_DEPTH;
+        for chunk_y in (bottom_chunk..=top_chunk).rev() {
+            let column: Vec<_> = (chunk_y * CHUNK_SIZE_X..(chunk_y + 1) * CHUNK_SIZE_X)
+                .rev()
+                .map(|y| (x, y, z))
+                .collect();
+            let block_ids = Self::get_blocks(world, &column);
+            if let Some(i) = block_ids.iter().position(|&block_id| is_solid(block_id)) {
+                return Some(column[i].1);
+            }
+        }
+        None
+    }
+
+    /// Returns the chunk formed and decorated
     pub fn get_chunk(
         world: &Arc<World>,
         x: i32,
         y: i32,
         z: i32,
+    ) -> Arc<RwLock<ChunkState>> {
+        let chunk_arc = Self::get_formed_chunk(world, x, y, z);
+        if !chunk_arc.read().unwrap().decorated {
+            Self::decorate_chunk(world, x, y, z);
+        }
+        chunk_arc
+    }
+
+    // Returns the chunk with its terrain generated but without decorations. The
+    // terrain is generated without holding the chunk's lock, so readers of other
+    // chunks aren't stalled behind it. Two threads may generate the same chunk at
+    // once, generation is deterministic so the first result is kept.
+    fn get_formed_chunk(
+        world: &Arc<World>,
+        x: i32,
+        y: i32,
+        z: i32,
     ) -> Arc<RwLock<ChunkState>> {
         let chunk_arc = Self::ensure_chunk(world, x, y, z);
+        if chunk_arc.read().unwrap().is_formed() {
+            return chunk_arc;
+        }
 
-        let akasha_chunk = Akasha::ensure_chunk(&world.akasha, x, y, z);
+        let data = world
+            .generator
+            .generate(x, y, z, &world.akasha, &world.config);
 
-        ChunkState::ensure_formed(
-            &mut chunk_arc.write().unwrap(),
-            &akasha_chunk.read().unwrap(),
-        );
+        let mut chunk_state = chunk_arc.write().unwrap();
+        if !chunk_state.is_formed() {
+            chunk_state.data = Some(data);
+        }
+        drop(chunk_state);
         chunk_arc
     }
 
+    // Writes the chunk's own decorations, which may reach as many chunks away as
+    // their extents need. Neighbors are formed first and every guard is taken in
+    // x, y, z order, the same order WorldView::from_range locks chunks in.
+    fn decorate_chunk(world: &Arc<World>, x: i32, y: i32, z: i32) {
+        if !world.generator.decorates() {
+            Self::ensure_chunk(world, x, y, z)
+                .write()
+                .unwrap()
+                .decorated = true;
+            return;
+        }
+        let akasha_chunk = Akasha::ensure_chunk(&world.akasha, x, y, z);
+        let akasha_chunk = akasha_chunk.read().unwrap();
+        let size = akasha_chunk.decorations.neighborhood_size();
+        // Holding on to the chunks keeps World::prune from dropping them before
+        // they are locked
+        let mut chunk_arcs = Vec::new();
+        for nx in x - size..=x + size {
+            for ny in y - size..=y + size {
+                for nz in z - size..=z + size {
+                    chunk_arcs.push(Self::get_formed_chunk(world, nx, ny, nz));
+                }
+            }
+        }
+
+        let mut neighborhood = Neighborhood {
+            center: (x, y, z),
+            size,
+            chunks: chunk_arcs
+                .iter()
+                .map(|chunk_arc| chunk_arc.write().unwrap())
+                .collect(),
+            written: BTreeSet::new(),
+        };
+        // Another task may have decorated it while we waited for the locks
+        let center = neighborhood
+            .get_chunk_immutable(x, y, z)
+            .expect("The center chunk is always in its neighborhood");
+        if center.decorated {
+            return;
+        }
+        for tree in &akasha_chunk.decorations.trees {
+            tree.decorate(&mut neighborhood);
+        }
+        for ore_vein in &akasha_chunk.decorations.ore_veins {
+            ore_vein.decorate(&mut neighborhood);
+        }
+        neighborhood
+            .get_chunk(x, y, z)
+            .expect("The center chunk is always in its neighborhood")
+            .decorated = true;
+
+        // Neighbours may already be meshed or have collision data built from
+        // their old blocks. Nothing can have read the center before it was
+        // decorated, so it needs no update.
+        let written = std::mem::take(&mut neighborhood.written);
+        drop(neighborhood);
+        for (chunk_x, chunk_y, chunk_z) in written {
+            if (chunk_x, chunk_y, chunk_z) != (x, y, z) {
+                Self::notify_chunk_update(
+                    world,
+                    chunk_x,
+                    chunk_y,
+                    chunk_z,
+                    ChunkChange::Chunk,
+                );
+            }
+        }
+    }
+
     /*
-    pub fn set_block(world: &Arc<World>, x: i32, y: i32, z: i32, block_id: u8) {
+    pub fn set_block(world: &Arc<World>, x: i32, y: i32, z: i32, block_id: BlockId) {
         let chunk_x = x.div_euclid(CHUNK_SIZE_X);
         let chunk_y = y.div_euclid(CHUNK_SIZE_X);
         let chunk_z = z.div_euclid(CHUNK_SIZE_X);
@@ -280,7 +700,7 @@ impl World {
     }
     */
 
-    pub fn get_block(world: &Arc<World>, x: i32, y: i32, z: i32) -> u8 {
+    pub fn get_block(world: &Arc<World>, x: i32, y: i32, z: i32) -> BlockId {
         let chunk_x = x.div_euclid(CHUNK_SIZE_X) as i32;
         let chunk_y = y.div_euclid(CHUNK_SIZE_X) as i32;
         let chunk_z = z.div_euclid(CHUNK_SIZE_X) as i32;
@@ -292,28 +712,489 @@ impl World {
         )
     }
 
-    pub fn set_block(world: &Arc<World>, x: i32, y: i32, z: i32, block_id: u8) {
+    /// Batched get_block, the result is in the same order as `positions`.
+    /// Positions are grouped by chunk so every chunk is formed and locked once.
+    pub fn get_blocks(
+        world: &Arc<World>,
+        positions: &[(i32, i32, i32)],
+    ) -> Vec<BlockId> {
+        let mut by_chunk: HashMap<(i32, i32, i32), Vec<usize>> = HashMap::new();
+        for (i, &(x, y, z)) in positions.iter().enumerate() {
+            let chunk_pos = (
+                x.div_euclid(CHUNK_SIZE_X),
+                y.div_euclid(CHUNK_SIZE_X),
+                z.div_euclid(CHUNK_SIZE_X),
+            );
+            by_chunk.entry(chunk_pos).or_default().push(i);
+        }
+
+        let mut block_ids = vec![0; positions.len()];
+        for ((chunk_x, chunk_y, chunk_z), indices) in by_chunk {
+            let chunk = Self::get_chunk(world, chunk_x, chunk_y, chunk_z);
+            let chunk_state = chunk.read().unwrap();
+            for i in indices {
+                let (x, y, z) = positions[i];
+                block_ids[i] = chunk_state.get_block(
+                    x.rem_euclid(CHUNK_SIZE_X) as usize,
+                    y.rem_euclid(CHUNK_SIZE_X) as usize,
+                    z.rem_euclid(CHUNK_SIZE_X) as usize,
+                );
+            }
+        }
+        block_ids
+    }
+
+    /// Batched set_block, writing each block id to its position with no
+    /// metadata. Every affected chunk is locked once and gets a single update
+    /// message once all of its blocks are set.
+    pub fn set_blocks(world: &Arc<World>, blocks: &[((i32, i32, i32), BlockId)]) {
+        let mut by_chunk: HashMap<(i32, i32, i32), Vec<usize>> = HashMap::new();
+        for (i, &((x, y, z), _)) in blocks.iter().enumerate() {
+            let chunk_pos = (
+                x.div_euclid(CHUNK_SIZE_X),
+                y.div_euclid(CHUNK_SIZE_X),
+                z.div_euclid(CHUNK_SIZE_X),
+            );
+            by_chunk.entry(chunk_pos).or_default().push(i);
+        }
+
+        for ((chunk_x, chunk_y, chunk_z), indices) in by_chunk {
+            let chunk = Self::get_chunk(world, chunk_x, chunk_y, chunk_z);
+            let mut chunk_state = chunk.write().unwrap();
+            let mut old_id = 0;
+            for &i in &indices {
+                let ((x, y, z), block_id) = blocks[i];
+                let local_x = x.rem_euclid(CHUNK_SIZE_X) as usize;
+                let local_y = y.rem_euclid(CHUNK_SIZE_X) as usize;
+                let local_z = z.rem_euclid(CHUNK_SIZE_X) as usize;
+                old_id = chunk_state.get_block(local_x, local_y, local_z);
+                chunk_state.set_block(local_x, local_y, local_z, block_id);
+                chunk_state.set_block_meta(local_x, local_y, local_z, 0);
+            }
+            chunk_state.modified = true;
+            drop(chunk_state);
+            // A chunk with a single edit gets it reported as such
+            let change = match indices[..] {
+                [i] => ChunkChange::Block {
+                    position: blocks[i].0,
+                    old_id,
+                    new_id: blocks[i].1,
+                },
+                _ => ChunkChange::Chunk,
+            };
+            Self::notify_chunk_update(world, chunk_x, chunk_y, chunk_z, change);
+        }
+    }
+
+    pub fn get_block_meta(world: &Arc<World>, x: i32, y: i32, z: i32) -> u8 {
         let chunk_x = x.div_euclid(CHUNK_SIZE_X);
         let chunk_y = y.div_euclid(CHUNK_SIZE_X);
         let chunk_z = z.div_euclid(CHUNK_SIZE_X);
         let chunk = Self::get_chunk(world, chunk_x, chunk_y, chunk_z);
-        let mut chunk_state = chunk.write().unwrap();
-        chunk_state.set_block(
+        chunk.read().unwrap().get_block_meta(
             x.rem_euclid(CHUNK_SIZE_X) as usize,
             y.rem_euclid(CHUNK_SIZE_X) as usize,
             z.rem_euclid(CHUNK_SIZE_X) as usize,
-            block_id,
-        );
+        )
+    }
+
+    /// Place a block with no metadata
+    pub fn set_block(world: &Arc<World>, x: i32, y: i32, z: i32, block_id: BlockId) {
+        Self::set_block_with_meta(world, x, y, z, block_id, 0);
+    }
+
+    pub fn set_block_with_meta(
+        world: &Arc<World>,
+        x: i32,
+        y: i32,
+        z: i32,
+        block_id: BlockId,
+        metadata: u8,
+    ) {
+        let chunk_x = x.div_euclid(CHUNK_SIZE_X);
+        let chunk_y = y.div_euclid(CHUNK_SIZE_X);
+        let chunk_z = z.div_euclid(CHUNK_SIZE_X);
+        let chunk = Self::get_chunk(world, chunk_x, chunk_y, chunk_z);
+        let mut chunk_state = chunk.write().unwrap();
+        let local_x = x.rem_euclid(CHUNK_SIZE_X) as usize;
+        let local_y = y.rem_euclid(CHUNK_SIZE_X) as usize;
+        let local_z = z.rem_euclid(CHUNK_SIZE_X) as usize;
+        let old_id = chunk_state.get_block(local_x, local_y, local_z);
+        chunk_state.set_block(local_x, local_y, local_z, block_id);
+        chunk_state.set_block_meta(local_x, local_y, local_z, metadata);
+        chunk_state.modified = true;
+        drop(chunk_state);
+        let change = ChunkChange::Block {
+            position: (x, y, z),
+            old_id,
+            new_id: block_id,
+        };
+        Self::notify_chunk_update(world, chunk_x, chunk_y, chunk_z, change);
+    }
+
+    /// Sets every block in the box from `min` to `max`, both inclusive, to
+    /// `block_id` with no metadata. Each affected chunk is locked once and gets
+    /// a single update message once all of its blocks are set.
+    pub fn fill(
+        world: &Arc<World>,
+        min: (i32, i32, i32),
+        max: (i32, i32, i32),
+        block_id: BlockId,
+    ) {
+        let (min_x, min_y, min_z) =
+            (min.0.min(max.0), min.1.min(max.1), min.2.min(max.2));
+        let (max_x, max_y, max_z) =
+            (min.0.max(max.0), min.1.max(max.1), min.2.max(max.2));
+        let chunks_x = min_x.div_euclid(CHUNK_SIZE_X)..=max_x.div_euclid(CHUNK_SIZE_X);
+        let chunks_y = min_y.div_euclid(CHUNK_SIZE_X)..=max_y.div_euclid(CHUNK_SIZE_X);
+        let chunks_z = min_z.div_euclid(CHUNK_SIZE_X)..=max_z.div_euclid(CHUNK_SIZE_X);
+        for chunk_x in chunks_x {
+            for chunk_y in chunks_y.clone() {
+                for chunk_z in chunks_z.clone() {
+                    // Part of the box inside this chunk, in world coordinates
+                    let chunk_min = (
+                        min_x.max(chunk_x * CHUNK_SIZE_X),
+                        min_y.max(chunk_y * CHUNK_SIZE_X),
+                        min_z.max(chunk_z * CHUNK_SIZE_X),
+                    );
+                    let chunk_max = (
+                        max_x.min(chunk_x * CHUNK_SIZE_X + CHUNK_SIZE_X - 1),
+                        max_y.min(chunk_y * CHUNK_SIZE_X + CHUNK_SIZE_X - 1),
+                        max_z.min(chunk_z * CHUNK_SIZE_X + CHUNK_SIZE_X - 1),
+                    );
+                    let chunk = Self::get_chunk(world, chunk_x, chunk_y, chunk_z);
+                    let mut chunk_state = chunk.write().unwrap();
+                    for x in chunk_min.0..=chunk_max.0 {
+                        for y in chunk_min.1..=chunk_max.1 {
+                            for z in chunk_min.2..=chunk_max.2 {
+                                let local_x = x.rem_euclid(CHUNK_SIZE_X) as usize;
+                                let local_y = y.rem_euclid(CHUNK_SIZE_X) as usize;
+                                let local_z = z.rem_euclid(CHUNK_SIZE_X) as usize;
+                                chunk_state
+                                    .set_block(local_x, local_y, local_z, block_id);
+                                chunk_state
+                                    .set_block_meta(local_x, local_y, local_z, 0);
+                            }
+                        }
+                    }
+                    chunk_state.modified = true;
+                    drop(chunk_state);
+                    Self::notify_chunk_update(
+                        world,
+                        chunk_x,
+                        chunk_y,
+                        chunk_z,
+                        ChunkChange::Chunk,
+                    );
+                }
+            }
+        }
+    }
+
+    /// Replace a block with air and return what it drops as (item id, count)
+    /// pairs, for the caller to hand out
+    pub fn break_block(
+        world: &Arc<World>,
+        tile_registry: &TileRegistry,
+        x: i32,
+        y: i32,
+        z: i32,
+    ) -> Vec<(ItemId, u8)> {
+        let chunk_x = x.div_euclid(CHUNK_SIZE_X);
+        let chunk_y = y.div_euclid(CHUNK_SIZE_X);
+        let chunk_z = z.div_euclid(CHUNK_SIZE_X);
+        let chunk = Self::get_chunk(world, chunk_x, chunk_y, chunk_z);
+        let mut chunk_state = chunk.write().unwrap();
+        let local_x = x.rem_euclid(CHUNK_SIZE_X) as usize;
+        let local_y = y.rem_euclid(CHUNK_SIZE_X) as usize;
+        let local_z = z.rem_euclid(CHUNK_SIZE_X) as usize;
+        let block_id = chunk_state.get_block(local_x, local_y, local_z);
+        if block_id == 0 {
+            return Vec::new();
+        }
+        let metadata = chunk_state.get_block_meta(local_x, local_y, local_z);
+        chunk_state.set_block(local_x, local_y, local_z, 0);
+        chunk_state.set_block_meta(local_x, local_y, local_z, 0);
+        chunk_state.modified = true;
+        drop(chunk_state);
+        let change = ChunkChange::Block {
+            position: (x, y, z),
+            old_id: block_id,
+            new_id: 0,
+        };
+        Self::notify_chunk_update(world, chunk_x, chunk_y, chunk_z, change);
+
+        tile_registry
+            .get_handler(block_id)
+            .map_or_else(Vec::new, |tile| tile.drops(block_id, metadata))
+    }
+
+    fn notify_chunk_update(
+        world: &Arc<World>,
+        x: i32,
+        y: i32,
+        z: i32,
+        change: ChunkChange,
+    ) {
+        Self::mark_light_stale(world, x, y, z, change);
         for listener in &world.chunk_update_listeners {
             let _ = listener.send(ChunkUpdateMessage {
                 world: Arc::clone(world),
-                x: chunk_x,
-                y: chunk_y,
-                z: chunk_z,
+                x,
+                y,
+                z,
+                change,
             });
         }
     }
 
+    // Light reaches MAX_LIGHT_LEVEL blocks, so a single block only changes the
+    // light of the chunks that close to it. Any other change may reach into
+    // every neighbour.
+    fn mark_light_stale(
+        world: &Arc<World>,
+        x: i32,
+        y: i32,
+        z: i32,
+        change: ChunkChange,
+    ) {
+        let (min, max) = match change {
+            ChunkChange::Block { position, .. } => {
+                let reach = MAX_LIGHT_LEVEL as i32;
+                (
+                    (
+                        (position.0 - reach).div_euclid(CHUNK_SIZE_X),
+                        (position.1 - reach).div_euclid(CHUNK_SIZE_X),
+                        (position.2 - reach).div_euclid(CHUNK_SIZE_X),
+                    ),
+                    (
+                        (position.0 + reach).div_euclid(CHUNK_SIZE_X),
+                        (position.1 + reach).div_euclid(CHUNK_SIZE_X),
+                        (position.2 + reach).div_euclid(CHUNK_SIZE_X),
+                    ),
+                )
+            }
+            ChunkChange::Chunk => ((x - 1, y - 1, z - 1), (x + 1, y + 1, z + 1)),
+        };
+        let chunks = world.chunks.read().unwrap();
+        for chunk_x in min.0..=max.0 {
+            for chunk_y in min.1..=max.1 {
+                for chunk_z in min.2..=max.2 {
+                    if let Some(chunk_arc) = chunks.get(&(chunk_x, chunk_y, chunk_z)) {
+                        chunk_arc.write().unwrap().light_generation += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Computes the chunk's block light and stores it in its data, unless it
+    /// is already up to date. Light spreads in from at most MAX_LIGHT_LEVEL
+    /// blocks away, so only the chunk and that border around it are filled.
+    pub async fn update_light(
+        world: &Arc<World>,
+        tile_registry: &TileRegistry,
+        x: i32,
+        y: i32,
+        z: i32,
+    ) {
+        let chunk_arc = Self::get_chunk(world, x, y, z);
+        let generation = {
+            let chunk_state = chunk_arc.read().unwrap();
+            if chunk_state.lit_generation == Some(chunk_state.light_generation) {
+                return;
+            }
+            chunk_state.light_generation
+        };
+
+        let border = MAX_LIGHT_LEVEL as i32;
+        let basis = (x * CHUNK_SIZE_X, y * CHUNK_SIZE_X, z * CHUNK_SIZE_X);
+        let mut view = WorldView::from_range(
+            world,
+            basis.0 - border,
+            basis.0 + CHUNK_SIZE_X - 1 + border,
+            basis.1 - border,
+            basis.1 + CHUNK_SIZE_X - 1 + border,
+            basis.2 - border,
+            basis.2 + CHUNK_SIZE_X - 1 + border,
+        )
+        .await;
+        view.compute_block_light(tile_registry);
+
+        let mut chunk_state = chunk_arc.write().unwrap();
+        // Blocks changed while the light was computed, the next update redoes it
+        if chunk_state.light_generation != generation {
+            return;
+        }
+        let data = chunk_state
+            .data
+            .as_mut()
+            .expect("Chunk data must be initialized before lighting it");
+        let view_size = CHUNK_SIZE_X + 2 * border;
+        let row_length = CHUNK_SIZE_X as usize;
+        for z in 0..CHUNK_SIZE_X {
+            for y in 0..CHUNK_SIZE_X {
+                let chunk_index =
+                    (y * CHUNK_SIZE_X + z * CHUNK_SIZE_X * CHUNK_SIZE_X) as usize;
+                let view_index = (border
+                    + (y + border) * view_size
+                    + (z + border) * view_size * view_size)
+                    as usize;
+                data.light_levels[chunk_index..chunk_index + row_length]
+                    .copy_from_slice(
+                        &view.light_levels[view_index..view_index + row_length],
+                    );
+            }
+        }
+        chunk_state.lit_generation = Some(generation);
+    }
+
+    /// Path of the file a chunk is stored in inside a save directory
+    pub fn chunk_file_path(dir: &Path, x: i32, y: i32, z: i32) -> PathBuf {
+        dir.join(format!("chunk_{}_{}_{}.bin", x, y, z))
+    }
+
+    /// Write a chunk to `dir`, forming it first if it was not generated yet
+    pub fn save_chunk(
+        world: &Arc<World>,
+        x: i32,
+        y: i32,
+        z: i32,
+        dir: &Path,
+    ) -> io::Result<()> {
+        let chunk = Self::get_chunk(world, x, y, z);
+        // Locked until the file is written so no edit slips in unsaved
+        let mut chunk_state = chunk.write().unwrap();
+        let bytes = chunk_state
+            .data
+            .as_ref()
+            .expect("Chunk data must be initialized before saving")
+            .to_bytes();
+        fs::create_dir_all(dir)?;
+        fs::write(Self::chunk_file_path(dir, x, y, z), bytes)?;
+        chunk_state.modified = false;
+        Ok(())
+    }
+
+    /// Load a chunk from `dir`, replacing whatever is in memory.
+    /// If there is no file for this chunk it is generated normally instead.
+    pub fn load_chunk(
+        world: &Arc<World>,
+        x: i32,
+        y: i32,
+        z: i32,
+        dir: &Path,
+    ) -> io::Result<Arc<RwLock<ChunkState>>> {
+        let bytes = match fs::read(Self::chunk_file_path(dir, x, y, z)) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Ok(Self::get_chunk(world, x, y, z));
+            }
+            Err(e) => return Err(e),
+        };
+        let data = ChunkData::from_bytes(&bytes)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        // Skip noise generation entirely, the file is the source of truth
+        let chunk_arc = Self::ensure_chunk(world, x, y, z);
+        let was_formed = {
+            let mut chunk_state = chunk_arc.write().unwrap();
+            let was_formed = chunk_state.is_formed();
+            chunk_state.data = Some(data);
+            // Saved chunks already contain their decorations
+            chunk_state.decorated = true;
+            chunk_state.modified = false;
+            was_formed
+        };
+
+        // Anything already built from the old data needs to be rebuilt
+        if was_formed {
+            Self::notify_chunk_update(world, x, y, z, ChunkChange::Chunk);
+        }
+        Ok(chunk_arc)
+    }
+
+    /// Write every formed chunk to `dir`
+    pub fn save_all(world: &Arc<World>, dir: &Path) -> io::Result<()> {
+        fs::create_dir_all(dir)?;
+        let chunk_arcs = world
+            .chunks
+            .read()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+        for chunk_arc in chunk_arcs {
+            let mut chunk_state = chunk_arc.write().unwrap();
+            if let Some(data) = &chunk_state.data {
+                fs::write(
+                    Self::chunk_file_path(
+                        dir,
+                        chunk_state.x,
+                        chunk_state.y,
+                        chunk_state.z,
+                    ),
+                    data.to_bytes(),
+                )?;
+                chunk_state.modified = false;
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether `pos` lies within `radius` chunks of `center` on every axis
+    pub fn chunk_in_radius(
+        (center_x, center_y, center_z): (i32, i32, i32),
+        (x, y, z): (i32, i32, i32),
+        radius: i32,
+    ) -> bool {
+        (x - center_x).abs() <= radius
+            && (y - center_y).abs() <= radius
+            && (z - center_z).abs() <= radius
+    }
+
+    /// Drops the chunks too far from `center` to be drawn, meshed or decorated
+    /// into, along with their generation data. `mesh_padding` is how many
+    /// blocks past its chunk a mesh reads. Chunks with unsaved edits and chunks
+    /// something is still loading or editing are kept.
+    pub fn prune(
+        world: &Arc<World>,
+        center: (i32, i32, i32),
+        render_distance: i32,
+        mesh_padding: i32,
+    ) {
+        let decoration_reach = world
+            .akasha
+            .chunks
+            .read()
+            .unwrap()
+            .values()
+            .map(|akasha_chunk| {
+                akasha_chunk.read().unwrap().decorations.neighborhood_size()
+            })
+            .max()
+            .unwrap_or(0);
+        let mesh_reach = (mesh_padding as u32).div_ceil(CHUNK_SIZE_X as u32) as i32;
+        let keep_radius = render_distance.max(mesh_reach).max(decoration_reach) + 1;
+
+        // Nothing can pick up a chunk while the map is locked, so a chunk only
+        // referenced by the map stays unused until it is removed
+        let mut chunks = world.chunks.write().unwrap();
+        chunks.retain(|&pos, chunk_arc| {
+            Self::chunk_in_radius(center, pos, keep_radius)
+                || Arc::strong_count(chunk_arc) > 1
+                || chunk_arc.read().unwrap().modified
+        });
+        world
+            .akasha
+            .chunks
+            .write()
+            .unwrap()
+            .retain(|pos, akasha_chunk| {
+                chunks.contains_key(pos) || Arc::strong_count(akasha_chunk) > 1
+            });
+    }
+
     pub fn register_chunk_update_listener(
         &mut self,
     ) -> UnboundedReceiver<ChunkUpdateMessage> {
@@ -323,10 +1204,99 @@ impl World {
     }
 }
 
+/// Write access to the cube of chunks within `size` chunks of `center`, addressed
+/// with world coordinates so decorations can cross chunk borders
+pub struct Neighborhood<'a> {
+    pub center: (i32, i32, i32),
+    pub size: i32,
+    // Guards in x, y, z order, 2 * size + 1 chunks along every axis
+    pub chunks: Vec<RwLockWriteGuard<'a, ChunkState>>,
+    // Chunks set_block wrote to, so their listeners can be told afterwards
+    written: BTreeSet<(i32, i32, i32)>,
+}
+
+impl<'a> Neighborhood<'a> {
+    // None when the chunk is outside the neighborhood
+    fn chunk_index(&self, chunk_x: i32, chunk_y: i32, chunk_z: i32) -> Option<usize> {
+        let side = 2 * self.size + 1;
+        let offset_x = chunk_x - self.center.0 + self.size;
+        let offset_y = chunk_y - self.center.1 + self.size;
+        let offset_z = chunk_z - self.center.2 + self.size;
+        if !(0..side).contains(&offset_x)
+            || !(0..side).contains(&offset_y)
+            || !(0..side).contains(&offset_z)
+        {
+            return None;
+        }
+        Some(((offset_x * side + offset_y) * side + offset_z) as usize)
+    }
+
+    pub fn get_chunk(
+        &mut self,
+        chunk_x: i32,
+        chunk_y: i32,
+        chunk_z: i32,
+    ) -> Option<&mut RwLockWriteGuard<'a, ChunkState>> {
+        let index = self.chunk_index(chunk_x, chunk_y, chunk_z)?;
+        Some(&mut self.chunks[index])
+    }
+
+    pub fn get_chunk_immutable(
+        &self,
+        chunk_x: i32,
+        chunk_y: i32,
+        chunk_z: i32,
+    ) -> Option<&RwLockWriteGuard<'a, ChunkState>> {
+        let index = self.chunk_index(chunk_x, chunk_y, chunk_z)?;
+        Some(&self.chunks[index])
+    }
+
+    /// Writes a block, blocks outside the neighborhood are skipped
+    pub fn set_block(&mut self, x: i32, y: i32, z: i32, block_id: BlockId) {
+        let chunk_pos = (
+            x.div_euclid(CHUNK_SIZE_X),
+            y.div_euclid(CHUNK_SIZE_X),
+            z.div_euclid(CHUNK_SIZE_X),
+        );
+        let Some(chunk) = self.get_chunk(chunk_pos.0, chunk_pos.1, chunk_pos.2) else {
+            return;
+        };
+        chunk.set_block(
+            x.rem_euclid(CHUNK_SIZE_X) as usize,
+            y.rem_euclid(CHUNK_SIZE_X) as usize,
+            z.rem_euclid(CHUNK_SIZE_X) as usize,
+            block_id,
+        );
+        self.written.insert(chunk_pos);
+    }
+
+    /// Reads a block, blocks outside the neighborhood read as air
+    pub fn get_block(&self, x: i32, y: i32, z: i32) -> BlockId {
+        let Some(chunk) = self.get_chunk_immutable(
+            x.div_euclid(CHUNK_SIZE_X),
+            y.div_euclid(CHUNK_SIZE_X),
+            z.div_euclid(CHUNK_SIZE_X),
+        ) else {
+            return 0;
+        };
+        chunk.get_block(
+            x.rem_euclid(CHUNK_SIZE_X) as usize,
+            y.rem_euclid(CHUNK_SIZE_X) as usize,
+            z.rem_euclid(CHUNK_SIZE_X) as usize,
+        )
+    }
+}
+
 pub struct WorldView {
-    pub data: Vec<u8>,
+    pub data: Vec<BlockId>,
+    pub metadata: Vec<u8>,
+    // Block light per block, as stored in the chunks or by compute_block_light
+    pub light_levels: Vec<u8>,
     pub origin: (i32, i32, i32),
     pub size: (i32, i32, i32),
+    // Indices of the blocks written with set_block since the view was made,
+    // these are what commit copies back into the world
+    changed: BTreeSet<usize>,
 }
 
 impl WorldView {
@@ -359,7 +1329,9 @@ impl WorldView {
 
         // Pre-allocate the data array
         let total_blocks = (size_x * size_y * size_z) as usize;
-        let mut data = vec![0u8; total_blocks];
+        let mut data = vec![0; total_blocks];
+        let mut metadata = vec![0u8; total_blocks];
+        let mut light_levels = vec![0u8; total_blocks];
 
         // Get all required chunks using get_chunk to ensure proper decoration
         let mut chunk_arcs = Vec::new();
@@ -372,19 +1344,6 @@ impl WorldView {
             }
         }
 
-        // Create a map for fast chunk lookup
-        let mut chunk_map = std::collections::HashMap::new();
-        let mut chunk_index = 0;
-
-        for chunk_x in chunk_start_x..=chunk_end_x {
-            for chunk_y in chunk_start_y..=chunk_end_y {
-                for chunk_z in chunk_start_z..=chunk_end_z {
-                    chunk_map.insert((chunk_x, chunk_y, chunk_z), chunk_index);
-                    chunk_index += 1;
-                }
-            }
-        }
-
         // Lock all chunks and extract data (they're already formed and decorated)
         let mut chunk_guards = Vec::new();
         for chunk_arc in &chunk_arcs {
@@ -392,39 +1351,49 @@ impl WorldView {
             chunk_guards.push(guard);
         }
 
-        // Copy block data from chunks to our view
-        for x in start_x..=end_x {
-            for y in start_y..=end_y {
-                for z in start_z..=end_z {
-                    let chunk_x = x.div_euclid(CHUNK_SIZE_X);
-                    let chunk_y = y.div_euclid(CHUNK_SIZE_X);
-                    let chunk_z = z.div_euclid(CHUNK_SIZE_X);
-
-                    let chunk_local_x = x.rem_euclid(CHUNK_SIZE_X) as usize;
-                    let chunk_local_y = y.rem_euclid(CHUNK_SIZE_X) as usize;
-                    let chunk_local_z = z.rem_euclid(CHUNK_SIZE_X) as usize;
-
-                    // Find the chunk in our map
-                    if let Some(&chunk_idx) =
-                        chunk_map.get(&(chunk_x, chunk_y, chunk_z))
-                    {
-                        let chunk_guard = &chunk_guards[chunk_idx];
-                        let block_id = chunk_guard.get_block(
-                            chunk_local_x,
-                            chunk_local_y,
-                            chunk_local_z,
-                        );
-
-                        // Calculate index in our view data
-                        let view_x = x - start_x;
-                        let view_y = y - start_y;
-                        let view_z = z - start_z;
-                        let view_index =
-                            (view_x + view_y * size_x + view_z * size_x * size_y)
-                                as usize;
-
-                        data[view_index] = block_id;
-                    }
+        // Copy the part of every chunk overlapping the view. Chunks and views
+        // both store x innermost, so each row along x is one slice copy.
+        let chunk_positions = (chunk_start_x..=chunk_end_x).flat_map(|chunk_x| {
+            (chunk_start_y..=chunk_end_y).flat_map(move |chunk_y| {
+                (chunk_start_z..=chunk_end_z)
+                    .map(move |chunk_z| (chunk_x, chunk_y, chunk_z))
+            })
+        });
+        for ((chunk_x, chunk_y, chunk_z), chunk_guard) in
+            chunk_positions.zip(&chunk_guards)
+        {
+            let chunk_data = chunk_guard
+                .data
+                .as_ref()
+                .expect("Chunk data must be initialized before viewing it");
+            let basis = (
+                chunk_x * CHUNK_SIZE_X,
+                chunk_y * CHUNK_SIZE_X,
+                chunk_z * CHUNK_SIZE_X,
+            );
+            // Overlap of the chunk and the view, in world coordinates
+            let min_x = start_x.max(basis.0);
+            let max_x = end_x.min(basis.0 + CHUNK_SIZE_X - 1);
+            let row_length = (max_x - min_x + 1) as usize;
+            for z in start_z.max(basis.2)..=end_z.min(basis.2 + CHUNK_SIZE_X - 1) {
+                for y in start_y.max(basis.1)..=end_y.min(basis.1 + CHUNK_SIZE_X - 1) {
+                    let chunk_index = ((min_x - basis.0)
+                        + (y - basis.1) * CHUNK_SIZE_X
+                        + (z - basis.2) * CHUNK_SIZE_X * CHUNK_SIZE_X)
+                        as usize;
+                    let view_index = ((min_x - start_x)
+                        + (y - start_y) * size_x
+                        + (z - start_z) * size_x * size_y)
+                        as usize;
+                    data[view_index..view_index + row_length].copy_from_slice(
+                        &chunk_data.block_ids[chunk_index..chunk_index + row_length],
+                    );
+                    metadata[view_index..view_index + row_length].copy_from_slice(
+                        &chunk_data.metadata[chunk_index..chunk_index + row_length],
+                    );
+                    light_levels[view_index..view_index + row_length].copy_from_slice(
+                        &chunk_data.light_levels[chunk_index..chunk_index + row_length],
+                    );
                 }
             }
         }
@@ -434,14 +1403,17 @@ impl WorldView {
 
         WorldView {
             data,
+            metadata,
+            light_levels,
             origin: (start_x, start_y, start_z),
             size: (size_x, size_y, size_z),
+            changed: BTreeSet::new(),
         }
     }
 
     /// Get a block at the given world coordinates
     /// Returns 0 (air) if the coordinates are outside the view bounds
-    pub fn get_block(&self, x: i32, y: i32, z: i32) -> u8 {
+    pub fn get_block(&self, x: i32, y: i32, z: i32) -> BlockId {
         // Check if coordinates are within bounds
         let (origin_x, origin_y, origin_z) = self.origin;
         let (size_x, size_y, size_z) = self.size;
@@ -468,6 +1440,192 @@ impl WorldView {
         self.data[index]
     }
 
+    /// Get the metadata of the block at the given world coordinates
+    /// Returns 0 if the coordinates are outside the view bounds
+    pub fn get_block_meta(&self, x: i32, y: i32, z: i32) -> u8 {
+        if !self.contains(x, y, z) {
+            return 0;
+        }
+        let (origin_x, origin_y, origin_z) = self.origin;
+        let (size_x, size_y, _) = self.size;
+        let local_x = x - origin_x;
+        let local_y = y - origin_y;
+        let local_z = z - origin_z;
+        let index = (local_x + local_y * size_x + local_z * size_x * size_y) as usize;
+        self.metadata[index]
+    }
+
+    /// Place a block with no metadata in the view, see set_block_with_meta
+    pub fn set_block(&mut self, x: i32, y: i32, z: i32, block_id: BlockId) {
+        self.set_block_with_meta(x, y, z, block_id, 0);
+    }
+
+    /// Change a block in the view only, the world sees it once the view is
+    /// committed. Block light is not updated. Panics outside the view bounds.
+    pub fn set_block_with_meta(
+        &mut self,
+        x: i32,
+        y: i32,
+        z: i32,
+        block_id: BlockId,
+        metadata: u8,
+    ) {
+        if !self.contains(x, y, z) {
+            panic!(
+                "Block ({}, {}, {}) is outside the view {:?}",
+                x,
+                y,
+                z,
+                self.bounds()
+            );
+        }
+        let (origin_x, origin_y, origin_z) = self.origin;
+        let (size_x, size_y, _) = self.size;
+        let local_x = x - origin_x;
+        let local_y = y - origin_y;
+        let local_z = z - origin_z;
+        let index = (local_x + local_y * size_x + local_z * size_x * size_y) as usize;
+        self.data[index] = block_id;
+        self.metadata[index] = metadata;
+        self.changed.insert(index);
+    }
+
+    /// Writes every block changed with set_block back into the world. Each
+    /// affected chunk is locked once and gets a single update message.
+    pub fn commit(self, world: &Arc<World>) {
+        let mut by_chunk: HashMap<(i32, i32, i32), Vec<usize>> = HashMap::new();
+        for &index in &self.changed {
+            let (x, y, z) = self.world_position(index);
+            let chunk_pos = (
+                x.div_euclid(CHUNK_SIZE_X),
+                y.div_euclid(CHUNK_SIZE_X),
+                z.div_euclid(CHUNK_SIZE_X),
+            );
+            by_chunk.entry(chunk_pos).or_default().push(index);
+        }
+
+        for ((chunk_x, chunk_y, chunk_z), indices) in by_chunk {
+            let chunk = World::get_chunk(world, chunk_x, chunk_y, chunk_z);
+            let mut chunk_state = chunk.write().unwrap();
+            let mut old_id = 0;
+            for &index in &indices {
+                let (x, y, z) = self.world_position(index);
+                let local_x = x.rem_euclid(CHUNK_SIZE_X) as usize;
+                let local_y = y.rem_euclid(CHUNK_SIZE_X) as usize;
+                let local_z = z.rem_euclid(CHUNK_SIZE_X) as usize;
+                old_id = chunk_state.get_block(local_x, local_y, local_z);
+                chunk_state.set_block(local_x, local_y, local_z, self.data[index]);
+                chunk_state.set_block_meta(
+                    local_x,
+                    local_y,
+                    local_z,
+                    self.metadata[index],
+                );
+            }
+            chunk_state.modified = true;
+            drop(chunk_state);
+            // A chunk with a single edit gets it reported as such
+            let change = match indices[..] {
+                [index] => ChunkChange::Block {
+                    position: self.world_position(index),
+                    old_id,
+                    new_id: self.data[index],
+                },
+                _ => ChunkChange::Chunk,
+            };
+            World::notify_chunk_update(world, chunk_x, chunk_y, chunk_z, change);
+        }
+    }
+
+    // World coordinates of the block at `index` in the view's arrays
+    fn world_position(&self, index: usize) -> (i32, i32, i32) {
+        let (origin_x, origin_y, origin_z) = self.origin;
+        let (size_x, size_y, _) = self.size;
+        let index = index as i32;
+        (
+            origin_x + index % size_x,
+            origin_y + (index / size_x) % size_y,
+            origin_z + index / (size_x * size_y),
+        )
+    }
+
+    /// Flood fills block light out of every emitting tile in the view. Light
+    /// only travels MAX_LIGHT_LEVEL blocks, so a view padded by that much around
+    /// a chunk gets the chunk's light right even when it comes from a neighbour.
+    pub fn compute_block_light(&mut self, tile_registry: &TileRegistry) {
+        let (size_x, size_y, size_z) = self.size;
+        let mut light_levels = vec![0u8; self.data.len()];
+        let mut queue = VecDeque::new();
+        for (index, &block_id) in self.data.iter().enumerate() {
+            let emission = tile_registry
+                .get_handler(block_id)
+                .map_or(0, |tile| tile.light_emission().min(MAX_LIGHT_LEVEL));
+            if emission > 0 {
+                light_levels[index] = emission;
+                queue.push_back(index);
+            }
+        }
+
+        while let Some(index) = queue.pop_front() {
+            let level = light_levels[index];
+            if level <= 1 {
+                continue;
+            }
+            let x = index as i32 % size_x;
+            let y = (index as i32 / size_x) % size_y;
+            let z = index as i32 / (size_x * size_y);
+            for (dx, dy, dz) in [
+                (1, 0, 0),
+                (-1, 0, 0),
+                (0, 1, 0),
+                (0, -1, 0),
+                (0, 0, 1),
+                (0, 0, -1),
+            ] {
+                let (neighbor_x, neighbor_y, neighbor_z) = (x + dx, y + dy, z + dz);
+                if neighbor_x < 0
+                    || neighbor_x >= size_x
+                    || neighbor_y < 0
+                    || neighbor_y >= size_y
+                    || neighbor_z < 0
+                    || neighbor_z >= size_z
+                {
+                    continue;
+                }
+                let neighbor =
+                    (neighbor_x + neighbor_y * size_x + neighbor_z * size_x * size_y)
+                        as usize;
+                if light_levels[neighbor] >= level - 1 {
+                    continue;
+                }
+                let blocks_light = tile_registry
+                    .get_handler(self.data[neighbor])
+                    .is_some_and(|tile| tile.blocks_light());
+                if blocks_light {
+                    continue;
+                }
+                light_levels[neighbor] = level - 1;
+                queue.push_back(neighbor);
+            }
+        }
+        self.light_levels = light_levels;
+    }
+
+    /// Block light level at the given world coordinates. Returns 0 outside the
+    /// view bounds.
+    pub fn get_light(&self, x: i32, y: i32, z: i32) -> u8 {
+        if !self.contains(x, y, z) || self.light_levels.is_empty() {
+            return 0;
+        }
+        let (origin_x, origin_y, origin_z) = self.origin;
+        let (size_x, size_y, _) = self.size;
+        let local_x = x - origin_x;
+        let local_y = y - origin_y;
+        let local_z = z - origin_z;
+        let index = (local_x + local_y * size_x + local_z * size_x * size_y) as usize;
+        self.light_levels[index]
+    }
+
     /// Check if the given world coordinates are within the view bounds
     pub fn contains(&self, x: i32, y: i32, z: i32) -> bool {
         let (origin_x, origin_y, origin_z) = self.origin;
@@ -496,7 +1654,7 @@ impl WorldView {
     }
 
     /// Iterate over all blocks in the view
-    pub fn iter_blocks(&self) -> impl Iterator<Item = (i32, i32, i32, u8)> + '_ {
+    pub fn iter_blocks(&self) -> impl Iterator<Item = (i32, i32, i32, BlockId)> + '_ {
         let (origin_x, origin_y, origin_z) = self.origin;
         let (size_x, size_y, size_z) = self.size;
 
@@ -513,3 +1671,301 @@ impl WorldView {
         })
     }
 }
+
+// Inclusive minimum and maximum block positions of a view
+type ViewBounds = ((i32, i32, i32), (i32, i32, i32));
+
+// A cached view and the use it was last handed out at, for LRU eviction
+struct CachedView {
+    view: Arc<WorldView>,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct WorldViewCacheState {
+    // Keyed by the view bounds, inclusive on both ends like WorldView::bounds
+    views: HashMap<ViewBounds, CachedView>,
+    uses: u64,
+    // Bumped for every chunk update, views loaded across one aren't kept
+    invalidations: u64,
+}
+
+/// Keeps recently loaded views around so requests for the same range, like
+/// several LODs of one chunk, copy the chunks once. Listens for chunk updates
+/// and drops every view overlapping an updated chunk.
+pub struct WorldViewCache {
+    capacity: usize,
+    chunk_updates: Mutex<UnboundedReceiver<ChunkUpdateMessage>>,
+    state: Mutex<WorldViewCacheState>,
+}
+
+impl WorldViewCache {
+    /// Holds at most `capacity` views, `chunk_updates` should come from
+    /// World::register_chunk_update_listener
+    pub fn new(
+        capacity: usize,
+        chunk_updates: UnboundedReceiver<ChunkUpdateMessage>,
+    ) -> Self {
+        Self {
+            capacity,
+            chunk_updates: Mutex::new(chunk_updates),
+            state: Mutex::new(WorldViewCacheState::default()),
+        }
+    }
+
+    /// The cached view for `bounds`, or the one `load` makes, which is cached
+    /// unless a chunk changed while it was being made.
+    pub async fn get_or_load<F, Fut>(
+        &self,
+        bounds: ViewBounds,
+        load: F,
+    ) -> Arc<WorldView>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = WorldView>,
+    {
+        let invalidations = {
+            let mut state = self.state.lock().unwrap();
+            self.apply_chunk_updates(&mut state);
+            state.uses += 1;
+            let uses = state.uses;
+            if let Some(cached) = state.views.get_mut(&bounds) {
+                cached.last_used = uses;
+                return Arc::clone(&cached.view);
+            }
+            state.invalidations
+        };
+
+        let view = Arc::new(load().await);
+
+        let mut state = self.state.lock().unwrap();
+        self.apply_chunk_updates(&mut state);
+        if state.invalidations == invalidations && self.capacity > 0 {
+            if state.views.len() >= self.capacity {
+                let least_recent = state
+                    .views
+                    .iter()
+                    .min_by_key(|(_, cached)| cached.last_used)
+                    .map(|(bounds, _)| *bounds);
+                if let Some(least_recent) = least_recent {
+                    state.views.remove(&least_recent);
+                }
+            }
+            let last_used = state.uses;
+            state.views.insert(
+                bounds,
+                CachedView {
+                    view: Arc::clone(&view),
+                    last_used,
+                },
+            );
+        }
+        view
+    }
+
+    // Drops the views overlapping any chunk updated since the last call
+    fn apply_chunk_updates(&self, state: &mut WorldViewCacheState) {
+        let mut chunk_updates = self.chunk_updates.lock().unwrap();
+        while let Ok(update) = chunk_updates.try_recv() {
+            state.invalidations += 1;
+            let chunk_min = (
+                update.x * CHUNK_SIZE_X,
+                update.y * CHUNK_SIZE_X,
+                update.z * CHUNK_SIZE_X,
+            );
+            let chunk_max = (
+                chunk_min.0 + CHUNK_SIZE_X - 1,
+                chunk_min.1 + CHUNK_SIZE_X - 1,
+                chunk_min.2 + CHUNK_SIZE_X - 1,
+            );
+            state.views.retain(|&(min, max), _| {
+                max.0 < chunk_min.0
+                    || max.1 < chunk_min.1
+                    || max.2 < chunk_min.2
+                    || min.0 > chunk_max.0
+                    || min.1 > chunk_max.1
+                    || min.2 > chunk_max.2
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{akasha::generator::FlatGenerator, tile::blocks};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // The view as World::get_block and get_block_meta see it, one block at a time
+    fn blocks_one_by_one(
+        world: &Arc<World>,
+        start: (i32, i32, i32),
+        end: (i32, i32, i32),
+    ) -> (Vec<BlockId>, Vec<u8>) {
+        let mut data = Vec::new();
+        let mut metadata = Vec::new();
+        for z in start.2..=end.2 {
+            for y in start.1..=end.1 {
+                for x in start.0..=end.0 {
+                    data.push(World::get_block(world, x, y, z));
+                    metadata.push(World::get_block_meta(world, x, y, z));
+                }
+            }
+        }
+        (data, metadata)
+    }
+
+    #[tokio::test]
+    async fn views_match_the_blocks_one_by_one() {
+        let world = Arc::new(World::with_generator(
+            WorldConfig::default(),
+            Box::new(FlatGenerator::default()),
+        ));
+        // Scattered edits with metadata, so every row of a view differs
+        for i in (-45..45).step_by(3) {
+            let position = (i, i / 2, -i);
+            World::set_block_with_meta(
+                &world,
+                position.0,
+                position.1,
+                position.2,
+                blocks::STONE,
+                3,
+            );
+            World::set_block_with_meta(
+                &world,
+                -i,
+                0,
+                i / 3,
+                blocks::DIRT,
+                (i & 7) as u8,
+            );
+        }
+
+        let ranges = [
+            // One whole chunk
+            ((0, 0, 0), (31, 31, 31)),
+            // A chunk padded on every side, like the tessellator's views
+            ((-2, -2, -2), (33, 33, 33)),
+            // Negative coordinates, across several chunk borders
+            ((-70, -40, -35), (-1, 5, 2)),
+            // Thin slices around the origin
+            ((-45, 0, 0), (45, 0, 0)),
+            ((0, -45, 0), (0, 45, 0)),
+            ((-1, -1, -45), (0, 0, 45)),
+        ];
+        for (start, end) in ranges {
+            let view = WorldView::from_range(
+                &world, start.0, end.0, start.1, end.1, start.2, end.2,
+            )
+            .await;
+            let (data, metadata) = blocks_one_by_one(&world, start, end);
+            assert!(
+                view.data == data,
+                "blocks differ in {:?}..={:?}",
+                start,
+                end
+            );
+            assert!(
+                view.metadata == metadata,
+                "metadata differs in {:?}..={:?}",
+                start,
+                end
+            );
+        }
+    }
+
+    #[test]
+    fn flat_worlds_find_their_surface_without_noise() {
+        let generator = FlatGenerator {
+            surface_height: 200,
+            dirt_depth: 2,
+        };
+        let world = Arc::new(World::with_generator(
+            WorldConfig::default(),
+            Box::new(generator),
+        ));
+        let tile_registry = TileRegistry::new();
+        assert_eq!(
+            World::surface_height(&world, &tile_registry, -5, 7),
+            Some(200)
+        );
+        assert_eq!(World::get_block(&world, -5, 200, 7), blocks::GRASS);
+        assert!(world.akasha.chunks.read().unwrap().is_empty());
+    }
+
+    fn cached_world(capacity: usize) -> (Arc<World>, WorldViewCache) {
+        let mut world = World::with_generator(
+            WorldConfig::default(),
+            Box::new(FlatGenerator::default()),
+        );
+        let cache = WorldViewCache::new(capacity, world.register_chunk_update_listener());
+        (Arc::new(world), cache)
+    }
+
+    // Gets the view for `bounds` from the cache, counting the loads it needed
+    async fn get(
+        cache: &WorldViewCache,
+        world: &Arc<World>,
+        (min, max): ViewBounds,
+        loads: &AtomicUsize,
+    ) -> Arc<WorldView> {
+        cache
+            .get_or_load((min, max), || async {
+                loads.fetch_add(1, Ordering::Relaxed);
+                WorldView::from_range(world, min.0, max.0, min.1, max.1, min.2, max.2)
+                    .await
+            })
+            .await
+    }
+
+    const FIRST: ViewBounds = ((0, 0, 0), (3, 3, 3));
+    const SECOND: ViewBounds = ((40, 0, 0), (43, 3, 3));
+    const THIRD: ViewBounds = ((80, 0, 0), (83, 3, 3));
+
+    #[tokio::test]
+    async fn views_are_reused_once_loaded() {
+        let (world, cache) = cached_world(4);
+        let loads = AtomicUsize::new(0);
+        let first = get(&cache, &world, FIRST, &loads).await;
+        let again = get(&cache, &world, FIRST, &loads).await;
+        assert_eq!(loads.load(Ordering::Relaxed), 1);
+        assert!(Arc::ptr_eq(&first, &again));
+
+        get(&cache, &world, SECOND, &loads).await;
+        assert_eq!(loads.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn least_recently_used_view_is_evicted_at_capacity() {
+        let (world, cache) = cached_world(2);
+        let loads = AtomicUsize::new(0);
+        get(&cache, &world, FIRST, &loads).await;
+        get(&cache, &world, SECOND, &loads).await;
+        // Using the first view again leaves the second as the least recent
+        get(&cache, &world, FIRST, &loads).await;
+        get(&cache, &world, THIRD, &loads).await;
+        assert_eq!(loads.load(Ordering::Relaxed), 3);
+
+        get(&cache, &world, FIRST, &loads).await;
+        assert_eq!(loads.load(Ordering::Relaxed), 3);
+        get(&cache, &world, SECOND, &loads).await;
+        assert_eq!(loads.load(Ordering::Relaxed), 4);
+    }
+
+    #[tokio::test]
+    async fn chunk_updates_invalidate_overlapping_views() {
+        let (world, cache) = cached_world(4);
+        let loads = AtomicUsize::new(0);
+        get(&cache, &world, FIRST, &loads).await;
+        get(&cache, &world, SECOND, &loads).await;
+
+        // Only the chunk holding the first view changes
+        World::set_block(&world, 1, 1, 1, blocks::LAMP);
+        let first = get(&cache, &world, FIRST, &loads).await;
+        assert_eq!(loads.load(Ordering::Relaxed), 3);
+        assert_eq!(first.get_block(1, 1, 1), blocks::LAMP);
+        get(&cache, &world, SECOND, &loads).await;
+        assert_eq!(loads.load(Ordering::Relaxed), 3);
+    }
+}
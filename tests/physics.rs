@@ -0,0 +1,109 @@
+//! Collision behaviour on a flat world, where every position is predictable.
+//! The tests run on tokio's default worker stacks, which collision chunks have
+//! to be built within in debug builds too.
+
+use std::{sync::Arc, time::Duration};
+
+use mkcraft::{
+    PhysicsEnvironment, PhysicsObject, TileRegistry, World, WorldConfig,
+    akasha::generator::FlatGenerator,
+};
+
+// Player sized box, the position is at eye height like in the game
+const PLAYER_BOX: [[f32; 3]; 2] = [[-0.3, -1.64, -0.3], [0.3, 0.18, 0.3]];
+
+fn flat_environment(surface_height: i32) -> (Arc<World>, Arc<PhysicsEnvironment>) {
+    let generator = FlatGenerator {
+        surface_height,
+        dirt_depth: 2,
+    };
+    let mut world = World::with_generator(WorldConfig::default(), Box::new(generator));
+    let chunk_updates = world.register_chunk_update_listener();
+    let tile_registry = Arc::new(TileRegistry::new());
+    let environment = PhysicsEnvironment::new(chunk_updates, tile_registry);
+    (Arc::new(world), environment)
+}
+
+// Collision data is generated in the background, and chunks that aren't ready
+// yet are solid
+async fn wait_for_collision(environment: &PhysicsEnvironment, position: [f32; 3]) {
+    let [x, y, z] = position.map(|value| value.floor() as i32);
+    for _ in 0..500 {
+        let mut ready = true;
+        for dx in -1..=1 {
+            for dy in -2..=1 {
+                for dz in -1..=1 {
+                    ready &= environment.block_at(x + dx, y + dy, z + dz).is_some();
+                }
+            }
+        }
+        if ready {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("Collision data around {:?} never got ready", position);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn standing_on_chunk_boundaries_is_stable() {
+    // The grass top is y = 32, a chunk border, and the box straddles x = 0 and
+    // z = 0 where the chunk coordinates flip sign
+    let (world, environment) = flat_environment(31);
+    let tile_registry = Arc::new(TileRegistry::new());
+    let mut player = PhysicsObject::new([0.0, 32.0 + 1.64, 0.0], [0.0; 3], PLAYER_BOX);
+    environment
+        .ensure_for_object(world.clone(), tile_registry.clone(), &player)
+        .await;
+    wait_for_collision(&environment, player.position).await;
+
+    // Let it settle onto the ground first
+    for _ in 0..60 {
+        player.update(&environment, 1.0 / 60.0).await;
+    }
+    assert!(player.is_on_ground(&environment).await);
+    let settled = player.position;
+    assert!((settled[1] - (32.0 + 1.64)).abs() < 0.01, "{:?}", settled);
+    for tick in 0..600 {
+        player.update(&environment, 1.0 / 60.0).await;
+        assert_eq!(player.position, settled, "moved on tick {}", tick);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pushing_into_a_wall_on_a_chunk_boundary_is_stable() {
+    let (world, environment) = flat_environment(31);
+    let tile_registry = Arc::new(TileRegistry::new());
+    let mut player =
+        PhysicsObject::new([-0.5, 32.0 + 1.64, -0.5], [0.0; 3], PLAYER_BOX);
+    environment
+        .ensure_for_object(world.clone(), tile_registry.clone(), &player)
+        .await;
+    wait_for_collision(&environment, player.position).await;
+    // Walls along x = 0 and z = 0, the faces the player pushes against are the
+    // chunk borders
+    for offset in -3..3 {
+        for y in 32..35 {
+            World::set_block(&world, 0, y, offset, 1);
+            World::set_block(&world, offset, y, 0, 1);
+        }
+    }
+    for _ in 0..500 {
+        if environment.solid_at(-3, 34, 0).await && environment.solid_at(0, 34, 2).await
+        {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    let mut positions = Vec::new();
+    for _ in 0..300 {
+        player.velocity[0] = 4.0;
+        player.velocity[2] = 4.0;
+        player.update(&environment, 1.0 / 60.0).await;
+        positions.push(player.position);
+    }
+    let settled = positions[60];
+    assert!(settled[0] < -0.3 && settled[2] < -0.3, "{:?}", settled);
+    assert!(positions[60..].iter().all(|&position| position == settled));
+}
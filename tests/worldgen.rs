@@ -0,0 +1,93 @@
+//! Guards world generation against accidental changes. If a change to the
+//! terrain is intended, regenerate the golden hashes with
+//! `cargo test --test worldgen print_golden_hashes -- --ignored --nocapture`
+//! and paste them into GOLDEN_CHUNKS.
+
+use std::sync::Arc;
+
+use mkcraft::{
+    TileRegistry, World, WorldConfig, WorldView,
+    akasha::{ChunkNoises, generator::FlatGenerator},
+    tile::blocks,
+    world::{BlockId, ChunkData, MAX_LIGHT_LEVEL},
+};
+
+// Chunk positions and the hash of their generated block ids
+const GOLDEN_CHUNKS: [((i32, i32, i32), u64); 3] = [
+    ((0, 0, 0), 0xb80b9649ad8bcb76),
+    ((0, -1, 0), 0x21dabc6f8071cbce),
+    ((5, 0, -3), 0xd6ac26845eba4a31),
+];
+
+// FNV-1a, unlike the std hashers its output is fixed across Rust versions
+fn hash_blocks(blocks: &[BlockId]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for block in blocks {
+        for byte in block.to_le_bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    hash
+}
+
+fn generate_chunk((x, y, z): (i32, i32, i32)) -> ChunkData {
+    let config = WorldConfig::default();
+    let noises = ChunkNoises::new(x, y, z, &config.terrain);
+    ChunkData::new(x, y, z, &noises, &config)
+}
+
+#[test]
+fn chunks_match_golden_hashes() {
+    for (position, golden) in GOLDEN_CHUNKS {
+        let hash = hash_blocks(&generate_chunk(position).block_ids);
+        assert_eq!(hash, golden, "chunk {:?} changed", position);
+    }
+}
+
+#[test]
+#[ignore]
+fn print_golden_hashes() {
+    for (position, _) in GOLDEN_CHUNKS {
+        let hash = hash_blocks(&generate_chunk(position).block_ids);
+        println!("    ({:?}, {:#018x}),", position, hash);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn same_config_generates_same_blocks() {
+    let first = Arc::new(World::new(WorldConfig::default()));
+    let second = Arc::new(World::new(WorldConfig::default()));
+    // Spans chunk borders so decorations reaching across them are compared too
+    let (start, end) = ((-40, -40, -40), (40, 40, 40));
+    let first =
+        WorldView::from_range(&first, start.0, end.0, start.1, end.1, start.2, end.2)
+            .await;
+    let second =
+        WorldView::from_range(&second, start.0, end.0, start.1, end.1, start.2, end.2)
+            .await;
+    assert!(
+        first.data == second.data,
+        "Worlds with the same config differ"
+    );
+    assert!(first.metadata == second.metadata);
+}
+
+#[tokio::test]
+async fn stored_light_follows_block_edits() {
+    let world = Arc::new(World::with_generator(
+        WorldConfig::default(),
+        Box::new(FlatGenerator::default()),
+    ));
+    let tile_registry = TileRegistry::new();
+    // Right at the border, so the light spreads into the next chunk over
+    World::set_block(&world, 31, 40, 0, blocks::LAMP);
+    World::update_light(&world, &tile_registry, 1, 1, 0).await;
+    let view = WorldView::from_range(&world, 32, 32, 40, 40, 0, 0).await;
+    assert_eq!(view.get_light(32, 40, 0), MAX_LIGHT_LEVEL - 1);
+
+    World::break_block(&world, &tile_registry, 31, 40, 0);
+    World::update_light(&world, &tile_registry, 1, 1, 0).await;
+    let view = WorldView::from_range(&world, 32, 32, 40, 40, 0, 0).await;
+    assert_eq!(view.get_light(32, 40, 0), 0);
+}